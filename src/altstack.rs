@@ -0,0 +1,132 @@
+// altstack.rs
+
+// Copyright (C) 2026 Daniel Mueller <deso@posteo.net>
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+//! Management of alternate signal stacks.
+//!
+//! A thread overflowing its stack receives SIGSEGV once it touches the
+//! guard page below it. At this point there is no stack left to run a
+//! signal handler on, unless the thread has an alternate signal stack
+//! (see `sigaltstack(2)`) and the handler was installed with
+//! `SA_ONSTACK`.
+
+use std::cell::RefCell;
+use std::io::Error as IoError;
+use std::mem::zeroed;
+use std::os::raw::c_void;
+use std::ptr::null;
+use std::ptr::null_mut;
+
+use libc::mmap;
+use libc::mprotect;
+use libc::munmap;
+use libc::sigaltstack;
+use libc::stack_t;
+use libc::sysconf;
+use libc::MAP_ANONYMOUS;
+use libc::MAP_FAILED;
+use libc::MAP_PRIVATE;
+use libc::PROT_NONE;
+use libc::PROT_READ;
+use libc::PROT_WRITE;
+use libc::SIGSTKSZ;
+use libc::SS_DISABLE;
+use libc::_SC_PAGESIZE;
+
+use crate::check;
+use crate::Error;
+use crate::Str;
+use crate::WithCtx;
+
+
+/// The minimum size of the alternate signal stacks we set up.
+///
+/// `SIGSTKSZ` is notoriously small and does not account for, say, the
+/// AVX-512 state the kernel saves on the stack.
+const MIN_SIZE: usize = 64 * 1024;
+
+
+/// An alternate signal stack allocated by us.
+struct AltStack {
+  /// The start of the mapping, including the guard page.
+  base: *mut c_void,
+  /// The length of the mapping.
+  len: usize,
+}
+
+impl Drop for AltStack {
+  fn drop(&mut self) {
+    // Disable the stack before unmapping it, so that no signal
+    // handler can end up running on unmapped memory.
+    let mut stack = unsafe { zeroed::<stack_t>() };
+    stack.ss_flags = SS_DISABLE;
+    let _ = unsafe { sigaltstack(&stack, null_mut()) };
+    let _ = unsafe { munmap(self.base, self.len) };
+  }
+}
+
+
+thread_local! {
+  /// The alternate signal stack we set up for the thread, if any.
+  static ALTSTACK: RefCell<Option<AltStack>> = const { RefCell::new(None) };
+}
+
+
+/// Check whether the calling thread has an alternate signal stack.
+fn has_altstack() -> Result<bool, Error> {
+  let mut stack = unsafe { zeroed::<stack_t>() };
+  check(unsafe { sigaltstack(null(), &mut stack) }, -1)?;
+  Ok(stack.ss_flags & SS_DISABLE == 0)
+}
+
+
+/// Set up an alternate signal stack for the calling thread, unless it
+/// has one already.
+///
+/// The stack is released once the thread exits.
+pub(crate) fn install() -> Result<(), (Str, Error)> {
+  if has_altstack().ctx(|| "failed to inquire alternate signal stack")? {
+    return Ok(())
+  }
+
+  let page_size = unsafe { sysconf(_SC_PAGESIZE) };
+  let page_size = if page_size > 0 {
+    page_size as usize
+  } else {
+    4096
+  };
+  let size = SIGSTKSZ.max(MIN_SIZE);
+  // Place a guard page below the stack, so that overflowing it does not
+  // silently corrupt adjacent memory.
+  let len = page_size + size;
+  let base = unsafe {
+    mmap(
+      null_mut(),
+      len,
+      PROT_READ | PROT_WRITE,
+      MAP_PRIVATE | MAP_ANONYMOUS,
+      -1,
+      0,
+    )
+  };
+  if base == MAP_FAILED {
+    return Err(Error::from(IoError::last_os_error()))
+      .ctx(|| "failed to allocate alternate signal stack")
+  }
+  // From here on the mapping is released on error.
+  let altstack = AltStack { base, len };
+
+  check(unsafe { mprotect(base, page_size, PROT_NONE) }, -1)
+    .ctx(|| "failed to set up guard page for alternate signal stack")?;
+
+  let mut stack = unsafe { zeroed::<stack_t>() };
+  stack.ss_sp = unsafe { base.cast::<u8>().add(page_size) }.cast();
+  stack.ss_size = size;
+  stack.ss_flags = 0;
+  check(unsafe { sigaltstack(&stack, null_mut()) }, -1)
+    .ctx(|| "failed to install alternate signal stack")?;
+
+  ALTSTACK.with(|slot| *slot.borrow_mut() = Some(altstack));
+  Ok(())
+}
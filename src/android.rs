@@ -0,0 +1,30 @@
+// android.rs
+
+// Copyright (C) 2026 Daniel Mueller <deso@posteo.net>
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+//! Cooperation with Android's crash handling.
+//!
+//! On Android, crashing processes are handled by debuggerd, which has
+//! `tombstoned` write a tombstone (into `/data/tombstones`) instead of
+//! the kernel writing a core file.
+
+use std::ffi::CString;
+use std::panic::Location;
+
+use libc::android_set_abort_message;
+
+
+/// Set the abort message recorded in the tombstone for the panic with
+/// the given message and location.
+///
+/// Only the first message set in a process is retained.
+pub(crate) fn set_abort_message(message: &str, location: Option<&Location<'_>>) {
+  let message = match location {
+    Some(location) => format!("panicked at {}:\n{}", location, message),
+    None => format!("panicked:\n{}", message),
+  };
+  // Interior NUL bytes would truncate the message, so strip them.
+  let message = CString::new(message.replace('\0', "")).unwrap_or_default();
+  let () = unsafe { android_set_abort_message(message.as_ptr()) };
+}
@@ -0,0 +1,224 @@
+// atfork.rs
+
+// Copyright (C) 2026 Daniel Mueller <deso@posteo.net>
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+//! Re-verification of the dump configuration in forked children.
+//!
+//! Daemonizing processes commonly fork, change their working directory,
+//! and drop privileges, all of which can invalidate assumptions made at
+//! registration time. The checks in here are performed in the child
+//! right after a fork and report problems on stderr.
+
+use std::ffi::CStr;
+use std::ffi::CString;
+use std::io::Error as IoError;
+use std::io::ErrorKind;
+use std::mem::zeroed;
+use std::os::unix::ffi::OsStrExt;
+use std::path::Path;
+use std::ptr::null_mut;
+use std::sync::atomic::AtomicBool;
+use std::sync::atomic::AtomicPtr;
+use std::sync::atomic::AtomicUsize;
+use std::sync::atomic::Ordering;
+use std::thread::yield_now;
+
+use libc::access;
+use libc::prctl;
+use libc::pthread_atfork;
+use libc::stat;
+use libc::write;
+use libc::PR_GET_DUMPABLE;
+use libc::STDERR_FILENO;
+use libc::W_OK;
+use libc::X_OK;
+
+use crate::Error;
+use crate::Str;
+use crate::WithCtx;
+
+
+/// The path of the current working directory, as a C string.
+const CURRENT_DIR: &[u8] = b".\0";
+
+
+/// The state captured at registration time and checked against.
+struct State {
+  /// The dump directory.
+  dir: CString,
+  /// The device & inode of the working directory, if the dump directory
+  /// is relative.
+  cwd: Option<(u64, u64)>,
+}
+
+/// The state to verify against in forked children.
+///
+/// The pointer is set once per registration. A replaced pointee is only
+/// freed once no fork that may have copied it is in progress anymore
+/// (see [`FORKING`]).
+static STATE: AtomicPtr<State> = AtomicPtr::new(null_mut());
+/// The number of forks currently in progress in this process.
+static FORKING: AtomicUsize = AtomicUsize::new(0);
+/// Whether our fork handler has been installed already.
+static INSTALLED: AtomicBool = AtomicBool::new(false);
+
+
+/// Retrieve the device & inode of the given path.
+fn identity(path: &CStr) -> Option<(u64, u64)> {
+  let mut buf = unsafe { zeroed::<stat>() };
+  if unsafe { stat(path.as_ptr(), &mut buf) } != 0 {
+    return None
+  }
+  #[allow(clippy::unnecessary_cast)]
+  Some((buf.st_dev as u64, buf.st_ino as u64))
+}
+
+
+/// Write the given message parts to stderr.
+///
+/// This function is async-signal-safe.
+fn report(parts: &[&[u8]]) {
+  let _ = parts.iter().try_for_each(|part| {
+    let result = unsafe { write(STDERR_FILENO, part.as_ptr().cast(), part.len()) };
+    if result < 0 {
+      Err(())
+    } else {
+      Ok(())
+    }
+  });
+}
+
+
+/// The handler invoked in the parent before a fork.
+unsafe extern "C" fn enter_fork() {
+  let _count = FORKING.fetch_add(1, Ordering::SeqCst);
+}
+
+
+/// The handler invoked in the parent after a fork.
+unsafe extern "C" fn leave_fork() {
+  let _count = FORKING.fetch_sub(1, Ordering::SeqCst);
+}
+
+
+/// Free `state`, which has to be unreachable through [`STATE`], once no
+/// fork may have copied it in a state of flux anymore.
+fn retire(state: *mut State) {
+  if state.is_null() {
+    return
+  }
+
+  // Forked children work on a copy of our memory, but the copy is not
+  // taken atomically with respect to other threads. Freeing while a
+  // fork is in progress could hand a child a dangling pointer.
+  while FORKING.load(Ordering::SeqCst) != 0 {
+    let () = yield_now();
+  }
+  let _state = unsafe { Box::from_raw(state) };
+}
+
+
+/// The handler invoked in the child after a fork.
+///
+/// We are a forked copy of a potentially multi-threaded program and
+/// have to restrict ourselves to async-signal-safe functionality.
+unsafe extern "C" fn verify_child() {
+  // The fork we are the result of is no longer in progress and no
+  // other thread exists to run one in this process.
+  let () = FORKING.store(0, Ordering::SeqCst);
+
+  let state = STATE.load(Ordering::SeqCst);
+  let state = if let Some(state) = unsafe { state.as_ref() } {
+    state
+  } else {
+    return
+  };
+
+  let dir = state.dir.as_c_str();
+  if unsafe { access(dir.as_ptr(), W_OK | X_OK) } != 0 {
+    report(&[
+      b"coredump: dump directory ",
+      dir.to_bytes(),
+      b" is not accessible in forked child\n",
+    ]);
+  }
+
+  if let Some(cwd) = state.cwd {
+    let current = unsafe { CStr::from_bytes_with_nul_unchecked(CURRENT_DIR) };
+    if identity(current) != Some(cwd) {
+      report(&[
+        b"coredump: working directory changed in forked child; relative dump directory ",
+        dir.to_bytes(),
+        b" may now refer to a different location\n",
+      ]);
+    }
+  }
+
+  // A value of 2 permits dumps readable by root only and an error
+  // leaves us none the wiser, so only complain about a clear "no".
+  if unsafe { prctl(PR_GET_DUMPABLE) } == 0 {
+    report(&[b"coredump: forked child is not dumpable; no core will be created\n"]);
+  }
+}
+
+
+/// The changes applied by [`install`], for rolling them back.
+///
+/// Dropping the object commits the changes.
+pub(crate) struct Undo {
+  /// The state that was in effect before.
+  state: *mut State,
+}
+
+impl Undo {
+  /// Roll back the changes.
+  ///
+  /// Fork handlers cannot be removed, but without state ours does
+  /// nothing.
+  pub(crate) fn undo(mut self) {
+    let state = STATE.swap(self.state, Ordering::SeqCst);
+    self.state = null_mut();
+    let () = retire(state);
+  }
+}
+
+impl Drop for Undo {
+  fn drop(&mut self) {
+    let () = retire(self.state);
+  }
+}
+
+
+/// Install a fork handler verifying the dump configuration for the
+/// given dump directory in forked children.
+pub(crate) fn install(dir: &Path) -> Result<Undo, (Str, Error)> {
+  let path = CString::new(dir.as_os_str().as_bytes())
+    .map_err(|err| Error::from(IoError::new(ErrorKind::InvalidInput, err)))
+    .ctx(|| format!("dump directory {} is not a valid path", dir.display()))?;
+  let cwd = if dir.is_relative() {
+    let current = unsafe { CStr::from_bytes_with_nul_unchecked(CURRENT_DIR) };
+    identity(current)
+  } else {
+    None
+  };
+
+  let state = Box::new(State { dir: path, cwd });
+  let previous = STATE.swap(Box::into_raw(state), Ordering::SeqCst);
+
+  if !INSTALLED.swap(true, Ordering::AcqRel) {
+    let result = unsafe {
+      pthread_atfork(Some(enter_fork), Some(leave_fork), Some(verify_child))
+    };
+    if result != 0 {
+      // Reinstate the state of the previous registration, if any, so
+      // that a failed registration leaves no trace.
+      let state = STATE.swap(previous, Ordering::SeqCst);
+      INSTALLED.store(false, Ordering::Release);
+      let () = retire(state);
+      Err(Error::from(IoError::from_raw_os_error(result)))
+        .ctx(|| "failed to install fork handler")?;
+    }
+  }
+  Ok(Undo { state: previous })
+}
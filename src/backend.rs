@@ -0,0 +1,68 @@
+// backend.rs
+
+// Copyright (C) 2026 Daniel Mueller <deso@posteo.net>
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+//! The system interface used for adjusting the core file size limit
+//! and for sending signals.
+//!
+//! The backend is selected at compile time, via the [`Sys`] alias. All
+//! functionality is used from within signal handlers and forked
+//! processes and so has to be async-signal-safe.
+//!
+//! Additional backends implement [`Backend`] and get selected by
+//! pointing [`Sys`] at them conditional on a cargo feature. As the
+//! selection happens at compile time, errors are always reported as
+//! [`Error`], irrespective of the backend in use.
+
+use std::os::raw::c_int;
+
+use libc::kill;
+use libc::pid_t;
+use libc::RLIMIT_CORE;
+
+use crate::check;
+use crate::resource::getrlimit;
+use crate::resource::rlimit;
+use crate::resource::setrlimit;
+use crate::Error;
+
+
+/// A provider of the system functionality we rely on.
+pub(crate) trait Backend {
+  /// Retrieve the core file size limit.
+  fn core_limit() -> Result<rlimit, Error>;
+
+  /// Set the core file size limit.
+  fn set_core_limit(limit: &rlimit) -> Result<(), Error>;
+
+  /// Send `signal` to the process with ID `pid`.
+  fn kill(pid: pid_t, signal: c_int) -> Result<(), Error>;
+}
+
+
+/// A backend calling into the C library by means of the `libc` crate.
+pub(crate) struct Libc;
+
+impl Backend for Libc {
+  fn core_limit() -> Result<rlimit, Error> {
+    let mut limit = rlimit {
+      rlim_cur: 0,
+      rlim_max: 0,
+    };
+    let () = check(unsafe { getrlimit(RLIMIT_CORE, &mut limit) }, -1)?;
+    Ok(limit)
+  }
+
+  fn set_core_limit(limit: &rlimit) -> Result<(), Error> {
+    check(unsafe { setrlimit(RLIMIT_CORE, limit) }, -1)
+  }
+
+  fn kill(pid: pid_t, signal: c_int) -> Result<(), Error> {
+    check(unsafe { kill(pid, signal) }, -1)
+  }
+}
+
+
+/// The backend in use.
+pub(crate) type Sys = Libc;
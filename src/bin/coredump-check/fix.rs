@@ -0,0 +1,151 @@
+// fix.rs
+
+// Copyright (C) 2026 Daniel Mueller <deso@posteo.net>
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+//! Adjustment of system configuration in order to make dumping core
+//! work.
+
+use std::fmt::Display;
+use std::fmt::Formatter;
+use std::fmt::Result as FmtResult;
+use std::fs::set_permissions;
+use std::fs::write;
+use std::fs::Permissions;
+use std::io::Result as IoResult;
+use std::os::unix::fs::PermissionsExt as _;
+use std::path::Path;
+use std::path::PathBuf;
+
+use coredump::Report;
+
+/// The `core_pattern` we install if the current one is unusable. It is
+/// the kernel's default and makes core files end up in the dump
+/// directory.
+const CORE_PATTERN: &str = "core";
+/// The `core_pipe_limit` we install if core files are piped to a
+/// helper without any limit.
+const CORE_PIPE_LIMIT: u32 = 16;
+/// The permissions of a temp directory (world writable with sticky
+/// bit).
+const TEMP_DIR_MODE: u32 = 0o1777;
+
+
+/// A change to the system's configuration.
+#[derive(Debug)]
+pub(crate) enum Change {
+  /// Write a new value to a sysctl file below `/proc/sys`.
+  Sysctl {
+    path: &'static str,
+    from: String,
+    to: String,
+  },
+  /// Change the permissions of a directory.
+  Mode { path: PathBuf, from: u32, to: u32 },
+}
+
+impl Change {
+  /// Apply the change.
+  pub(crate) fn apply(&self) -> IoResult<()> {
+    match self {
+      Self::Sysctl { path, to, .. } => write(path, to),
+      Self::Mode { path, to, .. } => set_permissions(path, Permissions::from_mode(*to)),
+    }
+  }
+}
+
+impl Display for Change {
+  fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+    match self {
+      Self::Sysctl { path, from, to } => write!(f, "{}: {:?} -> {:?}", path, from, to),
+      Self::Mode { path, from, to } => {
+        write!(f, "{}: mode {:04o} -> {:04o}", path.display(), from, to)
+      },
+    }
+  }
+}
+
+
+/// Check whether the given core pattern is unusable for file based
+/// core dumps, i.e., because it is empty or refers to a non-existent
+/// directory.
+fn is_unusable_pattern(pattern: &str) -> bool {
+  if pattern.is_empty() {
+    return true
+  }
+
+  if pattern.starts_with('|') {
+    return false
+  }
+
+  let pattern = Path::new(pattern);
+  match pattern.parent() {
+    Some(parent) if pattern.is_absolute() => !parent.is_dir(),
+    _ => false,
+  }
+}
+
+
+/// Determine the changes required to make dumping core work, based on
+/// the given report.
+pub(crate) fn plan(report: &Report) -> Vec<Change> {
+  let mut changes = Vec::new();
+
+  let mut pattern = report.core_pattern.clone();
+  if let Some(from) = &pattern {
+    if is_unusable_pattern(from) {
+      changes.push(Change::Sysctl {
+        path: "/proc/sys/kernel/core_pattern",
+        from: from.clone(),
+        to: CORE_PATTERN.to_string(),
+      });
+      pattern = Some(CORE_PATTERN.to_string());
+    }
+  }
+
+  let pipes = pattern
+    .as_ref()
+    .map(|pattern| pattern.starts_with('|'))
+    .unwrap_or(false);
+  let absolute = pattern
+    .as_ref()
+    .map(|pattern| pattern.starts_with('/'))
+    .unwrap_or(false);
+
+  // Without a limit the kernel does not wait for the helper to finish,
+  // meaning that it may not be able to inspect the crashed process.
+  if let (true, Some(0)) = (pipes, report.core_pipe_limit) {
+    changes.push(Change::Sysctl {
+      path: "/proc/sys/kernel/core_pipe_limit",
+      from: "0".to_string(),
+      to: CORE_PIPE_LIMIT.to_string(),
+    });
+  }
+
+  // Processes that changed credentials (e.g., daemons dropping
+  // privileges) are not dumpable by default. The "suidsafe" mode
+  // remedies that, but the kernel only honors it when core files are
+  // written to an absolute path or piped to a helper.
+  if let (true, Some(0)) = (pipes || absolute, report.suid_dumpable) {
+    changes.push(Change::Sysctl {
+      path: "/proc/sys/fs/suid_dumpable",
+      from: "0".to_string(),
+      to: "2".to_string(),
+    });
+  }
+
+  if let Some(dir) = &report.dump_dir {
+    let is_temp_dir = dir == Path::new("/tmp") || dir == Path::new("/var/tmp");
+    if let (true, Ok(metadata)) = (is_temp_dir, dir.metadata()) {
+      let from = metadata.permissions().mode() & 0o7777;
+      if from != TEMP_DIR_MODE {
+        changes.push(Change::Mode {
+          path: dir.clone(),
+          from,
+          to: TEMP_DIR_MODE,
+        });
+      }
+    }
+  }
+  changes
+}
@@ -0,0 +1,103 @@
+// main.rs
+
+// Copyright (C) 2026 Daniel Mueller <deso@posteo.net>
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+//! A program checking whether the system is configured for dumping
+//! core.
+
+mod fix;
+
+use std::env::args;
+use std::process::exit;
+
+use libc::geteuid;
+
+use coredump::Report;
+
+const USAGE: &str = "Usage: coredump-check [--json] [--fix [--dry-run]]
+
+Check whether the system is configured for dumping core.
+
+Options:
+  --json      Emit the report as JSON
+  --fix       Adjust system configuration to make dumping core work
+              (requires root privileges)
+  --dry-run   Only print the changes --fix would make
+  -h, --help  Print this help and exit
+
+The exit status is 0 if no problems were found, 1 if problems were
+found, and 2 on usage errors.";
+
+
+/// Adjust the system's configuration, printing each change on stderr.
+fn fix(dry_run: bool) {
+  let changes = fix::plan(&Report::collect());
+  if changes.is_empty() {
+    eprintln!("nothing to fix");
+    return
+  }
+
+  if dry_run {
+    for change in changes {
+      eprintln!("would change {}", change);
+    }
+    return
+  }
+
+  if unsafe { geteuid() } != 0 {
+    eprintln!("--fix requires root privileges");
+    exit(1)
+  }
+
+  for change in changes {
+    eprintln!("changing {}", change);
+    if let Err(err) = change.apply() {
+      eprintln!("failed to apply change: {}", err);
+      exit(1)
+    }
+  }
+}
+
+
+fn main() {
+  let mut json = false;
+  let mut fix = false;
+  let mut dry_run = false;
+
+  for arg in args().skip(1) {
+    match arg.as_str() {
+      "--json" => json = true,
+      "--fix" => fix = true,
+      "--dry-run" => dry_run = true,
+      "-h" | "--help" => {
+        println!("{}", USAGE);
+        exit(0)
+      },
+      _ => {
+        eprintln!("unrecognized argument: {}\n\n{}", arg, USAGE);
+        exit(2)
+      },
+    }
+  }
+
+  if dry_run && !fix {
+    eprintln!("--dry-run requires --fix\n\n{}", USAGE);
+    exit(2)
+  }
+
+  if fix {
+    self::fix(dry_run);
+  }
+
+  let report = Report::collect();
+  if json {
+    println!("{}", report.to_json());
+  } else {
+    println!("{}", report);
+  }
+
+  if !report.problems().is_empty() {
+    exit(1)
+  }
+}
@@ -0,0 +1,91 @@
+// buffer.rs
+
+// Copyright (C) 2026 Daniel Mueller <deso@posteo.net>
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+//! Formatting of text without allocating, for use in signal handlers.
+
+use std::ffi::CStr;
+
+
+/// A fixed-capacity buffer for assembling text.
+///
+/// All operations are async-signal-safe. Content that does not fit is
+/// dropped.
+pub(crate) struct Buffer<const N: usize> {
+  /// The buffer's storage.
+  data: [u8; N],
+  /// The number of bytes in use.
+  len: usize,
+  /// Whether content got dropped for the lack of space.
+  truncated: bool,
+}
+
+impl<const N: usize> Buffer<N> {
+  /// Create a new, empty buffer.
+  pub(crate) const fn new() -> Self {
+    Self {
+      data: [0; N],
+      len: 0,
+      truncated: false,
+    }
+  }
+
+  /// Append the given bytes.
+  pub(crate) fn push(&mut self, bytes: &[u8]) -> &mut Self {
+    let len = bytes.len().min(N - self.len);
+    self.data[self.len..self.len + len].copy_from_slice(&bytes[..len]);
+    self.len += len;
+    self.truncated |= len < bytes.len();
+    self
+  }
+
+  /// Append `value` in the given base (at most 16).
+  fn push_int(&mut self, mut value: u64, base: u64) -> &mut Self {
+    const DIGITS: &[u8; 16] = b"0123456789abcdef";
+    // 64 binary digits is the most any base can produce.
+    let mut digits = [0u8; 64];
+    let mut idx = digits.len();
+    loop {
+      idx -= 1;
+      digits[idx] = DIGITS[(value % base) as usize];
+      value /= base;
+      if value == 0 {
+        break
+      }
+    }
+    self.push(&digits[idx..])
+  }
+
+  /// Append `value` in decimal notation.
+  pub(crate) fn push_dec(&mut self, value: u64) -> &mut Self {
+    self.push_int(value, 10)
+  }
+
+  /// Append `value` in hexadecimal notation, prefixed with `0x`.
+  #[cfg(target_os = "linux")]
+  pub(crate) fn push_hex(&mut self, value: u64) -> &mut Self {
+    self.push(b"0x").push_int(value, 16)
+  }
+
+  /// Retrieve the contents of the buffer.
+  pub(crate) fn as_bytes(&self) -> &[u8] {
+    &self.data[..self.len]
+  }
+
+  /// NUL-terminate the buffer, retrieving its contents as a C string.
+  ///
+  /// `None` is returned if the buffer got truncated or contains NUL
+  /// bytes.
+  pub(crate) fn terminate(&mut self) -> Option<&CStr> {
+    if self.data[..self.len].contains(&0) {
+      return None
+    }
+    let _buf = self.push(b"\0");
+    if self.truncated {
+      return None
+    }
+    CStr::from_bytes_with_nul(&self.data[..self.len]).ok()
+  }
+}
+
@@ -0,0 +1,572 @@
+// config.rs
+
+// Copyright (C) 2026 Daniel Mueller <deso@posteo.net>
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+//! System configuration relevant to dumping core, as cached ahead of
+//! time.
+
+use std::ffi::CStr;
+#[cfg(not(any(target_os = "macos", target_os = "netbsd", target_os = "openbsd")))]
+use std::os::raw::c_char;
+use std::os::raw::c_int;
+use std::path::Path;
+use std::path::PathBuf;
+use std::process::id as pid;
+
+#[cfg(not(any(target_os = "macos", target_os = "netbsd", target_os = "openbsd")))]
+use libc::gethostname;
+
+#[cfg(any(target_os = "macos", target_os = "freebsd", target_os = "netbsd"))]
+use crate::sysctl;
+
+
+/// Read and parse a value from a file, typically below `/proc`.
+#[cfg(target_os = "linux")]
+pub(crate) fn read_value<T>(path: &str) -> Option<T>
+where
+  T: std::str::FromStr,
+{
+  std::fs::read_to_string(path).ok()?.trim().parse().ok()
+}
+
+
+/// Retrieve the system's host name.
+#[cfg(not(any(target_os = "macos", target_os = "netbsd", target_os = "openbsd")))]
+fn hostname() -> Option<String> {
+  let mut buffer = [0 as c_char; 256];
+  if unsafe { gethostname(buffer.as_mut_ptr(), buffer.len()) } != 0 {
+    return None
+  }
+  // POSIX does not guarantee NUL termination on truncation.
+  buffer[buffer.len() - 1] = 0;
+  let name = unsafe { CStr::from_ptr(buffer.as_ptr()) };
+  name.to_str().ok().map(str::to_string)
+}
+
+
+/// Details of a crash that are only known once it happens, as used for
+/// expanding the corresponding pattern specifiers.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct CrashDetails {
+  /// The time of the dump, in seconds since the Epoch.
+  #[cfg(not(any(
+    target_os = "macos",
+    target_os = "freebsd",
+    target_os = "netbsd",
+    target_os = "openbsd"
+  )))]
+  time: Option<u64>,
+  /// The name of the crashing thread.
+  #[cfg(not(any(
+    target_os = "macos",
+    target_os = "freebsd",
+    target_os = "netbsd",
+    target_os = "openbsd",
+    target_os = "illumos",
+    target_os = "solaris"
+  )))]
+  thread: Option<String>,
+}
+
+impl CrashDetails {
+  /// Describe a crash of the calling thread at `time`, given in seconds
+  /// since the Epoch.
+  pub(crate) fn current_thread(time: Option<u64>) -> Self {
+    // The BSD kernels do not support a specifier for the time of the
+    // dump.
+    #[cfg(any(
+      target_os = "macos",
+      target_os = "freebsd",
+      target_os = "netbsd",
+      target_os = "openbsd"
+    ))]
+    let _ = time;
+
+    Self {
+      #[cfg(not(any(
+        target_os = "macos",
+        target_os = "freebsd",
+        target_os = "netbsd",
+        target_os = "openbsd"
+      )))]
+      time,
+      #[cfg(not(any(
+        target_os = "macos",
+        target_os = "freebsd",
+        target_os = "netbsd",
+        target_os = "openbsd",
+        target_os = "illumos",
+        target_os = "solaris"
+      )))]
+      thread: thread_name(),
+    }
+  }
+}
+
+
+/// Retrieve the name of the calling thread, as the kernel reports it
+/// for `%e`.
+#[cfg(not(any(
+  target_os = "macos",
+  target_os = "freebsd",
+  target_os = "netbsd",
+  target_os = "openbsd",
+  target_os = "illumos",
+  target_os = "solaris"
+)))]
+fn thread_name() -> Option<String> {
+  #[cfg(any(target_os = "linux", target_os = "android"))]
+  let name = {
+    let mut buffer = [0 as c_char; 16];
+    if unsafe { libc::prctl(libc::PR_GET_NAME, buffer.as_mut_ptr()) } != 0 {
+      return None
+    }
+    let name = unsafe { CStr::from_ptr(buffer.as_ptr()) };
+    // The kernel escapes slashes, so as to not create sub-directories.
+    name.to_str().ok().map(|name| name.replace('/', "!"))
+  };
+  #[cfg(not(any(target_os = "linux", target_os = "android")))]
+  let name = None;
+
+  name
+}
+
+
+/// Expand the pattern specifier `c`.
+///
+/// `None` is returned if the specifier is unknown or its value depends
+/// on the crash itself (e.g., `%t`, the time of the dump, or `%e`, the
+/// name of the crashing thread) and is not provided by `crash`.
+#[cfg(not(any(
+  target_os = "macos",
+  target_os = "freebsd",
+  target_os = "netbsd",
+  target_os = "openbsd",
+  target_os = "illumos",
+  target_os = "solaris"
+)))]
+fn specifier(c: char, signal: c_int, pid: u32, crash: &CrashDetails) -> Option<String> {
+  match c {
+    'p' => Some(pid.to_string()),
+    'u' => Some(unsafe { libc::getuid() }.to_string()),
+    'g' => Some(unsafe { libc::getgid() }.to_string()),
+    's' => Some(signal.to_string()),
+    'h' => hostname(),
+    't' => crash.time.map(|time| time.to_string()),
+    'e' => crash.thread.clone(),
+    _ => None,
+  }
+}
+
+/// Expand the `kern.corefile` specifier `c`.
+///
+/// `None` is returned if the specifier is unknown or its value depends
+/// on the crash itself (e.g., FreeBSD's `%I`, an index based on the
+/// core files present at the time of the dump).
+#[cfg(any(target_os = "macos", target_os = "freebsd"))]
+fn specifier(c: char, _signal: c_int, pid: u32, _crash: &CrashDetails) -> Option<String> {
+  /// The length the kernel truncates process names to
+  /// (`MAXCOMLEN`).
+  #[cfg(target_os = "macos")]
+  const MAXCOMLEN: usize = 16;
+  #[cfg(target_os = "freebsd")]
+  const MAXCOMLEN: usize = 19;
+
+  match c {
+    #[cfg(target_os = "freebsd")]
+    'H' => hostname(),
+    'N' => process_name(MAXCOMLEN),
+    'P' => Some(pid.to_string()),
+    'U' => Some(unsafe { libc::getuid() }.to_string()),
+    _ => None,
+  }
+}
+
+
+/// Expand the core name specifier `c`, as supported by the NetBSD
+/// (`kern.defcorename`) and OpenBSD kernels.
+///
+/// `None` is returned if the specifier is unknown or its value depends
+/// on the crash itself.
+#[cfg(any(target_os = "netbsd", target_os = "openbsd"))]
+fn specifier(c: char, _signal: c_int, pid: u32, _crash: &CrashDetails) -> Option<String> {
+  /// The length the kernel truncates process names to
+  /// (`MAXCOMLEN`).
+  #[cfg(target_os = "netbsd")]
+  const MAXCOMLEN: usize = 16;
+  #[cfg(target_os = "openbsd")]
+  const MAXCOMLEN: usize = 23;
+
+  match c {
+    'n' => process_name(MAXCOMLEN),
+    'p' => Some(pid.to_string()),
+    // The login name associated with the process' session.
+    'u' => {
+      let name = unsafe { libc::getlogin() };
+      if name.is_null() {
+        return None
+      }
+      let name = unsafe { CStr::from_ptr(name) };
+      name.to_str().ok().map(str::to_string)
+    },
+    _ => None,
+  }
+}
+
+
+/// Expand the `coreadm(8)` pattern specifier `c`.
+///
+/// `None` is returned if the specifier is unknown or its value depends
+/// on the crash itself (e.g., `%t`, the time of the dump) and is not
+/// provided by `crash`.
+#[cfg(any(target_os = "illumos", target_os = "solaris"))]
+fn specifier(c: char, _signal: c_int, pid: u32, crash: &CrashDetails) -> Option<String> {
+  /// The length the kernel truncates process names to
+  /// (`MAXCOMLEN`).
+  const MAXCOMLEN: usize = 16;
+
+  match c {
+    'd' => {
+      let exe = std::env::current_exe().ok()?;
+      exe.parent()?.to_str().map(str::to_string)
+    },
+    'f' => process_name(MAXCOMLEN),
+    'g' => Some(unsafe { libc::getegid() }.to_string()),
+    'm' => {
+      let mut name = unsafe { std::mem::zeroed::<libc::utsname>() };
+      if unsafe { libc::uname(&mut name) } < 0 {
+        return None
+      }
+      let machine = unsafe { CStr::from_ptr(name.machine.as_ptr()) };
+      machine.to_str().ok().map(str::to_string)
+    },
+    'n' => hostname(),
+    'p' => Some(pid.to_string()),
+    't' => crash.time.map(|time| time.to_string()),
+    'u' => Some(unsafe { libc::geteuid() }.to_string()),
+    _ => None,
+  }
+}
+
+
+/// Retrieve the name of the process, truncated to `max_len` bytes.
+///
+/// The kernel's notion is that of the executable's file name. We
+/// approximate it by the name the program was invoked with.
+#[cfg(any(
+  target_os = "macos",
+  target_os = "freebsd",
+  target_os = "netbsd",
+  target_os = "openbsd",
+  target_os = "illumos",
+  target_os = "solaris"
+))]
+fn process_name(max_len: usize) -> Option<String> {
+  let name = unsafe { libc::getprogname() };
+  if name.is_null() {
+    return None
+  }
+  let name = unsafe { CStr::from_ptr(name) }.to_bytes();
+  let name = &name[..name.len().min(max_len)];
+  String::from_utf8(name.to_vec()).ok()
+}
+
+
+/// Expand the specifiers in `pattern`.
+///
+/// `None` is returned if the pattern contains a specifier whose value
+/// cannot be known ahead of time and is not provided by `crash` (see
+/// `specifier`). `signal` is the signal core is dumped with and `pid`
+/// the ID of the dumping process.
+fn expand(pattern: &str, signal: c_int, pid: u32, crash: &CrashDetails) -> Option<String> {
+  let mut expanded = String::with_capacity(pattern.len());
+  let mut chars = pattern.chars();
+
+  while let Some(c) = chars.next() {
+    if c != '%' {
+      expanded.push(c);
+      continue
+    }
+
+    match chars.next() {
+      Some('%') => expanded.push('%'),
+      // A trailing lone `%` is dropped by the kernel.
+      None => (),
+      Some(c) => expanded += &specifier(c, signal, pid, crash)?,
+    }
+  }
+  Some(expanded)
+}
+
+
+/// Read the kernel's core file name pattern.
+///
+/// On Linux, that is the contents of `/proc/sys/kernel/core_pattern`.
+/// macOS and FreeBSD provide it via the `kern.corefile` sysctl, NetBSD
+/// via the per-process `proc.curproc.corename` one (defaulting to
+/// `kern.defcorename`). OpenBSD always names core files after the
+/// program. On illumos and Solaris, the pattern is managed by
+/// `coreadm(8)`.
+pub(crate) fn core_pattern() -> Option<String> {
+  #[cfg(target_os = "linux")]
+  let pattern = std::fs::read_to_string("/proc/sys/kernel/core_pattern")
+    .ok()
+    .map(|pattern| pattern.trim_end().to_string());
+  #[cfg(any(target_os = "macos", target_os = "freebsd"))]
+  let pattern = sysctl::string("kern.corefile");
+  #[cfg(target_os = "netbsd")]
+  let pattern =
+    sysctl::string("proc.curproc.corename").or_else(|| sysctl::string("kern.defcorename"));
+  #[cfg(target_os = "openbsd")]
+  let pattern = Some("%n.core".to_string());
+  #[cfg(any(target_os = "illumos", target_os = "solaris"))]
+  let pattern = coreadm_pattern();
+  #[cfg(not(any(
+    target_os = "linux",
+    target_os = "macos",
+    target_os = "freebsd",
+    target_os = "netbsd",
+    target_os = "openbsd",
+    target_os = "illumos",
+    target_os = "solaris"
+  )))]
+  let pattern = None;
+
+  pattern
+}
+
+
+/// Retrieve the path of the helper program core files are piped to,
+/// if the given core file name pattern pipes them to one.
+pub(crate) fn pipe_helper(pattern: &str) -> Option<&str> {
+  pattern.strip_prefix('|')?.split_whitespace().next()
+}
+
+
+/// Determine the version of the Windows Subsystem for Linux the system
+/// runs under, if any.
+///
+/// WSL1 translates system calls and reports a kernel release ending in
+/// `-Microsoft`. WSL2 runs an actual Linux kernel, but one whose
+/// release contains `microsoft` in lower case.
+#[cfg(target_os = "linux")]
+pub(crate) fn wsl_version() -> Option<u32> {
+  let version = std::fs::read_to_string("/proc/version").ok()?;
+  if version.contains("Microsoft") {
+    Some(1)
+  } else if version.contains("microsoft") {
+    Some(2)
+  } else {
+    None
+  }
+}
+
+
+/// Retrieve the core file pattern in effect for the calling process,
+/// as reported by `coreadm(8)`.
+///
+/// Per-process core files are named according to the process' own
+/// pattern, which is inherited from its parent. The global pattern,
+/// which is configured system wide, only matters if per-process core
+/// dumps are disabled. `None` is returned if core dumps are disabled
+/// altogether.
+#[cfg(any(target_os = "illumos", target_os = "solaris"))]
+fn coreadm_pattern() -> Option<String> {
+  use std::process::Command;
+
+  let coreadm = |args: &[String]| {
+    Command::new("coreadm")
+      .args(args)
+      .output()
+      .ok()
+      .filter(|output| output.status.success())
+      .and_then(|output| String::from_utf8(output.stdout).ok())
+  };
+
+  // Global settings are reported as lines of the form
+  // `<setting>: <value>`.
+  let global = coreadm(&[]).unwrap_or_default();
+  let setting = |name: &str| {
+    global.lines().find_map(|line| {
+      let value = line.trim_start().strip_prefix(name)?.strip_prefix(':')?;
+      Some(value.trim().to_string())
+    })
+  };
+
+  if setting("per-process core dumps").as_deref() != Some("disabled") {
+    // The process' pattern is reported as `<pid>:\t<pattern>`.
+    let process = coreadm(&[pid().to_string()])?;
+    let (_, pattern) = process.split_once(':')?;
+    Some(pattern.trim().to_string())
+  } else if setting("global core dumps").as_deref() == Some("enabled") {
+    setting("global core file pattern")
+  } else {
+    None
+  }
+}
+
+
+/// Predict the path of the core file the kernel creates for the
+/// process with ID `pid`, given the system configuration and the dump
+/// directory `dir` (which will be the working directory at the time of
+/// the dump).
+fn predict(
+  pattern: &str,
+  uses_pid: bool,
+  suffix: &str,
+  dir: &Path,
+  signal: c_int,
+  pid: u32,
+  crash: &CrashDetails,
+) -> Option<PathBuf> {
+  // Core files piped to a helper end up wherever the helper decides.
+  if pattern.is_empty() || pattern.starts_with('|') {
+    return None
+  }
+
+  let mut expanded = expand(pattern, signal, pid, crash)?;
+  // The kernel only appends the PID if the pattern does not contain it
+  // already.
+  if uses_pid && !pattern.contains("%p") {
+    expanded += &format!(".{}", pid);
+  }
+  expanded += suffix;
+  Some(dir.join(expanded))
+}
+
+
+/// The location core files get written to, as determined by the
+/// kernel's core file name pattern.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum CoreLocation {
+  /// Core files are written to the file at the given path.
+  File(PathBuf),
+  /// Core files are piped to a helper program, which decides on where
+  /// they end up (e.g., `systemd-coredump`, which stores them in its
+  /// own location and makes them available via `coredumpctl(1)`). The
+  /// helper's command line is provided as configured, i.e., with
+  /// specifiers not expanded.
+  Pipe(String),
+}
+
+
+/// Core dump related system configuration.
+///
+/// Gathering this information involves reading files below `/proc` and
+/// formatting strings, neither of which we want to do at crash time.
+/// Hence, it is captured at registration time and only updated
+/// explicitly.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct CoreConfig {
+  /// The kernel's core file name pattern (see `core_pattern`).
+  pub(crate) core_pattern: Option<String>,
+  /// The path at which the core file is expected to be created, if it
+  /// can be predicted.
+  pub(crate) core_path: Option<PathBuf>,
+  /// The contents of `/proc/sys/kernel/core_uses_pid`.
+  core_uses_pid: bool,
+  /// The suffix the kernel appends to core file names, such as `.gz`
+  /// for compressed core files on FreeBSD.
+  core_suffix: &'static str,
+}
+
+impl CoreConfig {
+  /// Read the system's configuration, predicting core file paths for a
+  /// dump into `dir` by means of `signal`.
+  pub(crate) fn read(dir: &Path, signal: c_int) -> Self {
+    Self::read_for(dir, signal, pid())
+  }
+
+  /// Read the system's configuration, predicting core file paths for a
+  /// dump of the process with ID `pid`.
+  pub(crate) fn read_for(dir: &Path, signal: c_int, pid: u32) -> Self {
+    let core_pattern = core_pattern();
+    #[cfg(target_os = "linux")]
+    let core_uses_pid =
+      read_value::<u32>("/proc/sys/kernel/core_uses_pid").map(|value| value != 0);
+    #[cfg(not(target_os = "linux"))]
+    let core_uses_pid = None;
+
+    // FreeBSD compresses core files if asked to, reflecting the
+    // compression in the file name.
+    #[cfg(target_os = "freebsd")]
+    let core_suffix = match sysctl::int("kern.compress_user_cores") {
+      Some(1) => ".gz",
+      Some(2) => ".zst",
+      _ => "",
+    };
+    #[cfg(not(target_os = "freebsd"))]
+    let core_suffix = "";
+
+    let core_uses_pid = core_uses_pid.unwrap_or(false);
+    let core_path = core_pattern
+      .as_ref()
+      .and_then(|pattern| {
+        predict(
+          pattern,
+          core_uses_pid,
+          core_suffix,
+          dir,
+          signal,
+          pid,
+          &CrashDetails::default(),
+        )
+      });
+
+    Self {
+      core_pattern,
+      core_path,
+      core_uses_pid,
+      core_suffix,
+    }
+  }
+
+  /// Predict the path of the core file created for the process with ID
+  /// `pid`, based on the cached configuration.
+  ///
+  /// That is useful for snapshot cores, which are dumped by a forked
+  /// copy of the process.
+  pub(crate) fn predict_for(&self, dir: &Path, signal: c_int, pid: u32) -> Option<PathBuf> {
+    self
+      .core_pattern
+      .as_ref()
+      .and_then(|pattern| {
+        predict(
+          pattern,
+          self.core_uses_pid,
+          self.core_suffix,
+          dir,
+          signal,
+          pid,
+          &CrashDetails::default(),
+        )
+      })
+  }
+
+  /// Determine the location of the core file created should the calling
+  /// process crash as described by `crash`.
+  pub(crate) fn locate(
+    &self,
+    dir: &Path,
+    signal: c_int,
+    crash: &CrashDetails,
+  ) -> Option<CoreLocation> {
+    let pattern = self.core_pattern.as_ref()?;
+    if let Some(command) = pattern.strip_prefix('|') {
+      return Some(CoreLocation::Pipe(command.trim().to_string()))
+    }
+
+    predict(
+      pattern,
+      self.core_uses_pid,
+      self.core_suffix,
+      dir,
+      signal,
+      pid(),
+      crash,
+    )
+    .map(CoreLocation::File)
+  }
+}
@@ -0,0 +1,79 @@
+// context.rs
+
+// Copyright (C) 2026 Daniel Mueller <deso@posteo.net>
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+//! Capturing of register state from the machine context provided to
+//! signal handlers.
+//!
+//! The layout of the context is architecture specific. Everything
+//! architecture dependent is confined to this module, which supports
+//! x86_64, aarch64, and riscv64.
+
+use std::os::raw::c_void;
+
+
+/// The register state of a thread at the time it received a signal.
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct Registers {
+  /// The instruction pointer (or program counter).
+  pub(crate) ip: u64,
+  /// The stack pointer.
+  pub(crate) sp: u64,
+}
+
+impl Registers {
+  /// Capture the register state from the `ucontext_t` passed to a
+  /// signal handler installed with `SA_SIGINFO`.
+  ///
+  /// `None` is returned if `context` is NULL or the architecture is
+  /// not supported.
+  ///
+  /// This function is async-signal-safe.
+  ///
+  /// # Safety
+  /// `context` has to be NULL or point to a valid `ucontext_t`.
+  #[allow(clippy::unnecessary_cast)]
+  pub(crate) unsafe fn capture(context: *const c_void) -> Option<Self> {
+    #[cfg(target_arch = "x86_64")]
+    {
+      use libc::REG_RIP;
+      use libc::REG_RSP;
+
+      let context = context.cast::<libc::ucontext_t>().as_ref()?;
+      let gregs = &context.uc_mcontext.gregs;
+      Some(Self {
+        ip: gregs[REG_RIP as usize] as u64,
+        sp: gregs[REG_RSP as usize] as u64,
+      })
+    }
+    #[cfg(target_arch = "aarch64")]
+    {
+      let context = context.cast::<libc::ucontext_t>().as_ref()?;
+      Some(Self {
+        ip: context.uc_mcontext.pc as u64,
+        sp: context.uc_mcontext.sp as u64,
+      })
+    }
+    #[cfg(target_arch = "riscv64")]
+    {
+      let context = context.cast::<libc::ucontext_t>().as_ref()?;
+      // The program counter takes the slot of the hard-wired zero
+      // register (`x0`), the stack pointer is `x2`.
+      let gregs = &context.uc_mcontext.__gregs;
+      Some(Self {
+        ip: gregs[0] as u64,
+        sp: gregs[2] as u64,
+      })
+    }
+    #[cfg(not(any(
+      target_arch = "x86_64",
+      target_arch = "aarch64",
+      target_arch = "riscv64"
+    )))]
+    {
+      let _context = context;
+      None
+    }
+  }
+}
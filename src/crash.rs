@@ -0,0 +1,608 @@
+// crash.rs
+
+// Copyright (C) 2026 Daniel Mueller <deso@posteo.net>
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+//! Dumping of core for crashes other than panics.
+//!
+//! Crashes such as segmentation faults in `unsafe` code or in native
+//! libraries never reach the panic hook. The handlers in here prepare
+//! the process for dumping core the same way the panic hook does and
+//! then let the kernel's default action take place.
+
+#[cfg(feature = "alloc-error-hook")]
+use std::alloc;
+#[cfg(feature = "alloc-error-hook")]
+use std::alloc::Layout;
+use std::cell::Cell;
+use std::convert::TryInto;
+use std::ffi::CString;
+use std::io::Error as IoError;
+use std::io::ErrorKind;
+use std::os::raw::c_int;
+use std::os::raw::c_void;
+use std::os::unix::ffi::OsStrExt;
+use std::path::Path;
+use std::ptr::null_mut;
+use std::sync::atomic::AtomicPtr;
+use std::sync::atomic::AtomicUsize;
+use std::sync::atomic::Ordering;
+use std::sync::Mutex;
+use std::sync::PoisonError;
+use std::time::Duration;
+
+use libc::chdir;
+use libc::clock_gettime;
+use libc::close;
+use libc::getpid;
+use libc::open;
+use libc::raise;
+use libc::siginfo_t;
+use libc::write;
+use libc::CLOCK_REALTIME;
+use libc::O_CLOEXEC;
+use libc::O_CREAT;
+use libc::O_TRUNC;
+use libc::O_WRONLY;
+use libc::SA_ONSTACK;
+use libc::SIGABRT;
+use libc::SIGBUS;
+use libc::SIGFPE;
+use libc::SIGILL;
+use libc::SIGSEGV;
+#[cfg(feature = "alloc-error-hook")]
+use libc::STDERR_FILENO;
+
+use crate::arm_watchdog;
+use crate::backend::Backend as _;
+use crate::backend::Sys;
+use crate::buffer::Buffer;
+#[cfg(target_os = "linux")]
+use crate::context::Registers;
+use crate::resource::rlim_t;
+use crate::resource::RLIM_INFINITY;
+use crate::signal::Handler;
+use crate::signal::SavedAction;
+use crate::CrashAction;
+use crate::Error;
+use crate::HandlerPolicy;
+use crate::Str;
+use crate::WithCtx;
+
+
+/// The signals we install handlers for.
+const SIGNALS: [c_int; 5] = [SIGSEGV, SIGBUS, SIGILL, SIGFPE, SIGABRT];
+
+
+/// The distance below the lowest address of a thread's stack up to
+/// which faults are still attributed to the stack overflowing.
+///
+/// The guard page may be skipped by large stack frames and, for the
+/// main thread, the kernel maintains a gap of 256 pages below the stack
+/// that is not reported as part of it.
+#[cfg(target_os = "linux")]
+const STACK_GAP: usize = 1024 * 1024;
+
+
+/// The state captured at registration time and acted on in the signal
+/// handler.
+struct State {
+  /// The dump directory.
+  dir: CString,
+  /// The cap on the soft core file size limit, if the limit is to be
+  /// raised at all.
+  max_size: Option<rlim_t>,
+  /// Whether to write crash metadata.
+  metadata: bool,
+  /// The timeout of the watchdog to arm, if any.
+  watchdog: Option<Duration>,
+  /// How to deal with pre-existing handlers.
+  policy: HandlerPolicy,
+  /// The actions to take for the signals we handle.
+  actions: Vec<(c_int, CrashAction)>,
+  /// The actions that were in effect before we installed our handlers.
+  previous: Vec<SavedAction>,
+}
+
+/// The state to act on in the signal handler.
+///
+/// The pointer is set once per registration. A replaced pointee is only
+/// freed if no handler accesses state at the time (see [`READERS`]).
+static STATE: AtomicPtr<State> = AtomicPtr::new(null_mut());
+/// The number of handlers currently accessing [`STATE`].
+static READERS: AtomicUsize = AtomicUsize::new(0);
+/// The actions that were in effect before we installed our handlers,
+/// if we did.
+static PREVIOUS: Mutex<Vec<SavedAction>> = Mutex::new(Vec::new());
+
+thread_local! {
+  /// The range of addresses faults in which are attributed to the
+  /// thread's stack overflowing, if known.
+  ///
+  /// The variable is accessed from within the signal handler and so
+  /// must neither be lazily initialized nor have a destructor.
+  static OVERFLOW_RANGE: Cell<Option<(usize, usize)>> = const { Cell::new(None) };
+}
+
+
+/// Run `f` on the current state, if any, keeping it from being freed
+/// in the meantime.
+///
+/// This function is async-signal-safe.
+fn with_state<F, R>(f: F) -> Option<R>
+where
+  F: FnOnce(&State) -> R,
+{
+  let _count = READERS.fetch_add(1, Ordering::SeqCst);
+  let state = STATE.load(Ordering::SeqCst);
+  let result = unsafe { state.as_ref() }.map(f);
+  let _count = READERS.fetch_sub(1, Ordering::SeqCst);
+  result
+}
+
+
+/// Free `state`, which has to be unreachable through [`STATE`], unless
+/// a handler may still access it.
+fn retire(state: *mut State) {
+  if state.is_null() {
+    return
+  }
+
+  // A handler that loaded the pointer before it got replaced is
+  // accounted for by now. We do not wait for it to finish: in a forked
+  // child, the count may stem from a thread that does not exist here.
+  // Leaking the state in this rare case is the lesser evil.
+  if READERS.load(Ordering::SeqCst) == 0 {
+    let _state = unsafe { Box::from_raw(state) };
+  }
+}
+
+
+/// Record the bounds of the calling thread's stack, for the signal
+/// handler to detect stack overflows.
+///
+/// Retrieving the bounds is not async-signal-safe, so it has to happen
+/// ahead of time.
+#[cfg(target_os = "linux")]
+pub(crate) fn record_stack_bounds() {
+  use std::mem::zeroed;
+  use std::ptr::null_mut;
+
+  use libc::pthread_attr_destroy;
+  use libc::pthread_attr_getguardsize;
+  use libc::pthread_attr_getstack;
+  use libc::pthread_attr_t;
+  use libc::pthread_getattr_np;
+  use libc::pthread_self;
+
+  let mut attr = unsafe { zeroed::<pthread_attr_t>() };
+  if unsafe { pthread_getattr_np(pthread_self(), &mut attr) } != 0 {
+    return
+  }
+
+  let mut addr = null_mut();
+  let mut size = 0;
+  let mut guard = 0;
+  let result = unsafe { pthread_attr_getstack(&attr, &mut addr, &mut size) };
+  if unsafe { pthread_attr_getguardsize(&attr, &mut guard) } != 0 {
+    guard = 0;
+  }
+  let _ = unsafe { pthread_attr_destroy(&mut attr) };
+
+  // musl reports only the part of the main thread's stack that is
+  // currently mapped, while the stack may grow up to its size limit.
+  #[cfg(target_env = "musl")]
+  if result == 0 && unsafe { libc::syscall(libc::SYS_gettid) } == unsafe { getpid() }.into() {
+    let mut limit = unsafe { zeroed::<libc::rlimit>() };
+    if unsafe { libc::getrlimit(libc::RLIMIT_STACK, &mut limit) } == 0
+      && limit.rlim_cur != libc::RLIM_INFINITY
+    {
+      let high = (addr as usize).saturating_add(size);
+      addr = high.saturating_sub(limit.rlim_cur as usize) as *mut _;
+    }
+  }
+
+  if result == 0 {
+    // Depending on the glibc version, the guard area is either included
+    // in the reported stack or located below it. Cover both cases.
+    let low = addr as usize;
+    let range = (
+      low.saturating_sub(STACK_GAP),
+      low.saturating_add(guard.max(4096)),
+    );
+    let _result = OVERFLOW_RANGE.try_with(|overflow| overflow.set(Some(range)));
+  }
+}
+
+#[cfg(not(target_os = "linux"))]
+pub(crate) fn record_stack_bounds() {}
+
+
+/// Write crash metadata for the given signal into `dir`.
+///
+/// This function is async-signal-safe.
+fn write_metadata(dir: &CString, signal: c_int, info: *const siginfo_t, context: *const c_void) {
+  let pid = unsafe { getpid() } as u64;
+  let mut time = unsafe { std::mem::zeroed() };
+  let time = if unsafe { clock_gettime(CLOCK_REALTIME, &mut time) } == 0 {
+    time.tv_sec as u64
+  } else {
+    0
+  };
+
+  let mut path = Buffer::<4096>::new();
+  let _path = path
+    .push(dir.as_bytes())
+    .push(b"/coredump-")
+    .push_dec(pid)
+    .push(b"-")
+    .push_dec(time)
+    .push(b".txt");
+  let path = match path.terminate() {
+    Some(path) => path,
+    None => return,
+  };
+
+  let mut content = Buffer::<512>::new();
+  let _content = content
+    .push(b"pid: ")
+    .push_dec(pid)
+    .push(b"\nsignal: ")
+    .push_dec(signal as u64)
+    .push(b"\n");
+
+  #[cfg(target_os = "linux")]
+  if let Some(info) = unsafe { info.as_ref() } {
+    // Only signals generated by the kernel for a fault carry an address.
+    if (signal == SIGSEGV || signal == SIGBUS) && info.si_code > 0 {
+      let addr = unsafe { info.si_addr() } as usize;
+      let _content = content.push(b"fault-address: ").push_hex(addr as u64).push(b"\n");
+
+      if signal == SIGSEGV {
+        let range = OVERFLOW_RANGE.try_with(Cell::get).ok().flatten();
+        if let Some((low, high)) = range {
+          let kind: &[u8] = if (low..high).contains(&addr) {
+            b"stack-overflow"
+          } else {
+            b"segfault"
+          };
+          let _content = content.push(b"crash-kind: ").push(kind).push(b"\n");
+        }
+      }
+    }
+  }
+  #[cfg(target_os = "linux")]
+  if let Some(registers) = unsafe { Registers::capture(context) } {
+    let _content = content
+      .push(b"instruction-pointer: ")
+      .push_hex(registers.ip)
+      .push(b"\nstack-pointer: ")
+      .push_hex(registers.sp)
+      .push(b"\n");
+  }
+  #[cfg(not(target_os = "linux"))]
+  let _info = (info, context);
+
+  let fd = unsafe { open(path.as_ptr(), O_WRONLY | O_CREAT | O_TRUNC | O_CLOEXEC, 0o666) };
+  if fd < 0 {
+    return
+  }
+  let bytes = content.as_bytes();
+  let _ = unsafe { write(fd, bytes.as_ptr().cast(), bytes.len()) };
+  let _ = unsafe { close(fd) };
+}
+
+
+/// Set the soft core file size limit to `value`, capped at the hard
+/// limit.
+///
+/// This function is async-signal-safe.
+fn set_soft_limit(value: rlim_t) {
+  if let Ok(mut limit) = Sys::core_limit() {
+    limit.rlim_cur = limit.rlim_max.min(value);
+    let _ = Sys::set_core_limit(&limit);
+  }
+}
+
+
+/// Prepare the process for dumping core as configured by `state`.
+///
+/// This function is async-signal-safe.
+fn prepare(state: &State, signal: c_int, info: *const siginfo_t, context: *const c_void) {
+  if let Some(max_size) = state.max_size {
+    let () = set_soft_limit(max_size);
+  }
+  let _ = unsafe { chdir(state.dir.as_ptr()) };
+  if state.metadata {
+    let () = write_metadata(&state.dir, signal, info, context);
+  }
+  if let Some(timeout) = state.watchdog {
+    let _ = arm_watchdog(timeout);
+  }
+}
+
+
+/// The hook invoked by the Rust runtime when allocating memory failed.
+///
+/// The runtime aborts the process once we return, which terminates it
+/// with a core dump by default. As allocations are likely to fail, we
+/// stick to the state prepared at registration time.
+#[cfg(feature = "alloc-error-hook")]
+fn handle_alloc_error(layout: Layout) {
+  let mut message = Buffer::<128>::new();
+  let _message = message
+    .push(b"memory allocation of ")
+    .push_dec(layout.size() as u64)
+    .push(b" bytes failed\n");
+  let bytes = message.as_bytes();
+  let _ = unsafe { write(STDERR_FILENO, bytes.as_ptr().cast(), bytes.len()) };
+
+  let _prepared = with_state(|state| prepare(state, SIGABRT, null_mut(), null_mut()));
+}
+
+
+/// Install or remove the hook dumping core when allocating memory
+/// failed.
+///
+/// The hook acts on the state installed by [`install`].
+#[cfg(feature = "alloc-error-hook")]
+pub(crate) fn set_alloc_error_hook(enable: bool) {
+  if enable {
+    let () = alloc::set_alloc_error_hook(handle_alloc_error);
+  } else {
+    let _hook = alloc::take_alloc_error_hook();
+  }
+}
+
+
+/// The handler invoked for crash signals.
+///
+/// We may have interrupted arbitrary code and have to restrict
+/// ourselves to async-signal-safe functionality.
+extern "C" fn handle_crash(signal: c_int, info: *mut siginfo_t, context: *mut c_void) {
+  // Previous handlers may not return at all, so we must not access the
+  // state while running them.
+  let chain = with_state(|state| {
+    let previous = state
+      .previous
+      .iter()
+      .find(|action| action.signal() == signal)
+      .copied();
+    (previous, state.policy)
+  });
+  if let Some((Some(previous), policy)) = chain {
+    match policy {
+      HandlerPolicy::TheirsFirst => {
+        if unsafe { previous.chain(signal, info, context) } && is_ours(signal) {
+          // The handler returned without changing the disposition,
+          // meaning that it took care of the signal (think of a
+          // runtime relying on SIGSEGV for its operation). Handlers
+          // that did not, typically restore the default disposition
+          // before returning.
+          return
+        }
+      },
+      HandlerPolicy::OursFirst => (),
+      HandlerPolicy::Skip => {
+        if unsafe { previous.chain(signal, info, context) } {
+          return
+        }
+      },
+    }
+  }
+
+  let _prepared = with_state(|state| {
+    let action = state
+      .actions
+      .iter()
+      .find(|(other, _)| *other == signal)
+      .map(|(_, action)| *action);
+    if action == Some(CrashAction::NoDump) {
+      let () = set_soft_limit(0);
+    } else {
+      let () = prepare(state, signal, info, context);
+    }
+  });
+
+  // The signal is blocked while we run, so re-raising it merely marks
+  // it pending. Once we return it is delivered again, this time with
+  // the default action of terminating the process with a core dump.
+  // Faults would be raised anew anyway, as the faulting instruction is
+  // re-executed, but signals sent by other means would not.
+  let _ = SavedAction::reset(signal);
+  let _ = unsafe { raise(signal) };
+}
+
+
+/// Check whether `action` refers to our handler.
+///
+/// This function is async-signal-safe.
+pub(crate) fn is_handler(action: &SavedAction) -> bool {
+  action.as_raw().sa_sigaction == handle_crash as Handler as usize
+}
+
+
+/// Check whether our handler is the one installed for `signal`.
+///
+/// This function is async-signal-safe.
+fn is_ours(signal: c_int) -> bool {
+  SavedAction::save(signal)
+    .map(|action| is_handler(&action))
+    .unwrap_or(false)
+}
+
+
+/// Determine the signals to install handlers for, along with the
+/// action to take for each.
+///
+/// If `crash_signals` is `true`, core is dumped for all crash signals,
+/// otherwise only for SIGABRT if `abort_signal` is `true`. `overrides`
+/// take precedence. Signals left to pre-existing handlers are omitted.
+pub(crate) fn actions(
+  crash_signals: bool,
+  abort_signal: bool,
+  overrides: &[(c_int, CrashAction)],
+) -> Result<Vec<(c_int, CrashAction)>, (Str, Error)> {
+  if let Some((signal, _)) = overrides.iter().find(|(signal, _)| !SIGNALS.contains(signal)) {
+    return Err(Error::from(IoError::new(
+      ErrorKind::InvalidInput,
+      "not a crash signal",
+    )))
+    .ctx(|| format!("cannot configure action for signal {}", signal))
+  }
+
+  let actions = SIGNALS
+    .iter()
+    .map(|signal| {
+      let action = overrides
+        .iter()
+        .find(|(other, _)| other == signal)
+        .map(|(_, action)| *action)
+        .unwrap_or(if crash_signals || (abort_signal && *signal == SIGABRT) {
+          CrashAction::Dump
+        } else {
+          CrashAction::ChainOnly
+        });
+      (*signal, action)
+    })
+    .filter(|(_, action)| *action != CrashAction::ChainOnly)
+    .collect();
+  Ok(actions)
+}
+
+
+/// The changes applied by [`install`], for rolling them back.
+///
+/// Dropping the object commits the changes.
+pub(crate) struct Undo {
+  /// The state that was in effect before.
+  state: *mut State,
+  /// The signals our handlers were installed for before.
+  signals: Vec<c_int>,
+}
+
+impl Undo {
+  /// Roll back the changes.
+  pub(crate) fn undo(mut self) {
+    let mut previous = PREVIOUS.lock().unwrap_or_else(PoisonError::into_inner);
+    let _ = apply(&mut previous, &self.signals);
+    let state = STATE.swap(self.state, Ordering::SeqCst);
+    self.state = null_mut();
+    let () = retire(state);
+  }
+}
+
+impl Drop for Undo {
+  fn drop(&mut self) {
+    let () = retire(self.state);
+  }
+}
+
+
+/// Bring the set of signals our handlers are installed for in line with
+/// `signals`, recording the actions they replaced in `previous`.
+fn apply(previous: &mut Vec<SavedAction>, signals: &[c_int]) -> Result<(), (Str, Error)> {
+  let mut result = Ok(());
+  // Restore all actions we can, reporting the first error.
+  for idx in (0..previous.len()).rev() {
+    if !signals.contains(&previous[idx].signal()) {
+      let action = previous.remove(idx);
+      if let Err(err) = action.restore() {
+        if result.is_ok() {
+          result = Err(err);
+        }
+      }
+    }
+  }
+  let () = result?;
+
+  for signal in signals.iter() {
+    if previous.iter().all(|action| action.signal() != *signal) {
+      // Run on the alternate signal stack, if any, as the regular one
+      // may be exhausted.
+      let action = SavedAction::install(*signal, handle_crash, SA_ONSTACK)?;
+      let () = previous.push(action);
+    }
+  }
+  Ok(())
+}
+
+
+/// Install handlers for the signals in `actions`, taking the associated
+/// action and dumping core into `dir`.
+///
+/// If `manage_rlimit` is `true`, the soft core file size limit is
+/// raised to the hard limit, capped at `max_size` bytes (if provided),
+/// before dumping core. If `metadata` is `true`, crash metadata are
+/// written into `dir` as well. If `watchdog` is provided, a watchdog
+/// with the given timeout is armed before dumping core. `policy`
+/// governs the interaction with handlers that were installed before
+/// ours. Handlers already installed by an earlier invocation merely
+/// pick up the new configuration, while those for signals not
+/// contained in `actions` are uninstalled.
+pub(crate) fn install(
+  dir: &Path,
+  actions: &[(c_int, CrashAction)],
+  manage_rlimit: bool,
+  max_size: Option<u64>,
+  metadata: bool,
+  watchdog: Option<Duration>,
+  policy: HandlerPolicy,
+) -> Result<Undo, (Str, Error)> {
+  let path = CString::new(dir.as_os_str().as_bytes())
+    .map_err(|err| Error::from(IoError::new(ErrorKind::InvalidInput, err)))
+    .ctx(|| format!("dump directory {} is not a valid path", dir.display()))?;
+  // A cap not representable as `rlim_t` is no cap at all. Note that
+  // `rlim_t` is not the same type on all platforms.
+  #[allow(clippy::useless_conversion)]
+  let max_size = max_size
+    .and_then(|max_size| max_size.try_into().ok())
+    .unwrap_or(RLIM_INFINITY);
+
+  let signals = actions.iter().map(|(signal, _)| *signal).collect::<Vec<_>>();
+  let mut previous = PREVIOUS.lock().unwrap_or_else(PoisonError::into_inner);
+  let current = previous.iter().map(SavedAction::signal).collect::<Vec<_>>();
+  let mut saved = previous
+    .iter()
+    .filter(|action| signals.contains(&action.signal()))
+    .copied()
+    .collect::<Vec<_>>();
+  for signal in signals.iter().filter(|signal| !current.contains(signal)) {
+    // Capture the actions to chain to before our handlers could run.
+    let action = SavedAction::save(*signal)?;
+    let () = saved.push(action);
+  }
+
+  let state = Box::new(State {
+    dir: path,
+    max_size: Some(max_size).filter(|_| manage_rlimit),
+    metadata,
+    watchdog,
+    policy,
+    actions: actions.to_vec(),
+    previous: saved,
+  });
+  let state = STATE.swap(Box::into_raw(state), Ordering::SeqCst);
+
+  if let Err(err) = apply(&mut previous, &signals) {
+    // Leave no trace of the failed attempt.
+    let _ = apply(&mut previous, &current);
+    let state = STATE.swap(state, Ordering::SeqCst);
+    let () = retire(state);
+    return Err(err)
+  }
+  Ok(Undo {
+    state,
+    signals: current,
+  })
+}
+
+
+/// Uninstall the handlers for crash signals, restoring the actions
+/// that were in effect before they got installed.
+///
+/// Nothing is done if no handlers are installed.
+pub(crate) fn uninstall() -> Result<(), (Str, Error)> {
+  let mut previous = PREVIOUS.lock().unwrap_or_else(PoisonError::into_inner);
+  apply(&mut previous, &[])
+}
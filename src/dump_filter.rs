@@ -0,0 +1,141 @@
+// dump_filter.rs
+
+// Copyright (C) 2026 Daniel Mueller <deso@posteo.net>
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+//! Control over the memory mappings included in core files, by means
+//! of `/proc/self/coredump_filter`.
+
+use std::fs::read_to_string;
+use std::fs::write;
+use std::io::Error as IoError;
+use std::io::ErrorKind;
+use std::ops::BitAnd;
+use std::ops::BitOr;
+use std::ops::BitOrAssign;
+use std::ops::Sub;
+
+use crate::Error;
+use crate::Str;
+use crate::WithCtx;
+
+/// The path of the file controlling the calling process' filter.
+const PATH: &str = "/proc/self/coredump_filter";
+
+
+/// A set of memory mapping types to include in core files, as
+/// understood by `/proc/<pid>/coredump_filter` (see `core(5)`).
+///
+/// Sets are combined using the `|` operator:
+/// ```rust
+/// # use coredump::CoredumpFilter;
+/// let filter = CoredumpFilter::default() | CoredumpFilter::FILE_PRIVATE;
+/// assert!(filter.contains(CoredumpFilter::ANON_PRIVATE));
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct CoredumpFilter(u32);
+
+impl CoredumpFilter {
+  /// Anonymous private mappings, such as the heap and stacks.
+  pub const ANON_PRIVATE: Self = Self(1 << 0);
+  /// Anonymous shared mappings.
+  pub const ANON_SHARED: Self = Self(1 << 1);
+  /// File-backed private mappings.
+  pub const FILE_PRIVATE: Self = Self(1 << 2);
+  /// File-backed shared mappings.
+  pub const FILE_SHARED: Self = Self(1 << 3);
+  /// The ELF headers of file-backed mappings, which debuggers use for
+  /// identifying the mapped binaries (e.g., by their build ID).
+  pub const ELF_HEADERS: Self = Self(1 << 4);
+  /// Private huge pages.
+  pub const HUGETLB_PRIVATE: Self = Self(1 << 5);
+  /// Shared huge pages.
+  pub const HUGETLB_SHARED: Self = Self(1 << 6);
+  /// Private DAX pages.
+  pub const DAX_PRIVATE: Self = Self(1 << 7);
+  /// Shared DAX pages.
+  pub const DAX_SHARED: Self = Self(1 << 8);
+
+  /// Create an empty set, excluding all mapping types.
+  pub const fn empty() -> Self {
+    Self(0)
+  }
+
+  /// Create a set containing all mapping types.
+  pub const fn all() -> Self {
+    Self(0x1ff)
+  }
+
+  /// Create a set from its raw representation, dropping unknown bits.
+  pub const fn from_bits_truncate(bits: u32) -> Self {
+    Self(bits & Self::all().0)
+  }
+
+  /// Retrieve the raw representation of the set.
+  pub const fn bits(self) -> u32 {
+    self.0
+  }
+
+  /// Check whether the set contains all types contained in `other`.
+  pub const fn contains(self, other: Self) -> bool {
+    self.0 & other.0 == other.0
+  }
+}
+
+impl Default for CoredumpFilter {
+  /// The kernel's default set: anonymous mappings, ELF headers, and
+  /// private huge pages.
+  fn default() -> Self {
+    Self::ANON_PRIVATE | Self::ANON_SHARED | Self::ELF_HEADERS | Self::HUGETLB_PRIVATE
+  }
+}
+
+impl BitOr for CoredumpFilter {
+  type Output = Self;
+
+  fn bitor(self, other: Self) -> Self {
+    Self(self.0 | other.0)
+  }
+}
+
+impl BitOrAssign for CoredumpFilter {
+  fn bitor_assign(&mut self, other: Self) {
+    self.0 |= other.0
+  }
+}
+
+impl BitAnd for CoredumpFilter {
+  type Output = Self;
+
+  fn bitand(self, other: Self) -> Self {
+    Self(self.0 & other.0)
+  }
+}
+
+impl Sub for CoredumpFilter {
+  type Output = Self;
+
+  fn sub(self, other: Self) -> Self {
+    Self(self.0 & !other.0)
+  }
+}
+
+
+/// Read the calling process' core dump filter.
+pub(crate) fn read() -> Result<CoredumpFilter, (Str, Error)> {
+  let content = read_to_string(PATH)
+    .map_err(Error::from)
+    .ctx(|| format!("failed to read {}", PATH))?;
+  let bits = u32::from_str_radix(content.trim(), 16)
+    .map_err(|err| Error::from(IoError::new(ErrorKind::InvalidData, err)))
+    .ctx(|| format!("failed to parse {}", PATH))?;
+  Ok(CoredumpFilter::from_bits_truncate(bits))
+}
+
+
+/// Set the calling process' core dump filter.
+pub(crate) fn apply(filter: CoredumpFilter) -> Result<(), (Str, Error)> {
+  write(PATH, format!("{:#x}", filter.bits()))
+    .map_err(Error::from)
+    .ctx(|| format!("failed to write {}", PATH))
+}
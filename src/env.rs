@@ -0,0 +1,80 @@
+// env.rs
+
+// Copyright (C) 2026 Daniel Mueller <deso@posteo.net>
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+//! Configuration overrides provided by means of environment variables.
+
+use std::env::var_os;
+use std::ffi::OsStr;
+use std::io::Error as IoError;
+use std::io::ErrorKind;
+use std::path::PathBuf;
+
+use crate::Error;
+use crate::Str;
+use crate::WithCtx;
+
+
+/// The variable that, if truthy, suppresses registration.
+pub(crate) const DISABLE: &str = "COREDUMP_DISABLE";
+/// The variable overriding the dump directory.
+pub(crate) const DIR: &str = "COREDUMP_DIR";
+/// The variable capping the soft core file size limit, in bytes.
+pub(crate) const MAX_SIZE: &str = "COREDUMP_MAX_SIZE";
+
+
+/// Check whether an environment variable value is to be considered
+/// truthy.
+///
+/// Values considered falsy are the empty string, `0`, `false`, `no`,
+/// and `off` (compared case-insensitively).
+pub(crate) fn is_truthy(value: &OsStr) -> bool {
+  let value = value.to_string_lossy().trim().to_ascii_lowercase();
+  !matches!(value.as_str(), "" | "0" | "false" | "no" | "off")
+}
+
+
+/// Configuration overrides as read from the environment.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct Overrides {
+  /// Whether registration is suppressed.
+  pub(crate) disable: bool,
+  /// The directory to dump core files into.
+  pub(crate) dir: Option<PathBuf>,
+  /// The maximum soft core file size limit, in bytes.
+  pub(crate) max_size: Option<u64>,
+}
+
+impl Overrides {
+  /// Read the overrides from the process' environment.
+  pub(crate) fn read() -> Result<Self, (Str, Error)> {
+    let disable = var_os(DISABLE)
+      .map(|value| is_truthy(&value))
+      .unwrap_or(false);
+    let dir = var_os(DIR)
+      .filter(|dir| !dir.is_empty())
+      .map(PathBuf::from);
+    let max_size = var_os(MAX_SIZE)
+      .map(|value| {
+        value
+          .to_str()
+          .and_then(|value| value.trim().parse::<u64>().ok())
+          .ok_or_else(|| {
+            Error::from(IoError::new(
+              ErrorKind::InvalidInput,
+              format!("{:?} is not a valid size in bytes", value),
+            ))
+          })
+      })
+      .transpose()
+      .ctx(|| format!("failed to evaluate {}", MAX_SIZE))?;
+
+    let overrides = Self {
+      disable,
+      dir,
+      max_size,
+    };
+    Ok(overrides)
+  }
+}
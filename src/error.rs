@@ -0,0 +1,85 @@
+// error.rs
+
+// Copyright (C) 2019-2026 Daniel Mueller <deso@posteo.net>
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+use std::borrow::Cow;
+use std::error::Error as StdError;
+use std::fmt::Display;
+use std::fmt::Formatter;
+use std::fmt::Result as FmtResult;
+use std::io::Error as IoError;
+use std::num::TryFromIntError;
+
+
+pub(crate) type Str = Cow<'static, str>;
+
+
+/// The error type used by this crate.
+#[derive(Debug)]
+pub enum Error {
+  Io(IoError),
+  Int(TryFromIntError),
+  /// The functionality is not supported on the target platform.
+  Unsupported,
+}
+
+impl StdError for Error {
+  fn source(&self) -> Option<&(dyn StdError + 'static)> {
+    match self {
+      Error::Io(err) => err.source(),
+      Error::Int(err) => err.source(),
+      Error::Unsupported => None,
+    }
+  }
+}
+
+impl Display for Error {
+  fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+    match self {
+      Error::Io(err) => write!(f, "{}", err),
+      Error::Int(err) => write!(f, "{}", err),
+      Error::Unsupported => write!(f, "not supported on this platform"),
+    }
+  }
+}
+
+impl From<IoError> for Error {
+  fn from(e: IoError) -> Self {
+    Error::Io(e)
+  }
+}
+
+impl From<TryFromIntError> for Error {
+  fn from(e: TryFromIntError) -> Self {
+    Error::Int(e)
+  }
+}
+
+
+/// A helper trait for annotating errors with some context.
+pub(crate) trait WithCtx<T>
+where
+  Self: Sized,
+{
+  type E;
+
+  fn ctx<F, S>(self, ctx: F) -> Result<T, (Str, Self::E)>
+  where
+    F: Fn() -> S,
+    S: Into<Str>;
+}
+
+impl<T, E> WithCtx<T> for Result<T, E> {
+  type E = E;
+
+  fn ctx<F, S>(self, ctx: F) -> Result<T, (Str, Self::E)>
+  where
+    F: Fn() -> S,
+    S: Into<Str>,
+  {
+    self.map_err(|e| (ctx().into(), e))
+  }
+}
+
+
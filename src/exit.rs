@@ -0,0 +1,71 @@
+// exit.rs
+
+// Copyright (C) 2026 Daniel Mueller <deso@posteo.net>
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+//! Dumping of core when the process exits with an abnormal status.
+
+use std::io::Error as IoError;
+use std::io::ErrorKind;
+use std::os::raw::c_int;
+use std::os::raw::c_void;
+
+use crate::dump_dir;
+use crate::dump_priority;
+use crate::dump_signal;
+use crate::dump_snapshot;
+use crate::raise_configured_limit;
+use crate::report_error;
+use crate::Error;
+use crate::Str;
+use crate::WithCtx;
+
+
+extern "C" {
+  fn on_exit(function: extern "C" fn(c_int, *mut c_void), arg: *mut c_void) -> c_int;
+}
+
+
+/// The exit handler installed by [`dump_on_abnormal_exit`].
+extern "C" fn check_exit_status(status: c_int, arg: *mut c_void) {
+  // SAFETY: `arg` is the leaked vector set up in `dump_on_abnormal_exit`
+  //         and it stays valid for the remainder of the program.
+  let codes = unsafe { &*(arg as *const Vec<c_int>) };
+  if codes.contains(&status) {
+    if let Err((ctx, err)) = dump_snapshot(&dump_dir(), dump_priority(), dump_signal()) {
+      report_error("failed to dump core: ", &ctx, &err);
+    }
+  }
+}
+
+
+/// Create a snapshot core when the process exits with any of the given
+/// status codes.
+///
+/// Applications can use this function to register exit codes they
+/// consider abnormal, which can be helpful in tracking down the origin
+/// of an unexpected `exit(1)` somewhere deep in a dependency. Note that
+/// the core is created from within an exit handler, meaning that the
+/// stack of the code having called `exit` is preserved. The process is
+/// not terminated by a signal and will exit with the original status.
+///
+/// This function will also ensure that core dumps are enabled. It is
+/// only available on Linux with glibc.
+pub fn dump_on_abnormal_exit(codes: &[c_int]) -> Result<(), (Str, Error)> {
+  if cfg!(feature = "disabled") {
+    return Ok(())
+  }
+
+  raise_configured_limit()?;
+
+  // The exit handler lives for the remainder of the program and so does
+  // its argument.
+  let codes = Box::into_raw(Box::new(codes.to_vec()));
+  let result = unsafe { on_exit(check_exit_status, codes as *mut c_void) };
+  if result != 0 {
+    let _codes = unsafe { Box::from_raw(codes) };
+    Err(IoError::new(ErrorKind::Other, "too many exit handlers").into())
+      .ctx(|| "failed to register exit handler")?;
+  }
+  Ok(())
+}
@@ -0,0 +1,35 @@
+// filter.rs
+
+// Copyright (C) 2026 Daniel Mueller <deso@posteo.net>
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+//! Restriction of core dumps to panics with certain properties.
+
+use std::panic::Location;
+
+
+/// Check whether a panic with the given message and location passes
+/// the message and location filters.
+///
+/// An empty list of filters lets every panic pass. Otherwise, the
+/// message has to contain any of `messages` and the location's file
+/// has to start with any of `locations`.
+pub(crate) fn matches(
+  message: &str,
+  location: Option<&Location<'_>>,
+  messages: &[String],
+  locations: &[String],
+) -> bool {
+  let message_matches =
+    messages.is_empty() || messages.iter().any(|pattern| message.contains(pattern.as_str()));
+  let location_matches = locations.is_empty()
+    || location
+      .map(|location| {
+        locations
+          .iter()
+          .any(|prefix| location.file().starts_with(prefix.as_str()))
+      })
+      .unwrap_or(false);
+
+  message_matches && location_matches
+}
@@ -0,0 +1,228 @@
+// hook.rs
+
+// Copyright (C) 2026 Daniel Mueller <deso@posteo.net>
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+//! Bookkeeping of the panic hooks installed by (potentially repeated)
+//! registrations.
+
+use std::cell::Cell;
+use std::mem::replace;
+use std::panic::set_hook;
+use std::panic::take_hook;
+use std::sync::atomic::AtomicUsize;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use std::sync::Mutex;
+use std::sync::PoisonError;
+
+use crate::resource::rlimit;
+use crate::settings;
+use crate::Settings;
+
+
+/// The type of a panic hook, as used by the standard library.
+// `PanicInfo` got renamed to `PanicHookInfo`, but the latter is not
+// available on our minimum supported Rust version.
+#[allow(deprecated)]
+pub(crate) type Hook = Box<dyn Fn(&std::panic::PanicInfo<'_>) + Sync + Send + 'static>;
+
+
+/// A panic hook installed by us.
+struct Entry {
+  /// The ID of the registration that installed the hook.
+  id: usize,
+  /// The address of the hook, which identifies it.
+  address: usize,
+  /// Our hook, as created for the registration.
+  hook: Arc<Hook>,
+  /// The hook ours forwards to: the one that was installed before ours
+  /// or, once ours got reasserted, the one that replaced it.
+  previous: Arc<Mutex<Arc<Hook>>>,
+  /// The settings in effect before the registration.
+  settings: Option<Arc<Settings>>,
+  /// The core file size limit in effect before the registration, if
+  /// the registration adjusted it.
+  limit: Option<rlimit>,
+}
+
+
+/// The state to reinstate after removal of a hook.
+pub(crate) struct Removed {
+  /// Whether the previously installed hook got restored.
+  pub(crate) restored: bool,
+  /// The settings that were in effect before the registration.
+  pub(crate) settings: Option<Arc<Settings>>,
+  /// The core file size limit that was in effect before the
+  /// registration, if the registration adjusted it.
+  pub(crate) limit: Option<rlimit>,
+}
+
+
+/// The hooks installed by us, in the order of installation.
+static STACK: Mutex<Vec<Entry>> = Mutex::new(Vec::new());
+/// The ID of the registration whose hook is the one to act on a panic,
+/// or zero if there is none.
+static ACTIVE: AtomicUsize = AtomicUsize::new(0);
+/// The ID to use for the next registration.
+static NEXT_ID: AtomicUsize = AtomicUsize::new(1);
+
+thread_local! {
+  /// The ID of the registration whose hook is running on this thread,
+  /// or zero if there is none.
+  static RUNNING: Cell<usize> = const { Cell::new(0) };
+}
+
+
+/// Retrieve the address of a hook.
+fn address(hook: &Hook) -> usize {
+  &**hook as *const _ as *const () as usize
+}
+
+
+/// Wrap the hook of the registration with the given ID for
+/// installation.
+///
+/// Once reasserted, our hook forwards to the one that replaced it. That
+/// one may in turn forward to ours, in which case `original`, the hook
+/// that was installed before ours, is invoked in its stead.
+fn wrap(id: usize, hook: Arc<Hook>, original: Arc<Hook>) -> Hook {
+  Box::new(move |panic_info| {
+    let outer = RUNNING.with(|running| running.replace(id));
+    if outer == id {
+      original(panic_info)
+    } else {
+      hook(panic_info)
+    }
+    RUNNING.with(|running| running.set(outer));
+  })
+}
+
+
+/// Check whether the hook installed by the registration with the given
+/// ID is the one to act on a panic.
+///
+/// Only the most recent registration's hook acts. Hooks of earlier
+/// registrations may still be part of the chain of hooks (because they
+/// are wrapped by later ones or by foreign hooks), but they merely
+/// forward to the hook they replaced.
+pub(crate) fn is_active(id: usize) -> bool {
+  ACTIVE.load(Ordering::SeqCst) == id
+}
+
+
+/// Check whether any registration's hook is active.
+pub(crate) fn is_registered() -> bool {
+  ACTIVE.load(Ordering::SeqCst) != 0
+}
+
+
+/// Install a panic hook.
+///
+/// `make` is provided with the registration's ID as well as the
+/// previously installed hook and is expected to create the hook to
+/// install. `limit` is the core file size limit to reinstate on
+/// removal.
+pub(crate) fn install<F>(limit: Option<rlimit>, make: F)
+where
+  F: FnOnce(usize, Arc<Hook>) -> Hook,
+{
+  let mut stack = STACK.lock().unwrap_or_else(PoisonError::into_inner);
+  let id = NEXT_ID.fetch_add(1, Ordering::SeqCst);
+  let original = Arc::new(take_hook());
+  let previous = Arc::new(Mutex::new(Arc::clone(&original)));
+  let slot = Arc::clone(&previous);
+  let forward: Hook = Box::new(move |panic_info| {
+    let previous = slot.lock().unwrap_or_else(PoisonError::into_inner).clone();
+    previous(panic_info)
+  });
+  let hook = Arc::new(make(id, Arc::new(forward)));
+  let installed = wrap(id, Arc::clone(&hook), original);
+  let address = address(&installed);
+  let settings = settings();
+
+  let () = set_hook(installed);
+  let () = stack.push(Entry {
+    id,
+    address,
+    hook,
+    previous,
+    settings,
+    limit,
+  });
+  ACTIVE.store(id, Ordering::SeqCst);
+}
+
+
+/// Reinstall the hook installed most recently, should another one have
+/// replaced it.
+///
+/// The hook that replaced ours is forwarded to in place of the one that
+/// was installed before ours. `None` is returned if no hook is
+/// installed, otherwise whether it had to be reinstalled.
+pub(crate) fn reassert() -> Option<bool> {
+  let mut stack = STACK.lock().unwrap_or_else(PoisonError::into_inner);
+  let entry = stack.last_mut()?;
+
+  let current = take_hook();
+  if address(&current) == entry.address {
+    let () = set_hook(current);
+    return Some(false)
+  }
+
+  let mut previous = entry
+    .previous
+    .lock()
+    .unwrap_or_else(PoisonError::into_inner);
+  let original = replace(&mut *previous, Arc::new(current));
+  let installed = wrap(entry.id, Arc::clone(&entry.hook), original);
+  entry.address = address(&installed);
+  let () = set_hook(installed);
+  Some(true)
+}
+
+
+/// Remove the hook installed most recently.
+///
+/// On success, the state in effect before the corresponding
+/// registration is returned. If a foreign hook was installed on top of
+/// ours in the meantime, the foreign hook is left in place and ours is
+/// merely deactivated, as it cannot be removed from the middle of the
+/// chain.
+pub(crate) fn remove() -> Option<Removed> {
+  let mut stack = STACK.lock().unwrap_or_else(PoisonError::into_inner);
+  let entry = stack.pop()?;
+  ACTIVE.store(
+    stack.last().map(|entry| entry.id).unwrap_or(0),
+    Ordering::SeqCst,
+  );
+
+  let current = take_hook();
+  let restored = address(&current) == entry.address;
+  if restored {
+    // Drop our hook and with it its references to the previous one, so
+    // that we can reinstate the latter as is.
+    drop(current);
+    drop(entry.hook);
+    let previous = match Arc::try_unwrap(entry.previous) {
+      Ok(previous) => previous
+        .into_inner()
+        .unwrap_or_else(PoisonError::into_inner),
+      Err(previous) => previous
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .clone(),
+    };
+    let previous = Arc::try_unwrap(previous)
+      .unwrap_or_else(|previous| Box::new(move |panic_info| previous(panic_info)));
+    let () = set_hook(previous);
+  } else {
+    let () = set_hook(current);
+  }
+  let removed = Removed {
+    restored,
+    settings: entry.settings,
+    limit: entry.limit,
+  };
+  Some(removed)
+}
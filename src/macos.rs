@@ -0,0 +1,117 @@
+// macos.rs
+
+// Copyright (C) 2026 Daniel Mueller <deso@posteo.net>
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+use std::env::var_os;
+use std::fs::read_dir;
+use std::fs::read_to_string;
+use std::path::Path;
+use std::path::PathBuf;
+
+use libc::pid_t;
+
+
+/// Artifacts created by the system for a crashed process.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct CrashArtifacts {
+  /// Crash reports (`.ips` or `.crash` files) created by ReportCrash.
+  pub reports: Vec<PathBuf>,
+  /// The core file, if one was created in `/cores`.
+  pub core: Option<PathBuf>,
+}
+
+
+/// Check whether the `.ips` report `text` refers to process `pid`.
+///
+/// `.ips` files consist of a single line JSON header followed by a JSON
+/// body, which contains the process ID as `"pid"` member. Formatting
+/// differs between macOS versions, so we are lenient in parsing.
+fn ips_matches(text: &str, pid: pid_t) -> bool {
+  text.match_indices("\"pid\"").any(|(idx, key)| {
+    let rest = text[idx + key.len()..].trim_start();
+    rest
+      .strip_prefix(':')
+      .map(|rest| {
+        let rest = rest.trim_start();
+        let end = rest
+          .find(|c: char| !c.is_ascii_digit())
+          .unwrap_or(rest.len());
+        rest[..end].parse() == Ok(pid)
+      })
+      .unwrap_or(false)
+  })
+}
+
+
+/// Check whether the legacy `.crash` report `text` refers to process
+/// `pid`.
+///
+/// Such reports contain a line of the form `Process: <name> [<pid>]`.
+fn crash_matches(text: &str, pid: pid_t) -> bool {
+  let suffix = format!("[{}]", pid);
+  text
+    .lines()
+    .any(|line| line.starts_with("Process:") && line.trim_end().ends_with(&suffix))
+}
+
+
+/// Find the crash reports for `pid` in `dir`.
+fn find_reports(dir: &Path, pid: pid_t, reports: &mut Vec<PathBuf>) {
+  let entries = match read_dir(dir) {
+    Ok(entries) => entries,
+    Err(_) => return,
+  };
+
+  for entry in entries.flatten() {
+    let path = entry.path();
+    let matches = match path.extension().and_then(|ext| ext.to_str()) {
+      Some("ips") => read_to_string(&path)
+        .map(|text| ips_matches(&text, pid))
+        .unwrap_or(false),
+      Some("crash") => read_to_string(&path)
+        .map(|text| crash_matches(&text, pid))
+        .unwrap_or(false),
+      _ => false,
+    };
+
+    if matches {
+      reports.push(path);
+    }
+  }
+}
+
+
+/// Locate the artifacts the system created for the crashed process
+/// `pid`.
+///
+/// On macOS, core files are disabled by default. What is created in
+/// any case is a crash report, written by ReportCrash into
+/// `~/Library/Logs/DiagnosticReports` (or
+/// `/Library/Logs/DiagnosticReports` for system processes). This
+/// function searches both locations as well as `/cores`, the default
+/// location for core files.
+///
+/// Note that crash reports are written asynchronously and may only
+/// show up some time after the process terminated. Callers may want to
+/// retry for a while if nothing was found. This function is only
+/// available on macOS.
+pub fn locate_crash_artifacts(pid: pid_t) -> CrashArtifacts {
+  let mut dirs = Vec::with_capacity(2);
+  if let Some(home) = var_os("HOME") {
+    dirs.push(Path::new(&home).join("Library/Logs/DiagnosticReports"));
+  }
+  dirs.push(PathBuf::from("/Library/Logs/DiagnosticReports"));
+
+  let mut reports = Vec::new();
+  for dir in dirs {
+    let () = find_reports(&dir, pid, &mut reports);
+  }
+  reports.sort();
+
+  let core = Path::new("/cores").join(format!("core.{}", pid));
+  let core = if core.is_file() { Some(core) } else { None };
+
+  CrashArtifacts { reports, core }
+}
+
@@ -0,0 +1,148 @@
+// macros.rs
+
+// Copyright (C) 2026 Daniel Mueller <deso@posteo.net>
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+
+/// Assert that a boolean expression is `true`, dumping core at the
+/// assertion site if it is not.
+///
+/// This macro behaves like [`assert!`], except that on failure a
+/// snapshot core is created before the panic is raised. The core is
+/// created by a forked copy of the process, which is unaffected by any
+/// unwinding happening subsequently. As such, it captures the state of
+/// the program at the point of the assertion, even if the panic is
+/// eventually caught.
+///
+/// ```rust
+/// # use coredump::core_assert;
+/// let value = 42;
+/// core_assert!(value > 41);
+/// core_assert!(value < 43, "value {} out of bounds", value);
+/// ```
+#[macro_export]
+macro_rules! core_assert {
+  ($cond:expr $(,)?) => {
+    if !$cond {
+      $crate::__dump_snapshot();
+      panic!("{}", concat!("assertion failed: ", stringify!($cond)))
+    }
+  };
+  ($cond:expr, $($arg:tt)+) => {
+    if !$cond {
+      $crate::__dump_snapshot();
+      panic!($($arg)+)
+    }
+  };
+}
+
+
+/// Assert that two expressions are equal to each other, dumping core
+/// at the assertion site if they are not.
+///
+/// This macro behaves like [`assert_eq!`], except that on failure a
+/// snapshot core is created before the panic is raised. Please refer to
+/// [`core_assert!`] for details.
+///
+/// ```rust
+/// # use coredump::core_assert_eq;
+/// core_assert_eq!(1 + 1, 2);
+/// ```
+#[macro_export]
+macro_rules! core_assert_eq {
+  ($left:expr, $right:expr $(,)?) => {
+    match (&$left, &$right) {
+      (left, right) => {
+        if !(*left == *right) {
+          $crate::__dump_snapshot();
+          assert_eq!(left, right)
+        }
+      },
+    }
+  };
+  ($left:expr, $right:expr, $($arg:tt)+) => {
+    match (&$left, &$right) {
+      (left, right) => {
+        if !(*left == *right) {
+          $crate::__dump_snapshot();
+          assert_eq!(left, right, $($arg)+)
+        }
+      },
+    }
+  };
+}
+
+
+/// Assert that two expressions are not equal to each other, dumping
+/// core at the assertion site if they are.
+///
+/// This macro behaves like [`assert_ne!`], except that on failure a
+/// snapshot core is created before the panic is raised. Please refer to
+/// [`core_assert!`] for details.
+///
+/// ```rust
+/// # use coredump::core_assert_ne;
+/// core_assert_ne!(1 + 1, 3);
+/// ```
+#[macro_export]
+macro_rules! core_assert_ne {
+  ($left:expr, $right:expr $(,)?) => {
+    match (&$left, &$right) {
+      (left, right) => {
+        if *left == *right {
+          $crate::__dump_snapshot();
+          assert_ne!(left, right)
+        }
+      },
+    }
+  };
+  ($left:expr, $right:expr, $($arg:tt)+) => {
+    match (&$left, &$right) {
+      (left, right) => {
+        if *left == *right {
+          $crate::__dump_snapshot();
+          assert_ne!(left, right, $($arg)+)
+        }
+      },
+    }
+  };
+}
+
+
+/// Check that a boolean expression is `true`, recording the violation
+/// and creating a snapshot core if it is not, but without panicking.
+///
+/// This macro enables "capture evidence but don't crash" style checking
+/// of invariants in production. If the condition does not hold, the
+/// violation is reported on stderr, along with the location of the
+/// check and the optionally provided message. Furthermore, a snapshot
+/// core is created by a forked copy of the process. Execution then
+/// continues as usual.
+///
+/// To prevent a check in a hot code path from filling up the disk, a
+/// core is created only for the first violation at any given location.
+/// Subsequent violations are still reported.
+///
+/// The macro evaluates to the value of the condition.
+///
+/// ```rust
+/// # use coredump::ensure_core;
+/// let queue = vec![1, 2, 3];
+/// if !ensure_core!(queue.len() < 16, "queue grew to {} entries", queue.len()) {
+///   // Try to recover.
+/// }
+/// ```
+#[macro_export]
+macro_rules! ensure_core {
+  ($cond:expr $(,)?) => {
+    $crate::ensure_core!($cond, "{}", stringify!($cond))
+  };
+  ($cond:expr, $($arg:tt)+) => {{
+    let holds: bool = $cond;
+    if !holds {
+      static DUMPED: ::std::sync::atomic::AtomicBool = ::std::sync::atomic::AtomicBool::new(false);
+      $crate::__invariant_violated(file!(), line!(), column!(), format_args!($($arg)+), &DUMPED);
+    }
+    holds
+  }};
+}
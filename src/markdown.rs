@@ -0,0 +1,134 @@
+// markdown.rs
+
+// Copyright (C) 2026 Daniel Mueller <deso@posteo.net>
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+//! Markdown formatted bug reports, ready to be pasted into an issue.
+
+use std::backtrace::Backtrace;
+use std::env::current_exe;
+use std::ffi::CStr;
+use std::fmt::Write as _;
+use std::fs::write;
+use std::mem::zeroed;
+use std::panic::Location;
+use std::path::Path;
+use std::path::PathBuf;
+use std::process::id as pid;
+use std::time::SystemTime;
+use std::time::UNIX_EPOCH;
+
+use libc::uname;
+
+use crate::Error;
+use crate::Str;
+use crate::WithCtx;
+
+
+/// Describe the operating system we run on, e.g., `Linux 6.1.0
+/// x86_64`.
+fn os() -> Option<String> {
+  let mut name = unsafe { zeroed() };
+  if unsafe { uname(&mut name) } != 0 {
+    return None
+  }
+
+  let field = |field: &[_]| unsafe { CStr::from_ptr(field.as_ptr()) }.to_string_lossy();
+  Some(format!(
+    "{} {} {}",
+    field(&name.sysname),
+    field(&name.release),
+    field(&name.machine)
+  ))
+}
+
+
+/// Information about a crash to include in a bug report.
+pub(crate) struct Crash<'info> {
+  /// The application's version.
+  pub(crate) version: &'info str,
+  /// The panic message.
+  pub(crate) message: &'info str,
+  /// The location of the panic.
+  pub(crate) location: Option<&'info Location<'info>>,
+  /// The directory core files are dumped into.
+  pub(crate) dump_dir: &'info Path,
+  /// The predicted path of the core file.
+  pub(crate) core_path: Option<&'info Path>,
+  /// The path of the crash metadata file.
+  pub(crate) metadata: Option<&'info Path>,
+}
+
+impl Crash<'_> {
+  /// Format a bug report for the crash with ID `id`.
+  fn format(&self, id: &str) -> String {
+    let exe = current_exe()
+      .ok()
+      .and_then(|exe| {
+        exe
+          .file_name()
+          .map(|name| name.to_string_lossy().into_owned())
+      })
+      .unwrap_or_else(|| "<unknown>".to_string());
+
+    let mut report = String::new();
+    let _ = writeln!(report, "## Crash report\n");
+    let _ = writeln!(report, "- **Program:** `{}` {}", exe, self.version);
+    if let Some(os) = os() {
+      let _ = writeln!(report, "- **OS:** {}", os);
+    }
+    let _ = writeln!(report, "- **Crash ID:** `{}`", id);
+
+    let _ = writeln!(report, "\n### Panic\n");
+    let _ = writeln!(report, "```\n{}\n```", self.message);
+    if let Some(location) = self.location {
+      let _ = writeln!(report, "\nat `{}`", location);
+    }
+
+    let _ = writeln!(report, "\n### Backtrace\n");
+    let _ = writeln!(report, "```\n{}```", Backtrace::force_capture());
+
+    let _ = writeln!(report, "\n### Attachments\n");
+    match self.core_path {
+      Some(core) => {
+        let _ = writeln!(report, "The core file was written to `{}`.", core.display());
+      },
+      None => {
+        let _ = writeln!(
+          report,
+          "The core file was written to `{}` or an otherwise system specific location.",
+          self.dump_dir.display()
+        );
+      },
+    }
+    let _ = writeln!(
+      report,
+      "Core files may contain sensitive data, including passwords and keys. Please only \
+       attach it if you are comfortable sharing its contents or when asked to."
+    );
+    if let Some(metadata) = self.metadata {
+      let _ = writeln!(
+        report,
+        "\nPlease also attach the crash metadata from `{}`.",
+        metadata.display()
+      );
+    }
+    report
+  }
+
+  /// Write a bug report for the crash into a file in `dir`, returning
+  /// its path.
+  pub(crate) fn write(&self, dir: &Path) -> Result<PathBuf, (Str, Error)> {
+    let time = SystemTime::now()
+      .duration_since(UNIX_EPOCH)
+      .unwrap_or_default()
+      .as_secs();
+    let id = format!("{}-{}", pid(), time);
+    let path = dir.join(format!("coredump-{}.md", id));
+
+    write(&path, self.format(&id))
+      .map_err(Error::from)
+      .ctx(|| format!("failed to write bug report {}", path.display()))?;
+    Ok(path)
+  }
+}
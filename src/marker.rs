@@ -0,0 +1,133 @@
+// marker.rs
+
+// Copyright (C) 2026 Daniel Mueller <deso@posteo.net>
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+//! Marker files recording a crash for the next run of the program.
+
+use std::fs::read_to_string;
+use std::fs::remove_file;
+use std::fs::File;
+use std::io::Error as IoError;
+use std::io::ErrorKind;
+use std::io::Write as _;
+use std::path::Path;
+use std::path::PathBuf;
+use std::process::id as pid;
+use std::time::Duration;
+use std::time::SystemTime;
+use std::time::UNIX_EPOCH;
+
+use crate::Error;
+use crate::Str;
+use crate::WithCtx;
+
+
+/// Information about a crash of a previous run of the program.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct CrashInfo {
+  /// An identifier for the crash, of the form `<pid>-<secs>`. It
+  /// matches the one used in crash metadata file names.
+  pub id: String,
+  /// The ID of the crashed process.
+  pub pid: u32,
+  /// The time of the crash.
+  pub time: SystemTime,
+  /// The directory core files were dumped into.
+  pub dump_dir: PathBuf,
+  /// The path to the crash metadata file, if one was written.
+  pub metadata: Option<PathBuf>,
+}
+
+
+/// Write a crash marker to `path`.
+pub(crate) fn write(
+  path: &Path,
+  dump_dir: &Path,
+  metadata: Option<&Path>,
+) -> Result<(), (Str, Error)> {
+  let time = SystemTime::now()
+    .duration_since(UNIX_EPOCH)
+    .unwrap_or_default()
+    .as_secs();
+  let mut content = format!(
+    "id: {pid}-{time}\npid: {pid}\ntime: {time}\ndump-dir: {dir}\n",
+    pid = pid(),
+    time = time,
+    dir = dump_dir.display(),
+  );
+  if let Some(metadata) = metadata {
+    content += &format!("metadata: {}\n", metadata.display());
+  }
+
+  File::create(path)
+    .and_then(|mut file| file.write_all(content.as_bytes()))
+    .map_err(Error::from)
+    .ctx(|| format!("failed to write crash marker {}", path.display()))
+}
+
+
+/// Parse the contents of a crash marker.
+fn parse(content: &str) -> Option<CrashInfo> {
+  let mut id = None;
+  let mut pid = None;
+  let mut time = None;
+  let mut dump_dir = None;
+  let mut metadata = None;
+
+  for line in content.lines() {
+    let (key, value) = line.split_once(": ")?;
+    match key {
+      "id" => id = Some(value.to_string()),
+      "pid" => pid = Some(value.parse().ok()?),
+      "time" => time = Some(UNIX_EPOCH + Duration::from_secs(value.parse().ok()?)),
+      "dump-dir" => dump_dir = Some(PathBuf::from(value)),
+      "metadata" => metadata = Some(PathBuf::from(value)),
+      // Be lenient towards markers written by future versions.
+      _ => (),
+    }
+  }
+
+  Some(CrashInfo {
+    id: id?,
+    pid: pid?,
+    time: time?,
+    dump_dir: dump_dir?,
+    metadata,
+  })
+}
+
+
+/// Check whether a previous run of the program crashed.
+///
+/// This function consults the crash marker at `marker`, as written by
+/// a panic handler configured via
+/// [`Builder::crash_marker`][crate::Builder::crash_marker]. If the
+/// marker exists, it is removed and information about the crash is
+/// returned. That way, each crash is reported only once, which makes
+/// this function suitable for implementing "the program crashed last
+/// time" style flows at startup.
+pub fn check_previous_crash<P>(marker: P) -> Result<Option<CrashInfo>, (Str, Error)>
+where
+  P: AsRef<Path>,
+{
+  let marker = marker.as_ref();
+  let content = match read_to_string(marker) {
+    Ok(content) => content,
+    Err(err) if err.kind() == ErrorKind::NotFound => return Ok(None),
+    Err(err) => {
+      return Err(Error::from(err))
+        .ctx(|| format!("failed to read crash marker {}", marker.display()))
+    },
+  };
+
+  let info = parse(&content)
+    .ok_or_else(|| Error::from(IoError::new(ErrorKind::InvalidData, "malformed content")))
+    .ctx(|| format!("failed to parse crash marker {}", marker.display()))?;
+
+  let () = remove_file(marker)
+    .map_err(Error::from)
+    .ctx(|| format!("failed to remove crash marker {}", marker.display()))?;
+  Ok(Some(info))
+}
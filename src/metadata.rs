@@ -0,0 +1,147 @@
+// metadata.rs
+
+// Copyright (C) 2026 Daniel Mueller <deso@posteo.net>
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+//! Crash metadata written alongside core files.
+
+use std::env::current_exe;
+use std::fs::File;
+use std::io::Write;
+use std::mem::zeroed;
+use std::path::Path;
+use std::path::PathBuf;
+use std::process::id as pid;
+use std::thread::current as current_thread;
+use std::time::Duration;
+
+use libc::clock_gettime;
+use libc::clockid_t;
+use libc::CLOCK_MONOTONIC;
+use libc::CLOCK_REALTIME;
+
+use crate::Error;
+use crate::Str;
+use crate::WithCtx;
+
+
+/// Read the given clock.
+fn read_clock(clock: clockid_t) -> Option<Duration> {
+  let mut time = unsafe { zeroed() };
+  if unsafe { clock_gettime(clock, &mut time) } != 0 {
+    return None
+  }
+  Some(Duration::new(time.tv_sec as u64, time.tv_nsec as u32))
+}
+
+
+/// Format a duration as seconds with nanosecond precision.
+fn format_duration(duration: Duration) -> String {
+  format!("{}.{:09}", duration.as_secs(), duration.subsec_nanos())
+}
+
+
+/// Retrieve the time since boot at which the process got started.
+#[cfg(target_os = "linux")]
+fn process_start_time() -> Option<Duration> {
+  use std::fs::read_to_string;
+
+  use libc::sysconf;
+  use libc::_SC_CLK_TCK;
+
+  let stat = read_to_string("/proc/self/stat").ok()?;
+  // The second field, the executable name, is enclosed in parentheses
+  // and could contain spaces and parentheses itself. Hence, we look for
+  // the last closing one and start from there. The start time is the
+  // 22nd field overall.
+  let (_, fields) = stat.split_at(stat.rfind(')')? + 1);
+  let ticks = fields.split_whitespace().nth(19)?.parse::<u64>().ok()?;
+  let ticks_per_sec = unsafe { sysconf(_SC_CLK_TCK) };
+  if ticks_per_sec <= 0 {
+    return None
+  }
+  let ticks_per_sec = ticks_per_sec as u64;
+  Some(
+    Duration::from_secs(ticks / ticks_per_sec)
+      + Duration::from_nanos(ticks % ticks_per_sec * 1_000_000_000 / ticks_per_sec),
+  )
+}
+
+
+/// Metadata about a crash.
+#[derive(Debug, Default)]
+pub(crate) struct Metadata {
+  /// The metadata entries, as key-value pairs.
+  entries: Vec<(&'static str, String)>,
+}
+
+impl Metadata {
+  /// Collect metadata about the current process.
+  pub(crate) fn collect() -> Self {
+    let mut metadata = Self::default();
+    metadata.add("pid", pid().to_string());
+    if let Ok(exe) = current_exe() {
+      metadata.add("executable", exe.display().to_string());
+    }
+    if let Some(name) = current_thread().name() {
+      metadata.add("thread", name.to_string());
+    }
+    metadata.add_timestamps();
+    metadata
+  }
+
+  /// Add wall clock and monotonic timestamps as well as information
+  /// about the process' start time.
+  fn add_timestamps(&mut self) {
+    let realtime = read_clock(CLOCK_REALTIME);
+    if let Some(realtime) = realtime {
+      self.add("time-realtime", format_duration(realtime));
+    }
+    if let Some(monotonic) = read_clock(CLOCK_MONOTONIC) {
+      self.add("time-monotonic", format_duration(monotonic));
+    }
+
+    #[cfg(target_os = "linux")]
+    {
+      // The process start time is reported relative to boot, so we need
+      // the boot time clock to relate it to.
+      if let (Some(realtime), Some(boottime), Some(start)) = (
+        realtime,
+        read_clock(libc::CLOCK_BOOTTIME),
+        process_start_time(),
+      ) {
+        if let Some(uptime) = boottime.checked_sub(start) {
+          if let Some(start) = realtime.checked_sub(uptime) {
+            self.add("process-start-time", format_duration(start));
+          }
+          self.add("process-uptime", format_duration(uptime));
+        }
+      }
+    }
+  }
+
+  /// Add an entry.
+  pub(crate) fn add(&mut self, key: &'static str, value: String) {
+    self.entries.push((key, value))
+  }
+
+  /// Write the metadata into a file in `dir`, returning its path.
+  pub(crate) fn write(&self, dir: &Path) -> Result<PathBuf, (Str, Error)> {
+    let time = read_clock(CLOCK_REALTIME).unwrap_or_default().as_secs();
+    let path = dir.join(format!("coredump-{}-{}.txt", pid(), time));
+    let mut file = File::create(&path)
+      .map_err(Error::from)
+      .ctx(|| format!("failed to create metadata file {}", path.display()))?;
+
+    for (key, value) in &self.entries {
+      // Values could conceivably span multiple lines (think panic
+      // messages). Indent continuation lines so that the format stays
+      // parseable.
+      let value = value.replace('\n', "\n  ");
+      writeln!(file, "{}: {}", key, value)
+        .map_err(Error::from)
+        .ctx(|| format!("failed to write metadata file {}", path.display()))?;
+    }
+    Ok(path)
+  }
+}
@@ -0,0 +1,77 @@
+// priority.rs
+
+// Copyright (C) 2026 Daniel Mueller <deso@posteo.net>
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+//! Scheduling priorities to apply while dumping core.
+
+use std::os::raw::c_int;
+
+use libc::setpriority;
+use libc::PRIO_PROCESS;
+
+
+/// An I/O scheduling class along with its priority level, as used by
+/// `ioprio_set(2)`.
+///
+/// Levels range from 0 (highest priority) to 7 (lowest priority);
+/// larger values are clamped.
+#[cfg(target_os = "linux")]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum IoPriority {
+  /// The real-time class. Setting it requires privileges.
+  RealTime(u8),
+  /// The best-effort class, which is the default for processes.
+  BestEffort(u8),
+  /// The idle class, only getting disk time when nobody else needs it.
+  Idle,
+}
+
+#[cfg(target_os = "linux")]
+impl IoPriority {
+  /// Convert the priority into the representation understood by the
+  /// kernel.
+  fn to_raw(self) -> c_int {
+    const IOPRIO_CLASS_SHIFT: c_int = 13;
+
+    let (class, level) = match self {
+      Self::RealTime(level) => (1, level),
+      Self::BestEffort(level) => (2, level),
+      Self::Idle => (3, 0),
+    };
+    (class << IOPRIO_CLASS_SHIFT) | c_int::from(level.min(7))
+  }
+}
+
+
+/// The priorities to apply while dumping core.
+#[derive(Clone, Copy, Debug, Default)]
+pub(crate) struct Priority {
+  /// The CPU niceness to use.
+  pub(crate) nice: Option<c_int>,
+  /// The I/O priority to use.
+  #[cfg(target_os = "linux")]
+  pub(crate) io: Option<IoPriority>,
+}
+
+impl Priority {
+  /// Apply the priorities to the calling thread.
+  ///
+  /// This function is async-signal-safe. Errors are ignored, as
+  /// failing to adjust priorities should not prevent a dump.
+  pub(crate) fn apply(&self) {
+    // On Linux, these calls only affect the calling thread, which is
+    // the one the kernel writes the core file from if it is the one
+    // receiving the dump signal.
+    if let Some(nice) = self.nice {
+      let _ = unsafe { setpriority(PRIO_PROCESS, 0, nice) };
+    }
+
+    #[cfg(target_os = "linux")]
+    if let Some(io) = self.io {
+      const IOPRIO_WHO_PROCESS: c_int = 1;
+
+      let _ = unsafe { libc::syscall(libc::SYS_ioprio_set, IOPRIO_WHO_PROCESS, 0, io.to_raw()) };
+    }
+  }
+}
@@ -0,0 +1,123 @@
+// python.rs
+
+// Copyright (C) 2026 Daniel Mueller <deso@posteo.net>
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+//! Python bindings for the crate's functionality.
+//!
+//! The bindings are provided in the form of the [`coredump`] module
+//! initialization function, which has to be exposed by an extension
+//! module. The crate itself does not build one, because that requires a
+//! `cdylib` crate type. A minimal extension module crate looks like:
+//!
+//! ```rust,ignore
+//! use pyo3::prelude::*;
+//!
+//! #[pymodule]
+//! fn coredump(m: &Bound<'_, PyModule>) -> PyResult<()> {
+//!   ::coredump::python::coredump(m)
+//! }
+//! ```
+//!
+//! The Python module then provides the following functions:
+//! - `register(*, always_abort=False, watchdog=None, metadata=False)`:
+//!   register the panic handler, with `watchdog` being a timeout in
+//!   seconds
+//! - `trigger()`: dump core and terminate the process
+//! - `snapshot()`: create a snapshot core without terminating the
+//!   process
+//!
+//! Note that every Rust extension module links against its own copy of
+//! the Rust standard library and, hence, has its own panic hook. A
+//! panic handler registered via these bindings only covers panics
+//! originating in the extension module containing them. Extension
+//! modules written in Rust should therefore rather register the panic
+//! handler themselves. `trigger()` and `snapshot()`, on the other hand,
+//! are useful for any Python program wanting to capture its state.
+
+use std::time::Duration;
+
+use pyo3::exceptions::PyOSError;
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+
+use crate::dump_core_and_quit;
+use crate::dump_dir;
+use crate::dump_dir_fd;
+use crate::dump_fallbacks;
+use crate::dump_priority;
+use crate::dump_signal;
+use crate::dump_snapshot;
+use crate::raise_configured_limit;
+use crate::Builder;
+use crate::Error;
+use crate::HandlerPolicy;
+use crate::Str;
+
+
+/// Convert one of our errors into a Python exception.
+fn to_py_err((ctx, err): (Str, Error)) -> PyErr {
+  PyOSError::new_err(format!("{}: {}", ctx, err))
+}
+
+
+/// Register a panic handler that will cause the program to dump core.
+#[pyfunction]
+#[pyo3(signature = (*, always_abort=false, watchdog=None, metadata=false))]
+fn register(always_abort: bool, watchdog: Option<f64>, metadata: bool) -> PyResult<()> {
+  let watchdog = match watchdog {
+    Some(secs) if secs.is_finite() && secs >= 0.0 => Some(Duration::from_secs_f64(secs)),
+    Some(secs) => {
+      return Err(PyValueError::new_err(format!(
+        "invalid watchdog timeout: {}",
+        secs
+      )))
+    },
+    None => None,
+  };
+
+  let _registration = Builder::new()
+    .always_abort(always_abort)
+    .watchdog(watchdog)
+    .metadata(metadata)
+    .register()
+    .map_err(to_py_err)?;
+  Ok(())
+}
+
+
+/// Dump core and terminate the process.
+#[pyfunction]
+fn trigger() -> PyResult<()> {
+  raise_configured_limit().map_err(to_py_err)?;
+  dump_core_and_quit(
+    &dump_dir(),
+    dump_dir_fd().as_deref(),
+    None,
+    HandlerPolicy::default(),
+    dump_priority(),
+    dump_signal(),
+    &dump_fallbacks(),
+    || (),
+  )
+    .map_err(to_py_err)
+}
+
+
+/// Create a snapshot core without terminating the process.
+#[pyfunction]
+fn snapshot() -> PyResult<()> {
+  raise_configured_limit().map_err(to_py_err)?;
+  dump_snapshot(&dump_dir(), dump_priority(), dump_signal())
+    .map(|_pid| ())
+    .map_err(to_py_err)
+}
+
+
+/// Initialize the `coredump` Python module.
+pub fn coredump(m: &Bound<'_, PyModule>) -> PyResult<()> {
+  m.add_function(wrap_pyfunction!(register, m)?)?;
+  m.add_function(wrap_pyfunction!(trigger, m)?)?;
+  m.add_function(wrap_pyfunction!(snapshot, m)?)?;
+  Ok(())
+}
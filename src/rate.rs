@@ -0,0 +1,50 @@
+// rate.rs
+
+// Copyright (C) 2026 Daniel Mueller <deso@posteo.net>
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+//! Limiting of the rate at which core is dumped.
+
+use std::fs::read_to_string;
+use std::fs::write;
+use std::path::Path;
+use std::time::Duration;
+use std::time::SystemTime;
+use std::time::UNIX_EPOCH;
+
+
+/// The name of the file recording the time of the most recent dump.
+///
+/// The file lives in the dump directory, so that it is shared by all
+/// processes dumping there, including restarted instances of a crash
+/// looping service.
+const STAMP: &str = ".coredump-stamp";
+
+
+/// Check whether dumping core into `dir` is permitted, given that at
+/// least `interval` has to pass between two dumps, and record the dump
+/// if so.
+///
+/// The check is best effort: an unreadable stamp permits the dump and
+/// concurrently crashing processes may both end up dumping.
+pub(crate) fn acquire(dir: &Path, interval: Duration) -> bool {
+  let path = dir.join(STAMP);
+  let now = SystemTime::now()
+    .duration_since(UNIX_EPOCH)
+    .unwrap_or_default()
+    .as_secs();
+
+  let last = read_to_string(&path)
+    .ok()
+    .and_then(|content| content.trim().parse::<u64>().ok());
+  if let Some(last) = last {
+    // A stamp from the future, e.g., due to the clock having been
+    // adjusted, does not hold back dumps.
+    if last <= now && now - last < interval.as_secs() {
+      return false
+    }
+  }
+
+  let _ = write(&path, format!("{}\n", now));
+  true
+}
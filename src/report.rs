@@ -0,0 +1,435 @@
+// report.rs
+
+// Copyright (C) 2026 Daniel Mueller <deso@posteo.net>
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+//! A report on the system's and process' ability to dump core.
+
+#[cfg(any(target_os = "macos", target_os = "freebsd"))]
+use std::convert::TryInto as _;
+use std::fmt::Display;
+use std::fmt::Formatter;
+use std::fmt::Result as FmtResult;
+use std::fmt::Write as _;
+use std::os::raw::c_int;
+use std::path::Path;
+use std::path::PathBuf;
+
+use crate::config::core_pattern;
+use crate::config::pipe_helper;
+#[cfg(target_os = "linux")]
+use crate::config::read_value;
+#[cfg(target_os = "linux")]
+use crate::config::wsl_version;
+use crate::foreign_crash_handlers;
+use crate::is_chrooted;
+use crate::query_core_limit;
+use crate::resolve_dir;
+use crate::settings;
+#[cfg(any(target_os = "macos", target_os = "freebsd"))]
+use crate::sysctl;
+use crate::CoreLimit;
+
+
+/// Check whether the process is dumpable.
+#[cfg(target_os = "linux")]
+fn is_dumpable() -> Option<bool> {
+  use libc::prctl;
+  use libc::PR_GET_DUMPABLE;
+
+  match unsafe { prctl(PR_GET_DUMPABLE) } {
+    -1 => None,
+    result => Some(result != 0),
+  }
+}
+
+
+/// Check whether the process runs under an emulator, such as
+/// qemu-user.
+///
+/// Emulators like qemu-user fake `/proc/self/exe` to refer to the
+/// emulated program. The per-thread view of the same link is not
+/// covered, though, and refers to the emulator itself.
+#[cfg(target_os = "linux")]
+fn is_emulated() -> Option<bool> {
+  use std::fs::read_link;
+
+  use libc::syscall;
+  use libc::SYS_gettid;
+
+  let tid = unsafe { syscall(SYS_gettid) };
+  let exe = read_link("/proc/self/exe").ok()?;
+  let thread_exe = read_link(format!("/proc/self/task/{}/exe", tid)).ok()?;
+  Some(exe != thread_exe)
+}
+
+
+/// Identify the C library the process runs against.
+///
+/// glibc is able to tell its version at run time. musl and uClibc-ng
+/// do not expose theirs, so only the name is reported.
+fn c_library() -> Option<String> {
+  #[cfg(all(target_os = "linux", target_env = "gnu"))]
+  {
+    use std::ffi::CStr;
+
+    let version = unsafe { libc::gnu_get_libc_version() };
+    if version.is_null() {
+      return None
+    }
+    let version = unsafe { CStr::from_ptr(version) };
+    Some(format!("glibc {}", version.to_string_lossy()))
+  }
+  #[cfg(target_env = "musl")]
+  {
+    Some("musl".to_string())
+  }
+  #[cfg(target_env = "uclibc")]
+  {
+    Some("uClibc-ng".to_string())
+  }
+  #[cfg(not(any(
+    all(target_os = "linux", target_env = "gnu"),
+    target_env = "musl",
+    target_env = "uclibc"
+  )))]
+  {
+    None
+  }
+}
+
+
+/// Escape a string for inclusion in a JSON document, including the
+/// surrounding quotes.
+fn json_str(s: &str) -> String {
+  let mut json = String::with_capacity(s.len() + 2);
+  json.push('"');
+  for c in s.chars() {
+    match c {
+      '"' => json.push_str("\\\""),
+      '\\' => json.push_str("\\\\"),
+      '\n' => json.push_str("\\n"),
+      '\r' => json.push_str("\\r"),
+      '\t' => json.push_str("\\t"),
+      c if c.is_control() => {
+        let _ = write!(json, "\\u{:04x}", c as u32);
+      },
+      c => json.push(c),
+    }
+  }
+  json.push('"');
+  json
+}
+
+
+/// Format an optional value as JSON, using `null` if it is absent.
+fn json_opt<T, F>(value: Option<T>, f: F) -> String
+where
+  F: FnOnce(T) -> String,
+{
+  value.map(f).unwrap_or_else(|| "null".to_string())
+}
+
+
+/// A report on the ability to dump core.
+///
+/// Every piece of information is gathered on a best effort basis.
+/// Values that could not be determined, for example because `/proc` is
+/// not available, are reported as `None`.
+#[derive(Clone, Debug)]
+#[non_exhaustive]
+pub struct Report {
+  /// The kernel's core file name pattern
+  /// (`/proc/sys/kernel/core_pattern` or, on macOS and FreeBSD, the
+  /// `kern.corefile` sysctl, or, on NetBSD, the `proc.curproc.corename`
+  /// sysctl).
+  pub core_pattern: Option<String>,
+  /// The number of core dumps piped to a helper that may be processed
+  /// concurrently (`/proc/sys/kernel/core_pipe_limit`).
+  pub core_pipe_limit: Option<u32>,
+  /// The dump policy for set-user-ID and similar binaries
+  /// (`/proc/sys/fs/suid_dumpable` or, on macOS and FreeBSD, the
+  /// `kern.sugid_coredump` sysctl).
+  pub suid_dumpable: Option<u32>,
+  /// Whether the process is dumpable (see `PR_GET_DUMPABLE` in
+  /// `prctl(2)`). On macOS and FreeBSD, whether core dumps are enabled
+  /// system wide (the `kern.coredump` sysctl).
+  pub dumpable: Option<bool>,
+  /// The core file size limit currently in effect.
+  pub limit: Option<CoreLimit>,
+  /// The directory core files are dumped into.
+  pub dump_dir: Option<PathBuf>,
+  /// Whether a panic handler is registered in this process.
+  pub registered: bool,
+  /// The crash signals that have a non-default disposition.
+  pub foreign_handlers: Vec<c_int>,
+  /// Whether the process appears to be confined to a chroot.
+  pub chrooted: Option<bool>,
+  /// The C library in use on Linux, e.g., `glibc 2.39` or `musl`.
+  pub libc: Option<String>,
+  /// The version of the Windows Subsystem for Linux the process runs
+  /// under (1 or 2), or `None` if it does not run under it.
+  pub wsl: Option<u32>,
+  /// Whether the process runs under a user space emulator, such as
+  /// qemu-user.
+  pub emulated: Option<bool>,
+}
+
+impl Report {
+  /// Gather a report about the current process.
+  pub fn collect() -> Self {
+    let core_pattern = core_pattern();
+    #[cfg(target_os = "linux")]
+    let (core_pipe_limit, suid_dumpable, dumpable) = (
+      read_value("/proc/sys/kernel/core_pipe_limit"),
+      read_value("/proc/sys/fs/suid_dumpable"),
+      is_dumpable(),
+    );
+    #[cfg(any(target_os = "macos", target_os = "freebsd"))]
+    let (core_pipe_limit, suid_dumpable, dumpable) = (
+      None,
+      sysctl::int("kern.sugid_coredump").and_then(|value| value.try_into().ok()),
+      sysctl::int("kern.coredump").map(|value| value != 0),
+    );
+    #[cfg(not(any(target_os = "linux", target_os = "macos", target_os = "freebsd")))]
+    let (core_pipe_limit, suid_dumpable, dumpable) = (None, None, None);
+    #[cfg(target_os = "linux")]
+    let (wsl, emulated) = (wsl_version(), is_emulated());
+    #[cfg(not(target_os = "linux"))]
+    let (wsl, emulated) = (None, None);
+
+    let settings = settings();
+    let registered = settings.is_some();
+    let dump_dir = settings
+      .map(|settings| settings.dir.clone())
+      .or_else(|| resolve_dir(&[], None, &mut Vec::new()).ok());
+
+    Self {
+      core_pattern,
+      core_pipe_limit,
+      suid_dumpable,
+      dumpable,
+      limit: query_core_limit()
+        .ok()
+        .map(|limit| CoreLimit::from_raw(&limit)),
+      dump_dir,
+      registered,
+      foreign_handlers: foreign_crash_handlers().unwrap_or_default(),
+      chrooted: is_chrooted(),
+      libc: c_library(),
+      wsl,
+      emulated,
+    }
+  }
+
+  /// Check whether core files are piped to a helper program instead of
+  /// being written to a file.
+  pub fn pipes_to_helper(&self) -> Option<bool> {
+    self
+      .core_pattern
+      .as_ref()
+      .map(|pattern| pattern.starts_with('|'))
+  }
+
+  /// Retrieve a list of problems preventing the creation of core files.
+  ///
+  /// An empty list means that no problems were found, not that core
+  /// files are guaranteed to be created.
+  pub fn problems(&self) -> Vec<String> {
+    let mut problems = Vec::new();
+    if let Some(CoreLimit { hard: Some(0), .. }) = self.limit {
+      problems.push("hard core file size limit is zero".to_string());
+    }
+    if self.dumpable == Some(false) {
+      problems.push("process is not dumpable".to_string());
+    }
+    if self.dump_dir.is_none() {
+      problems.push("no usable dump directory found".to_string());
+    }
+    if self.core_pattern.as_deref() == Some("") {
+      problems.push("core pattern is empty".to_string());
+    }
+    match self.wsl {
+      Some(1) => problems.push("core dumps are not supported by WSL1".to_string()),
+      // WSL2 commonly pipes core files to a helper that is not
+      // available inside the distribution.
+      Some(_) => {
+        if let Some(helper) = self.core_pattern.as_deref().and_then(pipe_helper) {
+          if !Path::new(helper).exists() {
+            problems.push(format!("core pattern helper {} does not exist", helper));
+          }
+        }
+      },
+      None => (),
+    }
+    if self.emulated == Some(true) {
+      // qemu-user, for example, handles signals itself and writes core
+      // files of its own, in the working directory.
+      problems.push(
+        "process runs under an emulator; core files may be missing or named differently"
+          .to_string(),
+      );
+    }
+    problems
+  }
+
+  /// Retrieve the names of the fields whose values could not be
+  /// determined.
+  ///
+  /// On systems with a minimal or no `/proc` file system, for example,
+  /// kernel settings are unknown. Such settings are not considered
+  /// problems.
+  pub fn unknowns(&self) -> Vec<&'static str> {
+    let fields = [
+      ("core_pattern", self.core_pattern.is_none()),
+      ("core_pipe_limit", self.core_pipe_limit.is_none()),
+      ("suid_dumpable", self.suid_dumpable.is_none()),
+      ("dumpable", self.dumpable.is_none()),
+      ("limit", self.limit.is_none()),
+      ("chrooted", self.chrooted.is_none()),
+      ("emulated", self.emulated.is_none()),
+    ];
+
+    fields
+      .iter()
+      .filter(|(_, unknown)| *unknown)
+      .map(|(name, _)| *name)
+      .collect()
+  }
+
+  /// Render the report as a JSON object.
+  ///
+  /// Keys are named after the fields of this type. Unknown values are
+  /// rendered as `null`, as are unlimited core file size limits. The
+  /// object additionally contains the lists of [`Report::unknowns`]
+  /// and [`Report::problems`].
+  pub fn to_json(&self) -> String {
+    let limit = |limit: Option<u64>| json_opt(limit, |limit| limit.to_string());
+    let handlers = self
+      .foreign_handlers
+      .iter()
+      .map(|signal| signal.to_string())
+      .collect::<Vec<_>>();
+    let unknowns = self
+      .unknowns()
+      .iter()
+      .map(|name| json_str(name))
+      .collect::<Vec<_>>();
+    let problems = self
+      .problems()
+      .iter()
+      .map(|problem| json_str(problem))
+      .collect::<Vec<_>>();
+
+    let fields = [
+      (
+        "core_pattern",
+        json_opt(self.core_pattern.as_deref(), json_str),
+      ),
+      (
+        "core_pipe_limit",
+        json_opt(self.core_pipe_limit, |limit| limit.to_string()),
+      ),
+      (
+        "suid_dumpable",
+        json_opt(self.suid_dumpable, |value| value.to_string()),
+      ),
+      (
+        "dumpable",
+        json_opt(self.dumpable, |dumpable| dumpable.to_string()),
+      ),
+      (
+        "limit",
+        json_opt(self.limit, |core_limit| {
+          format!(
+            "{{\"soft\":{},\"hard\":{}}}",
+            limit(core_limit.soft),
+            limit(core_limit.hard)
+          )
+        }),
+      ),
+      (
+        "dump_dir",
+        json_opt(self.dump_dir.as_ref(), |dir| {
+          json_str(&dir.to_string_lossy())
+        }),
+      ),
+      ("registered", self.registered.to_string()),
+      ("foreign_handlers", format!("[{}]", handlers.join(","))),
+      (
+        "chrooted",
+        json_opt(self.chrooted, |chrooted| chrooted.to_string()),
+      ),
+      ("libc", json_opt(self.libc.as_deref(), json_str)),
+      ("wsl", json_opt(self.wsl, |wsl| wsl.to_string())),
+      (
+        "emulated",
+        json_opt(self.emulated, |emulated| emulated.to_string()),
+      ),
+      ("unknowns", format!("[{}]", unknowns.join(","))),
+      ("problems", format!("[{}]", problems.join(","))),
+    ];
+
+    let fields = fields
+      .iter()
+      .map(|(key, value)| format!("{}:{}", json_str(key), value))
+      .collect::<Vec<_>>();
+    format!("{{{}}}", fields.join(","))
+  }
+}
+
+impl Display for Report {
+  fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+    fn opt<T: Display>(value: Option<T>) -> String {
+      value
+        .map(|value| value.to_string())
+        .unwrap_or_else(|| "unknown".to_string())
+    }
+
+    let limit = |limit: Option<u64>| {
+      limit
+        .map(|limit| limit.to_string())
+        .unwrap_or_else(|| "unlimited".to_string())
+    };
+
+    writeln!(f, "core pattern: {}", opt(self.core_pattern.as_deref()))?;
+    writeln!(f, "core pipe limit: {}", opt(self.core_pipe_limit))?;
+    writeln!(f, "suid dumpable: {}", opt(self.suid_dumpable))?;
+    writeln!(f, "dumpable: {}", opt(self.dumpable))?;
+    match self.limit {
+      Some(core_limit) => writeln!(
+        f,
+        "core file size limit: {} (soft), {} (hard)",
+        limit(core_limit.soft),
+        limit(core_limit.hard)
+      )?,
+      None => writeln!(f, "core file size limit: unknown")?,
+    }
+    writeln!(
+      f,
+      "dump directory: {}",
+      opt(self.dump_dir.as_ref().map(|dir| dir.display()))
+    )?;
+    writeln!(f, "registered: {}", self.registered)?;
+    writeln!(f, "foreign crash handlers: {:?}", self.foreign_handlers)?;
+    writeln!(f, "chrooted: {}", opt(self.chrooted))?;
+    writeln!(f, "libc: {}", opt(self.libc.as_deref()))?;
+    match self.wsl {
+      Some(wsl) => writeln!(f, "wsl: WSL{}", wsl)?,
+      None => writeln!(f, "wsl: no")?,
+    }
+    writeln!(f, "emulated: {}", opt(self.emulated))?;
+
+    let unknowns = self.unknowns();
+    if !unknowns.is_empty() {
+      writeln!(f, "unknown: {}", unknowns.join(", "))?;
+    }
+
+    let problems = self.problems();
+    if problems.is_empty() {
+      write!(f, "problems: none")
+    } else {
+      write!(f, "problems: {}", problems.join("; "))
+    }
+  }
+}
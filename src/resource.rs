@@ -0,0 +1,33 @@
+// resource.rs
+
+// Copyright (C) 2026 Daniel Mueller <deso@posteo.net>
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+//! The interface for retrieving and adjusting resource limits.
+//!
+//! On 32 bit Linux systems `rlim_t` is 32 bits wide, making the plain
+//! interface unable to represent limits beyond 4 GiB, which core files
+//! of large processes easily exceed. Hence, we use the 64 bit variant
+//! (which is what the plain one maps to on 64 bit systems) there.
+
+#[cfg(target_os = "linux")]
+pub(crate) use libc::getrlimit64 as getrlimit;
+#[cfg(target_os = "linux")]
+pub(crate) use libc::rlim64_t as rlim_t;
+#[cfg(target_os = "linux")]
+pub(crate) use libc::rlimit64 as rlimit;
+#[cfg(target_os = "linux")]
+pub(crate) use libc::setrlimit64 as setrlimit;
+#[cfg(target_os = "linux")]
+pub(crate) use libc::RLIM64_INFINITY as RLIM_INFINITY;
+
+#[cfg(not(target_os = "linux"))]
+pub(crate) use libc::getrlimit;
+#[cfg(not(target_os = "linux"))]
+pub(crate) use libc::rlim_t;
+#[cfg(not(target_os = "linux"))]
+pub(crate) use libc::rlimit;
+#[cfg(not(target_os = "linux"))]
+pub(crate) use libc::setrlimit;
+#[cfg(not(target_os = "linux"))]
+pub(crate) use libc::RLIM_INFINITY;
@@ -0,0 +1,42 @@
+// sample.rs
+
+// Copyright (C) 2026 Daniel Mueller <deso@posteo.net>
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+//! Sampling of crashes to dump core for.
+
+use std::panic::Location;
+
+
+/// Calculate the signature of a crash, identifying "the same" crash
+/// across processes and machines.
+///
+/// The signature is derived from the panic location if available and
+/// from the panic message otherwise. We use FNV-1a as hash function,
+/// because contrary to the standard library's hashers its output is
+/// guaranteed to be stable.
+pub(crate) fn signature(location: Option<&Location<'_>>, message: &str) -> u64 {
+  const OFFSET_BASIS: u64 = 0xcbf2_9ce4_8422_2325;
+  const PRIME: u64 = 0x0000_0100_0000_01b3;
+
+  let input = match location {
+    Some(location) => format!(
+      "{}:{}:{}",
+      location.file(),
+      location.line(),
+      location.column()
+    ),
+    None => message.to_string(),
+  };
+
+  input.bytes().fold(OFFSET_BASIS, |hash, byte| {
+    (hash ^ u64::from(byte)).wrapping_mul(PRIME)
+  })
+}
+
+
+/// Check whether the crash with the given signature is part of the
+/// sample of `percent` percent of crashes to dump core for.
+pub(crate) fn is_sampled(signature: u64, percent: u8) -> bool {
+  signature % 100 < u64::from(percent)
+}
@@ -0,0 +1,233 @@
+// signal.rs
+
+// Copyright (C) 2026 Daniel Mueller <deso@posteo.net>
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+//! Utilities for saving, chaining, and restoring signal actions.
+//!
+//! Combining multiple native signal handlers in a single process (say,
+//! a crash reporter and a runtime handling SIGSEGV for its own
+//! purposes) requires each of them to save the previously installed
+//! action and to either chain to it or restore it. The types in here
+//! encapsulate the fiddly parts of doing so.
+//!
+//! ```rust,no_run
+//! use std::os::raw::c_int;
+//! use std::os::raw::c_void;
+//!
+//! use coredump::signal::SavedAction;
+//!
+//! static mut PREVIOUS: Option<SavedAction> = None;
+//!
+//! extern "C" fn handler(signal: c_int, info: *mut libc::siginfo_t, context: *mut c_void) {
+//!   // Do our own business, then defer to whoever was there before us.
+//!   if let Some(previous) = unsafe { PREVIOUS } {
+//!     let _chained = unsafe { previous.chain(signal, info, context) };
+//!   }
+//! }
+//!
+//! let previous = SavedAction::install(libc::SIGUSR1, handler, 0).unwrap();
+//! unsafe { PREVIOUS = Some(previous) };
+//! ```
+
+use std::mem::transmute;
+use std::mem::zeroed;
+use std::os::raw::c_int;
+use std::os::raw::c_void;
+use std::ptr::null;
+use std::ptr::null_mut;
+
+use libc::pthread_sigmask;
+use libc::sigaction;
+use libc::sigaddset;
+use libc::sigemptyset;
+use libc::sighandler_t;
+use libc::siginfo_t;
+use libc::sigset_t;
+use libc::SA_NODEFER;
+use libc::SA_RESETHAND;
+use libc::SA_SIGINFO;
+use libc::SIG_BLOCK;
+use libc::SIG_DFL;
+use libc::SIG_IGN;
+use libc::SIG_SETMASK;
+use libc::SIG_UNBLOCK;
+
+use crate::check;
+use crate::Error;
+use crate::Str;
+use crate::WithCtx;
+
+
+/// The type of a signal handler as installed by
+/// [`SavedAction::install`].
+pub type Handler = extern "C" fn(c_int, *mut siginfo_t, *mut c_void);
+
+
+/// Install the given raw action for `signal`, returning the previously
+/// installed one.
+fn replace(signal: c_int, action: &sigaction) -> Result<SavedAction, Error> {
+  let mut previous = unsafe { zeroed::<sigaction>() };
+  check(unsafe { sigaction(signal, action, &mut previous) }, -1)?;
+  Ok(SavedAction {
+    signal,
+    action: previous,
+  })
+}
+
+
+/// A signal action as saved at some point in time.
+///
+/// Objects of this type are `Copy` and contain no references, making
+/// them suitable for storage in a `static` accessed from within a
+/// signal handler.
+#[derive(Clone, Copy)]
+pub struct SavedAction {
+  /// The signal the action belongs to.
+  signal: c_int,
+  /// The saved action.
+  action: sigaction,
+}
+
+impl SavedAction {
+  /// Save the action currently installed for `signal`.
+  ///
+  /// This method is async-signal-safe.
+  pub fn save(signal: c_int) -> Result<Self, (Str, Error)> {
+    let mut action = unsafe { zeroed::<sigaction>() };
+    check(unsafe { sigaction(signal, null(), &mut action) }, -1)
+      .ctx(|| "failed to inquire signal disposition")?;
+    Ok(Self { signal, action })
+  }
+
+  /// Install `handler` for `signal`, returning the previously installed
+  /// action.
+  ///
+  /// `SA_SIGINFO` is always added to `flags`. All signals are unblocked
+  /// while the handler runs (except for `signal` itself, unless
+  /// `SA_NODEFER` is passed in).
+  pub fn install(signal: c_int, handler: Handler, flags: c_int) -> Result<Self, (Str, Error)> {
+    let mut action = unsafe { zeroed::<sigaction>() };
+    action.sa_sigaction = handler as sighandler_t;
+    // The type of `sa_flags` (and of the `SA_*` constants) varies
+    // between C libraries, e.g., uClibc uses an unsigned type.
+    action.sa_flags = flags as _;
+    action.sa_flags |= SA_SIGINFO;
+    let () = check(unsafe { sigemptyset(&mut action.sa_mask) }, -1)
+      .ctx(|| "failed to initialize signal mask")?;
+    replace(signal, &action).ctx(|| "failed to install signal handler")
+  }
+
+  /// Reset the action for `signal` to the default one, returning the
+  /// previously installed action.
+  ///
+  /// This method is async-signal-safe.
+  pub fn reset(signal: c_int) -> Result<Self, (Str, Error)> {
+    let mut action = unsafe { zeroed::<sigaction>() };
+    action.sa_sigaction = SIG_DFL;
+    let () = check(unsafe { sigemptyset(&mut action.sa_mask) }, -1)
+      .ctx(|| "failed to initialize signal mask")?;
+    replace(signal, &action).ctx(|| "failed to reset signal disposition")
+  }
+
+  /// Retrieve the signal this action belongs to.
+  #[inline]
+  pub fn signal(&self) -> c_int {
+    self.signal
+  }
+
+  /// Check whether the action represents the signal's default
+  /// disposition.
+  #[inline]
+  pub fn is_default(&self) -> bool {
+    self.action.sa_sigaction == SIG_DFL
+  }
+
+  /// Check whether the action represents the signal being ignored.
+  #[inline]
+  pub fn is_ignored(&self) -> bool {
+    self.action.sa_sigaction == SIG_IGN
+  }
+
+  /// Retrieve the raw underlying `sigaction` object.
+  #[inline]
+  pub fn as_raw(&self) -> &sigaction {
+    &self.action
+  }
+
+  /// Invoke the saved handler, if any, with the given arguments.
+  ///
+  /// This method is meant to be called from within a signal handler
+  /// that got installed on top of the saved action. It returns `true`
+  /// if a handler was invoked and `false` if the saved action is the
+  /// default disposition or ignores the signal. In the former case, a
+  /// caller will typically want to [`restore`][Self::restore] the
+  /// action and re-raise the signal, in order for the default action
+  /// (e.g., termination with a core dump) to take place.
+  ///
+  /// The saved handler is invoked the way the system would have:
+  /// - the signals in its `sa_mask` are blocked while it runs and
+  ///   `signal` itself is blocked as well, unless it was installed
+  ///   with `SA_NODEFER`; the signal mask is restored once it returns
+  /// - if it was installed with `SA_RESETHAND`, the disposition of
+  ///   `signal` is reset to the default before it is invoked, which
+  ///   also removes the handler we are called from
+  ///
+  /// `SA_ONSTACK` and `SA_RESTART` are not emulated: the handler runs
+  /// on the stack of the current handler and whether interrupted
+  /// system calls are restarted depends on the flags of the latter.
+  ///
+  /// This method is async-signal-safe.
+  ///
+  /// # Safety
+  /// `info` and `context` have to be the values passed to the current
+  /// signal handler (or null pointers, if the saved handler is known
+  /// to cope with them). Furthermore, the saved handler has to be safe
+  /// to invoke in the current context.
+  pub unsafe fn chain(&self, signal: c_int, info: *mut siginfo_t, context: *mut c_void) -> bool {
+    if self.is_default() || self.is_ignored() {
+      return false
+    }
+
+    if self.action.sa_flags & SA_RESETHAND != 0 {
+      let _ = Self::reset(signal);
+    }
+
+    // Errors are impossible with the arguments we pass in, and there is
+    // nobody to report them to anyway.
+    let mut mask = unsafe { zeroed::<sigset_t>() };
+    let _ = unsafe { pthread_sigmask(SIG_BLOCK, &self.action.sa_mask, &mut mask) };
+    let mut set = unsafe { zeroed::<sigset_t>() };
+    let _ = unsafe { sigemptyset(&mut set) };
+    let _ = unsafe { sigaddset(&mut set, signal) };
+    let how = if self.action.sa_flags & SA_NODEFER != 0 {
+      SIG_UNBLOCK
+    } else {
+      SIG_BLOCK
+    };
+    let _ = unsafe { pthread_sigmask(how, &set, null_mut()) };
+
+    if self.action.sa_flags & SA_SIGINFO != 0 {
+      let handler = transmute::<sighandler_t, Handler>(self.action.sa_sigaction);
+      handler(signal, info, context);
+    } else {
+      let handler = transmute::<sighandler_t, extern "C" fn(c_int)>(self.action.sa_sigaction);
+      handler(signal);
+    }
+
+    let _ = unsafe { pthread_sigmask(SIG_SETMASK, &mask, null_mut()) };
+    true
+  }
+
+  /// Restore the saved action, making it the one in effect for the
+  /// signal.
+  ///
+  /// This method is async-signal-safe.
+  pub fn restore(&self) -> Result<(), (Str, Error)> {
+    check(
+      unsafe { sigaction(self.signal, &self.action, null_mut()) },
+      -1,
+    )
+    .ctx(|| "failed to restore signal disposition")
+  }
+}
@@ -0,0 +1,78 @@
+// suppress.rs
+
+// Copyright (C) 2026 Daniel Mueller <deso@posteo.net>
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+//! Temporary suppression of core dumps for regions of code.
+
+use std::cell::Cell;
+use std::marker::PhantomData;
+
+
+thread_local! {
+  /// The number of active suppressions on this thread.
+  static DEPTH: Cell<usize> = const { Cell::new(0) };
+}
+
+
+/// Check whether core dumps are suppressed on the calling thread.
+pub(crate) fn is_suppressed() -> bool {
+  DEPTH.with(|depth| depth.get() > 0)
+}
+
+
+/// A guard suppressing core dumps for panics on the calling thread for
+/// as long as it is alive.
+///
+/// Objects of this type are created by [`suppress`]. Suppressions
+/// nest: core dumps are re-enabled once all guards are dropped.
+#[derive(Debug)]
+#[must_use = "core dumps are only suppressed while the guard is alive"]
+pub struct Suppression {
+  /// The guard is bound to the thread it was created on.
+  _thread: PhantomData<*const ()>,
+}
+
+impl Drop for Suppression {
+  fn drop(&mut self) {
+    DEPTH.with(|depth| depth.set(depth.get() - 1))
+  }
+}
+
+
+/// Suppress core dumps for panics on the calling thread until the
+/// returned guard is dropped.
+///
+/// While suppressed, panics are left to the panic hook installed prior
+/// to registration, as if no handler had been registered. That is
+/// useful for regions of code in which panics are expected and caught,
+/// for example while handling untrusted input. Panics on other threads
+/// are unaffected.
+pub fn suppress() -> Suppression {
+  DEPTH.with(|depth| depth.set(depth.get() + 1));
+  Suppression {
+    _thread: PhantomData,
+  }
+}
+
+
+/// Invoke `f` with core dumps being suppressed for panics on the
+/// calling thread.
+///
+/// Please refer to [`suppress`] for details.
+///
+/// ```rust
+/// use std::panic::catch_unwind;
+///
+/// let result = coredump::with_coredumps_disabled(|| {
+///   catch_unwind(|| panic!("expected panic"))
+/// });
+/// assert!(result.is_err());
+/// ```
+pub fn with_coredumps_disabled<F, R>(f: F) -> R
+where
+  F: FnOnce() -> R,
+{
+  let _suppression = suppress();
+  f()
+}
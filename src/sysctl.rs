@@ -0,0 +1,86 @@
+// sysctl.rs
+
+// Copyright (C) 2026 Daniel Mueller <deso@posteo.net>
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+//! Retrieval of kernel state via `sysctl(3)`, as used for core dump
+//! configuration on BSD derived systems.
+
+use std::ffi::CString;
+use std::io::Error as IoError;
+use std::io::ErrorKind;
+use std::mem::size_of;
+use std::os::raw::c_int;
+use std::path::Path;
+use std::ptr::null_mut;
+
+use libc::sysctlbyname;
+
+use crate::config::CoreConfig;
+use crate::validate_writable_dir;
+use crate::Error;
+use crate::Str;
+use crate::WithCtx;
+
+
+/// Read the raw value of the variable with the given name into
+/// `buffer`, returning the number of bytes read.
+fn read(name: &str, buffer: &mut [u8]) -> Option<usize> {
+  let name = CString::new(name).ok()?;
+  let mut len = buffer.len();
+  let result = unsafe {
+    sysctlbyname(
+      name.as_ptr(),
+      buffer.as_mut_ptr().cast(),
+      &mut len,
+      null_mut(),
+      0,
+    )
+  };
+  if result != 0 {
+    return None
+  }
+  Some(len)
+}
+
+
+/// Read an integer variable.
+pub(crate) fn int(name: &str) -> Option<c_int> {
+  let mut buffer = [0u8; size_of::<c_int>()];
+  let len = read(name, &mut buffer)?;
+  if len != buffer.len() {
+    return None
+  }
+  Some(c_int::from_ne_bytes(buffer))
+}
+
+
+/// Read a string variable.
+pub(crate) fn string(name: &str) -> Option<String> {
+  // Core file name patterns are limited to `MAXPATHLEN` bytes.
+  let mut buffer = [0u8; 1024];
+  let len = read(name, &mut buffer)?;
+  let string = &buffer[..len];
+  let end = string.iter().position(|b| *b == 0).unwrap_or(len);
+  String::from_utf8(string[..end].to_vec()).ok()
+}
+
+
+
+/// Check that the system is configured to dump core.
+///
+/// Core dumps have to be enabled system wide, via the `kern.coredump`
+/// sysctl, and the directory core files get written to (as per
+/// `kern.corefile`) has to be writable. If the setting cannot be
+/// inquired, we optimistically assume core dumps to be enabled.
+pub(crate) fn check_config(config: &CoreConfig) -> Result<(), (Str, Error)> {
+  if int("kern.coredump") == Some(0) {
+    Err(IoError::new(ErrorKind::Other, "kern.coredump is zero").into())
+      .ctx(|| "core dumps are disabled")?;
+  }
+
+  if let Some(dir) = config.core_path.as_deref().and_then(Path::parent) {
+    let () = validate_writable_dir(dir)?;
+  }
+  Ok(())
+}
@@ -0,0 +1,109 @@
+// task.rs
+
+// Copyright (C) 2026 Daniel Mueller <deso@posteo.net>
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+//! Integration with asynchronous runtimes.
+//!
+//! Runtimes such as `async-std` or `smol` catch panics of spawned tasks
+//! and make them available to whoever awaits the task. Terminating the
+//! process because of such a panic is usually undesired. By wrapping a
+//! task's future using [`wrap`], a panic while polling it results in a
+//! snapshot core being created by a forked copy of the process, while
+//! execution continues as usual. If crash metadata are enabled (see
+//! [`Builder::metadata`][crate::Builder::metadata]), they will contain
+//! the name of the task.
+//!
+//! ```rust,ignore
+//! let task = async_std::task::spawn(coredump::task::wrap("worker", async {
+//!   // ...
+//! }));
+//! ```
+//!
+//! The functionality is runtime agnostic and only takes effect if a
+//! panic handler was registered.
+
+use std::cell::RefCell;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::Context;
+use std::task::Poll;
+
+
+thread_local! {
+  /// The name of the task currently being polled on this thread, if
+  /// any.
+  static CURRENT: RefCell<Option<Arc<str>>> = const { RefCell::new(None) };
+}
+
+
+/// Retrieve the name of the task currently being polled on the calling
+/// thread.
+pub(crate) fn current() -> Option<Arc<str>> {
+  CURRENT.with(|current| current.borrow().clone())
+}
+
+
+/// A guard restoring the previously current task on drop.
+struct Enter {
+  previous: Option<Arc<str>>,
+}
+
+impl Enter {
+  fn new(name: Arc<str>) -> Self {
+    let previous = CURRENT.with(|current| current.replace(Some(name)));
+    Self { previous }
+  }
+}
+
+impl Drop for Enter {
+  fn drop(&mut self) {
+    let previous = self.previous.take();
+    CURRENT.with(|current| {
+      let _name = current.replace(previous);
+    })
+  }
+}
+
+
+/// A future marking itself as the current task while being polled.
+///
+/// Objects of this type are created by [`wrap`].
+#[derive(Debug)]
+pub struct Task<F> {
+  /// The name of the task.
+  name: Arc<str>,
+  /// The wrapped future.
+  future: F,
+}
+
+impl<F> Future for Task<F>
+where
+  F: Future,
+{
+  type Output = F::Output;
+
+  fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+    // SAFETY: We never move `future` out of `self`.
+    let this = unsafe { self.get_unchecked_mut() };
+    let _enter = Enter::new(this.name.clone());
+    let future = unsafe { Pin::new_unchecked(&mut this.future) };
+    future.poll(cx)
+  }
+}
+
+
+/// Wrap a future such that a panic while polling it results in a
+/// snapshot core being created instead of the process getting
+/// terminated.
+pub fn wrap<S, F>(name: S, future: F) -> Task<F>
+where
+  S: AsRef<str>,
+  F: Future,
+{
+  Task {
+    name: Arc::from(name.as_ref()),
+    future,
+  }
+}
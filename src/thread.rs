@@ -0,0 +1,82 @@
+// thread.rs
+
+// Copyright (C) 2026 Daniel Mueller <deso@posteo.net>
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+//! Per-thread configuration of core dumping.
+//!
+//! Some threads, for example the workers of a job runner, have their
+//! panics caught and are simply recycled afterwards. Dumping core for
+//! such panics is usually undesired. By calling [`opt_out`] on such a
+//! thread, panics on it are left to the panic hook installed prior to
+//! registration, as if no handler had been registered, while panics on
+//! other threads (e.g., the main thread) still dump core.
+//!
+//! ```rust
+//! let worker = std::thread::spawn(|| {
+//!   coredump::thread::opt_out();
+//!   // ...
+//! });
+//! # worker.join().unwrap();
+//! ```
+//!
+//! Contrary to [`suppress`][crate::suppress], the opt-out lasts for the
+//! remainder of the thread's lifetime, unless reverted by [`opt_in`].
+//!
+//! Furthermore, threads that are to get core dumped on stack overflow
+//! may need an alternate signal stack set up by [`install_altstack`].
+
+use std::cell::Cell;
+
+use crate::altstack;
+use crate::crash;
+use crate::Error;
+use crate::Str;
+
+
+thread_local! {
+  /// Whether the thread opted out of core dumping.
+  static OPTED_OUT: Cell<bool> = const { Cell::new(false) };
+}
+
+
+/// Check whether the calling thread opted out of core dumping.
+pub(crate) fn is_opted_out() -> bool {
+  OPTED_OUT.with(Cell::get)
+}
+
+
+/// Opt the calling thread out of core dumping.
+pub fn opt_out() {
+  OPTED_OUT.with(|opted_out| opted_out.set(true))
+}
+
+
+/// Opt the calling thread back into core dumping, reverting the effect
+/// of [`opt_out`].
+pub fn opt_in() {
+  OPTED_OUT.with(|opted_out| opted_out.set(false))
+}
+
+
+/// Set up an alternate signal stack for the calling thread, unless it
+/// has one already.
+///
+/// The handlers installed for crash signals (see
+/// [`Builder::crash_signals`][crate::Builder::crash_signals]) run on
+/// the alternate signal stack, which is the only way for them to run
+/// at all when the thread overflowed its stack. Registration sets up
+/// such a stack for the registering thread. Threads spawned by the
+/// standard library typically have one already, but threads created by
+/// other means (e.g., by native libraries) may not. The stack is
+/// released once the thread exits.
+///
+/// Furthermore, the bounds of the thread's regular stack are recorded,
+/// so that stack overflows can be told apart from other segmentation
+/// faults (see [`Builder::metadata`][crate::Builder::metadata]).
+/// Registration does the same for the registering thread.
+pub fn install_altstack() -> Result<(), (Str, Error)> {
+  let () = altstack::install()?;
+  let () = crash::record_stack_bounds();
+  Ok(())
+}
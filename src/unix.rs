@@ -0,0 +1,3310 @@
+// unix.rs
+
+// Copyright (C) 2019-2026 Daniel Mueller <deso@posteo.net>
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+//! The implementation for Unix systems.
+
+use std::convert::TryInto;
+use std::env::current_dir;
+use std::env::set_current_dir;
+use std::env::temp_dir;
+use std::env::var_os;
+use std::ffi::CString;
+use std::ffi::OsStr;
+use std::fmt::Arguments;
+use std::fmt::Debug;
+use std::fmt::Formatter;
+use std::fmt::Result as FmtResult;
+use std::fs::remove_dir;
+use std::fs::File;
+use std::fs::DirBuilder;
+use std::io::Error as IoError;
+use std::io::ErrorKind;
+use std::mem::zeroed;
+use std::os::raw::c_int;
+use std::os::raw::c_void;
+use std::os::unix::ffi::OsStrExt;
+use std::os::unix::fs::DirBuilderExt;
+#[cfg(target_os = "linux")]
+use std::os::unix::fs::MetadataExt;
+use std::os::unix::io::AsRawFd;
+use std::os::unix::io::FromRawFd;
+use std::os::unix::io::OwnedFd;
+use std::path::Path;
+use std::path::PathBuf;
+use std::process::abort;
+use std::process::exit;
+use std::ptr::null;
+use std::ptr::null_mut;
+use std::str::from_utf8;
+use std::sync::atomic::AtomicBool;
+use std::sync::atomic::AtomicUsize;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use std::sync::Mutex;
+use std::sync::PoisonError;
+use std::thread::sleep;
+use std::thread::yield_now;
+use std::time::Duration;
+use std::time::SystemTime;
+use std::time::UNIX_EPOCH;
+
+use libc::_exit;
+use libc::access;
+use libc::chdir;
+use libc::close;
+use libc::dladdr;
+use libc::dup2;
+use libc::fchdir;
+#[cfg(not(target_os = "linux"))]
+use libc::fork;
+use libc::fstat;
+use libc::getpid;
+use libc::getppid;
+use libc::open;
+use libc::pid_t;
+#[cfg(target_os = "linux")]
+use libc::prctl;
+use libc::pthread_sigmask;
+use libc::raise;
+use libc::sigaddset;
+use libc::sigemptyset;
+use libc::sigismember;
+use libc::sigset_t;
+use libc::stat;
+use libc::waitpid;
+use libc::write;
+use libc::Dl_info;
+use libc::ENOENT;
+use libc::O_CLOEXEC;
+use libc::O_DIRECTORY;
+use libc::O_RDONLY;
+use libc::O_RDWR;
+#[cfg(target_os = "linux")]
+use libc::PR_GET_DUMPABLE;
+#[cfg(target_os = "linux")]
+use libc::PR_SET_DUMPABLE;
+use libc::RLIMIT_NOFILE;
+use libc::SA_ONSTACK;
+use libc::SA_SIGINFO;
+use libc::SIGABRT;
+use libc::SIGBUS;
+use libc::SIGFPE;
+use libc::SIGILL;
+use libc::SIGKILL;
+use libc::SIGQUIT;
+use libc::SIGSEGV;
+use libc::SIG_BLOCK;
+use libc::SIG_UNBLOCK;
+use libc::STDERR_FILENO;
+use libc::STDIN_FILENO;
+use libc::STDOUT_FILENO;
+use libc::WCOREDUMP;
+use libc::WIFSIGNALED;
+use libc::W_OK;
+use libc::X_OK;
+
+use crate::altstack;
+#[cfg(target_os = "android")]
+use crate::android;
+#[cfg(target_os = "linux")]
+use crate::atfork;
+use crate::backend::Backend as _;
+use crate::backend::Sys;
+use crate::buffer::Buffer;
+#[cfg(target_os = "linux")]
+use crate::config::pipe_helper;
+#[cfg(target_os = "linux")]
+use crate::config::wsl_version;
+use crate::config::CoreConfig;
+use crate::config::CrashDetails;
+use crate::crash;
+#[cfg(target_os = "linux")]
+use crate::dump_filter;
+use crate::env;
+use crate::env::Overrides;
+use crate::filter;
+use crate::hook;
+use crate::markdown;
+use crate::marker;
+use crate::metadata::Metadata;
+use crate::priority::Priority;
+use crate::rate;
+use crate::resource::getrlimit;
+use crate::resource::rlim_t;
+use crate::resource::rlimit;
+use crate::resource::RLIM_INFINITY;
+use crate::sample;
+use crate::signal::SavedAction;
+use crate::suppress::is_suppressed;
+#[cfg(any(target_os = "macos", target_os = "freebsd", target_os = "netbsd"))]
+use crate::sysctl;
+use crate::task;
+use crate::thread;
+use crate::Error;
+use crate::Str;
+use crate::WithCtx;
+
+pub use crate::config::CoreLocation;
+#[cfg(target_os = "linux")]
+pub use crate::dump_filter::CoredumpFilter;
+#[cfg(all(target_os = "linux", target_env = "gnu"))]
+pub use crate::exit::dump_on_abnormal_exit;
+#[cfg(target_os = "macos")]
+pub use crate::macos::locate_crash_artifacts;
+#[cfg(target_os = "macos")]
+pub use crate::macos::CrashArtifacts;
+pub use crate::marker::check_previous_crash;
+pub use crate::marker::CrashInfo;
+#[cfg(target_os = "linux")]
+pub use crate::priority::IoPriority;
+pub use crate::report::Report;
+pub use crate::suppress::suppress;
+pub use crate::suppress::with_coredumps_disabled;
+pub use crate::suppress::Suppression;
+
+
+/// Check the return value of a call (typically into libc) that modifies
+/// the last error variable in case of error.
+pub(crate) fn check<T>(result: T, error: T) -> Result<(), Error>
+where
+  T: Copy + PartialOrd<T>,
+{
+  if result == error {
+    Err(IoError::last_os_error())?;
+  }
+  Ok(())
+}
+
+
+/// The signals commonly associated with a process crashing, i.e., the
+/// ones resulting in a core dump by default.
+const CRASH_SIGNALS: [c_int; 6] = [SIGQUIT, SIGABRT, SIGSEGV, SIGBUS, SIGILL, SIGFPE];
+
+/// The path of the null device, as a C string.
+const DEV_NULL: &[u8] = b"/dev/null\0";
+
+
+/// Check whether the given signal has a non-default disposition, i.e.,
+/// is either ignored or has a handler installed.
+fn has_foreign_handler(signal: c_int) -> Result<bool, (Str, Error)> {
+  let action = SavedAction::save(signal)?;
+  Ok(!action.is_default())
+}
+
+
+/// Check whether the given signal is blocked on the calling thread.
+fn is_blocked(signal: c_int) -> Result<bool, Error> {
+  let mut set = unsafe { zeroed::<sigset_t>() };
+  let result = unsafe { pthread_sigmask(SIG_BLOCK, null(), &mut set) };
+  if result != 0 {
+    return Err(IoError::from_raw_os_error(result).into())
+  }
+  Ok(unsafe { sigismember(&set, signal) } == 1)
+}
+
+
+/// Unblock the given signal on the calling thread.
+///
+/// A blocked signal stays pending instead of being delivered. This
+/// function is async-signal-safe.
+fn unblock(signal: c_int) -> Result<(), Error> {
+  let mut set = unsafe { zeroed::<sigset_t>() };
+  check(unsafe { sigemptyset(&mut set) }, -1)?;
+  check(unsafe { sigaddset(&mut set, signal) }, -1)?;
+  let result = unsafe { pthread_sigmask(SIG_UNBLOCK, &set, null_mut()) };
+  if result != 0 {
+    return Err(IoError::from_raw_os_error(result).into())
+  }
+  Ok(())
+}
+
+
+/// Retrieve the base address of the object containing `addr`.
+fn object_base(addr: *const c_void) -> Option<*mut c_void> {
+  let mut info = unsafe { zeroed::<Dl_info>() };
+  if unsafe { dladdr(addr, &mut info) } == 0 {
+    return None
+  }
+  Some(info.dli_fbase)
+}
+
+
+/// Check whether the action installed for SIGSEGV is the handler the
+/// Rust standard library uses for reporting stack overflows.
+///
+/// The standard library does not expose its handler, so we identify it
+/// by its traits: it is installed with `SA_SIGINFO` and `SA_ONSTACK`
+/// for SIGSEGV and SIGBUS only, and it is part of the same object as
+/// the standard library.
+fn is_std_handler(actions: &[SavedAction]) -> bool {
+  let find = |signal| actions.iter().find(|action| action.signal() == signal);
+  let (segv, bus) = match (find(SIGSEGV), find(SIGBUS)) {
+    (Some(segv), Some(bus)) => (segv, bus),
+    _ => return false,
+  };
+
+  let handler = segv.as_raw().sa_sigaction;
+  let flags = SA_SIGINFO | SA_ONSTACK;
+  if segv.is_default() || segv.is_ignored() || crash::is_handler(segv) {
+    return false
+  }
+  if segv.as_raw().sa_flags & flags != flags || bus.as_raw().sa_sigaction != handler {
+    return false
+  }
+  // Other crash reporters typically use a single handler for all crash
+  // signals.
+  let shared = actions.iter().any(|action| {
+    ![SIGSEGV, SIGBUS].contains(&action.signal()) && action.as_raw().sa_sigaction == handler
+  });
+  if shared {
+    return false
+  }
+
+  let std_base = object_base(yield_now as fn() as *const c_void);
+  std_base.is_some() && object_base(handler as *const c_void) == std_base
+}
+
+
+/// Retrieve the list of crash signals (SIGQUIT, SIGABRT, SIGSEGV,
+/// SIGBUS, SIGILL, and SIGFPE) that currently have a non-default
+/// disposition.
+///
+/// Handlers for these signals are commonly installed by other crash
+/// reporting facilities, such as sentry-native, breakpad, or sanitizer
+/// runtimes. Of particular relevance is SIGQUIT, which is the signal
+/// used for dumping core: a handler for it may prevent a core file
+/// from being created. Please refer to [`HandlerPolicy`] for ways to
+/// deal with such a handler.
+///
+/// The handler the Rust standard library installs for SIGSEGV and
+/// SIGBUS in order to report stack overflows is not considered
+/// foreign: it restores the default disposition for faults it does not
+/// attribute to a stack overflow and aborts the process otherwise, so
+/// a core file is created either way. It is identified heuristically;
+/// where that fails (e.g., because the standard library is linked
+/// statically on a system not supporting `dladdr(3)` for it), the two
+/// signals are still reported.
+pub fn foreign_crash_handlers() -> Result<Vec<c_int>, (Str, Error)> {
+  let actions = CRASH_SIGNALS
+    .iter()
+    .map(|signal| SavedAction::save(*signal))
+    .collect::<Result<Vec<_>, _>>()?;
+  let std_handler = is_std_handler(&actions);
+
+  let signals = actions
+    .iter()
+    .filter(|action| !action.is_default())
+    .filter(|action| !(std_handler && [SIGSEGV, SIGBUS].contains(&action.signal())))
+    .map(SavedAction::signal)
+    .collect();
+  Ok(signals)
+}
+
+
+/// Force a core dump of the process by raising `signal`.
+///
+/// The signal is directed at the calling thread, so that it is the one
+/// reported as having received the signal in the core file. A signal
+/// sent to the process could be delivered to an arbitrary thread.
+/// Should raising the signal fail, `fallbacks` are tried in order.
+fn dump_core(signal: c_int, fallbacks: &[Fallback]) -> Result<(), (Str, Error)> {
+  if unsafe { raise(signal) } == 0 {
+    return Ok(())
+  }
+
+  let err = IoError::last_os_error();
+  for fallback in fallbacks {
+    match fallback {
+      Fallback::Kill => {
+        if Sys::kill(unsafe { getpid() }, signal).is_ok() {
+          return Ok(())
+        }
+      },
+      Fallback::Abort => {
+        // We may be running in a signal handler, so do not allocate.
+        let mut message = Buffer::<128>::new();
+        let _message = message
+          .push(b"coredump: failed to raise signal ")
+          .push_dec(signal as u64)
+          .push(b" (error ")
+          .push_dec(err.raw_os_error().unwrap_or(0) as u64)
+          .push(b"); aborting\n");
+        let bytes = message.as_bytes();
+        let _ = unsafe { write(STDERR_FILENO, bytes.as_ptr().cast(), bytes.len()) };
+        abort()
+      },
+    }
+  }
+  Err(Error::from(err)).ctx(|| "failed to raise dump signal")
+}
+
+
+/// Fork the process without running any fork handlers.
+///
+/// Fork handlers (including the one verifying the dump configuration)
+/// are meant for children carrying on with the program, which is not
+/// what helper processes do.
+#[cfg(target_os = "linux")]
+fn fork_raw() -> pid_t {
+  use libc::syscall;
+
+  #[cfg(any(
+    target_arch = "aarch64",
+    target_arch = "loongarch64",
+    target_arch = "riscv64"
+  ))]
+  let result = unsafe {
+    // These architectures lack a dedicated fork system call.
+    syscall(libc::SYS_clone, libc::SIGCHLD, 0, 0, 0, 0)
+  };
+  #[cfg(not(any(
+    target_arch = "aarch64",
+    target_arch = "loongarch64",
+    target_arch = "riscv64"
+  )))]
+  let result = unsafe { syscall(libc::SYS_fork) };
+  result as pid_t
+}
+
+/// Fork the process.
+///
+/// Fork handlers are only of concern to us on Linux, where we may
+/// install our own.
+#[cfg(not(target_os = "linux"))]
+fn fork_raw() -> pid_t {
+  unsafe { fork() }
+}
+
+
+/// Close all file descriptors from `first` onwards.
+///
+/// This function is async-signal-safe.
+fn close_from(first: c_int) {
+  #[cfg(target_os = "linux")]
+  {
+    use std::os::raw::c_uint;
+
+    let result = unsafe { libc::syscall(libc::SYS_close_range, first, c_uint::MAX, 0) };
+    if result == 0 {
+      return
+    }
+  }
+
+  let mut limit = rlimit {
+    rlim_cur: 0,
+    rlim_max: 0,
+  };
+  let max = if unsafe { getrlimit(RLIMIT_NOFILE, &mut limit) } == 0
+    && limit.rlim_cur != RLIM_INFINITY
+  {
+    limit.rlim_cur.min(c_int::MAX as rlim_t) as c_int
+  } else {
+    1 << 20
+  };
+  for fd in first..max {
+    let _ = unsafe { close(fd) };
+  }
+}
+
+
+/// Detach the calling process from the file descriptors it inherited,
+/// pointing the standard ones at `/dev/null`.
+///
+/// Helper processes would otherwise keep, say, pipes a supervisor reads
+/// from open, delaying it from noticing the termination of the process
+/// it supervises. This function is async-signal-safe.
+fn detach_fds() {
+  let null = unsafe { open(DEV_NULL.as_ptr().cast(), O_RDWR | O_CLOEXEC) };
+  for fd in [STDIN_FILENO, STDOUT_FILENO, STDERR_FILENO] {
+    if null >= 0 {
+      let _ = unsafe { dup2(null, fd) };
+    } else {
+      let _ = unsafe { close(fd) };
+    }
+  }
+  // This closes `null` as well.
+  let () = close_from(STDERR_FILENO + 1);
+}
+
+
+/// Arm a watchdog that kills the process with SIGKILL should it still
+/// be alive after `timeout` has passed.
+///
+/// The watchdog is implemented as a helper process, because threads
+/// would be taken down together with a process that is dumping core
+/// (and so could not possibly help if core file writing hangs). Aside
+/// from error reporting, this function is async-signal-safe.
+pub(crate) fn arm_watchdog(timeout: Duration) -> Result<(), (Str, Error)> {
+  let parent = unsafe { getpid() };
+  let child = fork_raw();
+  check(child, -1).ctx(|| "failed to fork watchdog process")?;
+
+  if child == 0 {
+    // We are the watchdog. Note that we are a forked copy of a
+    // potentially multi-threaded process, so we should stick to as
+    // little functionality as possible here.
+    let () = detach_fds();
+    sleep(timeout);
+    // If our parent is gone already we got reparented and must not
+    // touch whatever process is our parent now.
+    if unsafe { getppid() } == parent {
+      let _ = Sys::kill(parent, SIGKILL);
+    }
+    unsafe { _exit(0) }
+  }
+  Ok(())
+}
+
+
+/// Change the working directory to `dir`, through `dir_fd` if provided.
+///
+/// In the latter case, this function is async-signal-safe.
+fn change_dir(dir: &Path, dir_fd: Option<&OwnedFd>) -> Result<(), Error> {
+  if let Some(dir_fd) = dir_fd {
+    // A directory removed after we opened it can still be changed
+    // into, but no core file could be created in it.
+    let mut buf = unsafe { zeroed::<stat>() };
+    check(unsafe { fstat(dir_fd.as_raw_fd(), &mut buf) }, -1)?;
+    if buf.st_nlink == 0 {
+      return Err(IoError::from_raw_os_error(ENOENT).into())
+    }
+    check(unsafe { fchdir(dir_fd.as_raw_fd()) }, -1)
+  } else {
+    set_current_dir(dir).map_err(Error::from)
+  }
+}
+
+
+/// Create a core dump of the process in the given directory by killing
+/// it.
+///
+/// If `dir_fd` is provided, it refers to `dir` and the working
+/// directory is changed through it, so that neither memory has to be
+/// allocated nor a path to be resolved. Aside from the invocation of
+/// `notify` right before the signal is sent and from error reporting,
+/// only async-signal-safe functionality is used.
+#[allow(clippy::too_many_arguments)]
+pub(crate) fn dump_core_and_quit<F>(
+  dir: &Path,
+  dir_fd: Option<&OwnedFd>,
+  watchdog: Option<Duration>,
+  policy: HandlerPolicy,
+  priority: Priority,
+  signal: c_int,
+  fallbacks: &[Fallback],
+  notify: F,
+) -> Result<(), (Str, Error)>
+where
+  F: FnOnce(),
+{
+  // An ignored signal (e.g., as inherited from the parent process) is
+  // silently discarded by the kernel. Ignoring is no handler to defer
+  // to, so we restore the default disposition regardless of policy.
+  let action = SavedAction::save(signal)?;
+  if action.is_ignored() {
+    let _previous = SavedAction::reset(signal)?;
+  }
+
+  match policy {
+    HandlerPolicy::TheirsFirst => (),
+    HandlerPolicy::OursFirst => {
+      let _previous = SavedAction::reset(signal)?;
+    },
+    HandlerPolicy::Skip => {
+      if has_foreign_handler(signal)? {
+        return Ok(())
+      }
+    },
+  }
+
+  // We try to change the working directory to the system's temp dir to
+  // have the core dump generated there. Note that this is a best-effort
+  // action. It is even possible that that core file pattern (on a Linux
+  // system) contains an absolute path in which case the working
+  // directory likely doesn't matter at all.
+  let cur_dir = unsafe { open(b".\0".as_ptr().cast(), O_RDONLY | O_DIRECTORY | O_CLOEXEC) };
+  check(cur_dir, -1).ctx(|| "failed to open current directory")?;
+  // SAFETY: The file descriptor was just opened and is owned by no one
+  //         else.
+  let cur_dir = unsafe { OwnedFd::from_raw_fd(cur_dir) };
+
+  // Contexts on this path are static, as we are in a crash context and
+  // would rather not allocate.
+  change_dir(dir, dir_fd).ctx(|| "failed to change working directory")?;
+
+  if let Some(timeout) = watchdog {
+    // Failure to arm the watchdog should not prevent us from dumping
+    // core.
+    if let Err((ctx, err)) = arm_watchdog(timeout) {
+      report_error("failed to arm watchdog: ", &ctx, &err);
+    }
+  }
+
+  let () = priority.apply();
+  // A blocked signal would merely be marked pending.
+  let () = unblock(signal).ctx(|| "failed to unblock dump signal")?;
+  let () = notify();
+  if let Err(err) = dump_core(signal, fallbacks) {
+    // Opportunistically restore the working directory. We probably
+    // won't continue to run because the panic will propagate up, but
+    // let's plan for all cases.
+    // We can't do much about an error at this point. So just ignore
+    // it...
+    let _ = unsafe { fchdir(cur_dir.as_raw_fd()) };
+    Err(err)
+  } else {
+    Ok(())
+  }
+}
+
+
+/// Create a core dump of the process in the given directory without
+/// terminating it.
+///
+/// The dump is created by a forked copy of the process, meaning that it
+/// contains the full memory contents of the process but only the
+/// calling thread. On success, the ID of said copy is returned.
+pub(crate) fn dump_snapshot(dir: &Path, priority: Priority, signal: c_int) -> Result<pid_t, (Str, Error)> {
+  // We must not allocate in the forked child, so prepare everything we
+  // need up front.
+  let path = CString::new(dir.as_os_str().as_bytes())
+    .map_err(|err| Error::from(IoError::new(ErrorKind::InvalidInput, err)))
+    .ctx(|| "dump directory is not a valid path")?;
+
+  let child = fork_raw();
+  check(child, -1).ctx(|| "failed to fork snapshot process")?;
+
+  if child == 0 {
+    // We are a forked copy of a potentially multi-threaded process, so
+    // we have to restrict ourselves to async-signal-safe functionality.
+    let _ = unsafe { chdir(path.as_ptr()) };
+    let () = priority.apply();
+    let _ = SavedAction::reset(signal);
+    let _ = unblock(signal);
+    let _ = Sys::kill(unsafe { getpid() }, signal);
+    // We should not get here, but if we do we are unable to dump core.
+    unsafe { _exit(1) }
+  }
+
+  let mut status = 0;
+  loop {
+    let result = unsafe { waitpid(child, &mut status, 0) };
+    match check(result, -1) {
+      Ok(()) => break,
+      Err(Error::Io(ref err)) if err.kind() == ErrorKind::Interrupted => continue,
+      Err(err) => return Err(err).ctx(|| "failed to wait for snapshot process"),
+    }
+  }
+
+  if !WIFSIGNALED(status) || !WCOREDUMP(status) {
+    Err(IoError::new(ErrorKind::Other, "no core file was written").into())
+      .ctx(|| "failed to dump snapshot core")?;
+  }
+  Ok(child)
+}
+
+
+/// Create a snapshot core in the dump directory, reporting any
+/// errors to the error sink (see [`Builder::error_sink`]).
+///
+/// This function is an implementation detail of the assertion macros.
+#[doc(hidden)]
+pub fn __dump_snapshot() {
+  if cfg!(feature = "disabled") {
+    return
+  }
+
+  let result = raise_configured_limit().and_then(|limit| {
+    let result = dump_snapshot(&dump_dir(), dump_priority(), dump_signal());
+    // As for `trigger_core_dump`, the process lives on and so should
+    // not be left with the raised limit.
+    if let Some(limit) = limit {
+      let () = restore_core_limit(&limit)?;
+    }
+    result
+  });
+  if let Err((ctx, err)) = result {
+    report_error("failed to dump core: ", &ctx, &err);
+  }
+}
+
+
+/// Deliberately dump core, without a panic being involved.
+///
+/// This function is meant for fatal error paths that do not panic but
+/// for which a core file is still desired. The configuration of the
+/// most recent registration (dump directory, signal, priorities, core
+/// file size cap) is honored, if any; absent a registration, defaults
+/// are used. The soft core file size limit is raised as necessary.
+///
+/// If `terminate` is `true`, the process is killed by the dump signal
+/// and this function only returns if that failed; reaching the end
+/// without the process having been terminated (e.g., because the
+/// signal is blocked or handled) is reported as an error. Otherwise, a
+/// snapshot core is created by a forked copy of the process (see
+/// [`Builder::libfuzzer`] for the implications), the core file size
+/// limit is restored, and the function returns.
+///
+/// On success, the path at which the core file is expected to be
+/// created is returned, if it can be predicted (see
+/// [`Registration::core_path`]).
+///
+/// If the `disabled` feature is active, nothing is done and `None` is
+/// returned.
+pub fn trigger_core_dump(terminate: bool) -> Result<Option<PathBuf>, (Str, Error)> {
+  if cfg!(feature = "disabled") {
+    return Ok(None)
+  }
+
+  let dir = dump_dir();
+  let dir_fd = dump_dir_fd();
+  let signal = dump_signal();
+  let fallbacks = dump_fallbacks();
+  let priority = dump_priority();
+  let limit = raise_configured_limit()?;
+  let restore_limit = || match &limit {
+    Some(limit) => restore_core_limit(limit),
+    None => Ok(()),
+  };
+
+  if terminate {
+    let cur_dir = current_dir()
+      .map_err(Error::from)
+      .ctx(|| "failed to retrieve current directory")?;
+    let () = dump_core_and_quit(
+      &dir,
+      dir_fd.as_deref(),
+      None,
+      HandlerPolicy::default(),
+      priority,
+      signal,
+      &fallbacks,
+      || (),
+    )?;
+    // We only get here if the signal did not terminate us.
+    let _ = set_current_dir(cur_dir);
+    let _ = restore_limit();
+    Err(IoError::new(ErrorKind::Other, "process survived dump signal").into())
+      .ctx(|| format!("failed to dump core using signal {}", signal))
+  } else {
+    let result = dump_snapshot(&dir, priority, signal);
+    let () = restore_limit()?;
+    let child = result?;
+    // As we are not in a crash context we can inquire the system's
+    // configuration afresh. The core belongs to the forked copy, so
+    // its ID is the relevant one.
+    let child = child.try_into().map_err(Error::from).ctx(|| {
+      format!(
+        "unable to predict core path: PID {} is not a valid unsigned value",
+        child
+      )
+    })?;
+    Ok(CoreConfig::read_for(&dir, signal, child).core_path)
+  }
+}
+
+
+/// Determine where the core file ends up should the calling thread
+/// crash at `time`.
+///
+/// The kernel's core file name pattern is read afresh (see
+/// [`Registration::core_path`] for the sources consulted) and its
+/// specifiers are expanded for the calling process, based on the dump
+/// directory and signal of the most recent registration or, absent
+/// one, on the defaults. On Linux, that includes `%p`, `%u`, `%g`,
+/// `%s`, and `%h`, as well as `%e`, which is expanded to the name of
+/// the calling thread, making this function best invoked from the
+/// thread about to crash (e.g., from a panic hook). `%t` is expanded
+/// to `time`, if provided.
+///
+/// If core files are piped to a helper program, the helper's command
+/// line is reported instead. `None` is returned if the location cannot
+/// be determined, e.g., because the pattern contains a specifier whose
+/// value is not known.
+pub fn core_location(time: Option<SystemTime>) -> Option<CoreLocation> {
+  let dir = dump_dir();
+  let signal = dump_signal();
+  let time = time
+    .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+    .map(|time| time.as_secs());
+  let crash = CrashDetails::current_thread(time);
+  CoreConfig::read(&dir, signal).locate(&dir, signal, &crash)
+}
+
+
+/// Report an invariant violation on stderr and create a snapshot core,
+/// unless one was created already for the call site in question.
+///
+/// This function is an implementation detail of the `ensure_core!`
+/// macro.
+#[doc(hidden)]
+pub fn __invariant_violated(
+  file: &str,
+  line: u32,
+  column: u32,
+  msg: Arguments<'_>,
+  dumped: &AtomicBool,
+) {
+  eprintln!(
+    "invariant violated at {}:{}:{}: {}",
+    file, line, column, msg
+  );
+
+  if !dumped.swap(true, Ordering::Relaxed) {
+    __dump_snapshot()
+  }
+}
+
+
+/// Retrieve the current core file size limit.
+pub(crate) fn query_core_limit() -> Result<rlimit, (Str, Error)> {
+  Sys::core_limit().ctx(|| "failed to retrieve core file size limit")
+}
+
+
+/// Retrieve the current core file size limit, making sure that it
+/// permits the creation of core files in principle.
+fn core_limit() -> Result<rlimit, (Str, Error)> {
+  let limit = query_core_limit()?;
+
+  // There is no way for us to know what a sufficiently large core file
+  // size would be, but we know for sure that 0 ain't it (as it
+  // effectively means we can't create a core file at all).
+  if limit.rlim_max == 0 {
+    Err(IoError::new(ErrorKind::Other, "hard limit is zero").into())
+      .ctx(|| "failed to adjust core file size limit")?;
+  }
+  Ok(limit)
+}
+
+
+/// Enable core dumps to file by ensuring that the respective rlimit is
+/// set correctly.
+/// Note that we do not touch the name under which a core file is
+/// created. At least on Linux that is a global property and we do not
+/// want to change it for that reason.
+///
+/// The soft limit is capped at `max_size` bytes, if provided.
+///
+/// On success, the previously active limit is returned.
+fn raise_core_limit(max_size: Option<u64>) -> Result<rlimit, (Str, Error)> {
+  let previous = core_limit()?;
+  let mut limit = previous;
+
+  // As an application we are only allowed to touch the soft limit
+  // (`rlim_cur`), while the hard limit acts as a ceiling. We bump it
+  // as high as we can.
+  limit.rlim_cur = limit.rlim_max;
+  if let Some(max_size) = max_size {
+    // A cap not representable as `rlim_t` is no cap at all. Note that
+    // `rlim_t` is not the same type on all platforms.
+    #[allow(clippy::useless_conversion)]
+    let max_size: rlim_t = max_size.try_into().unwrap_or(RLIM_INFINITY);
+    limit.rlim_cur = limit.rlim_cur.min(max_size);
+  }
+
+  Sys::set_core_limit(&limit).ctx(|| "failed to adjust core file size limit")?;
+
+  Ok(previous)
+}
+
+
+/// Raise the core file size limit as configured by the most recent
+/// registration, i.e., not at all if [`Builder::manage_rlimit`] was
+/// disabled.
+///
+/// On success, the previously active limit is returned, if the limit
+/// was adjusted.
+pub(crate) fn raise_configured_limit() -> Result<Option<rlimit>, (Str, Error)> {
+  let (manage_rlimit, max_size) = SETTINGS
+    .lock()
+    .unwrap_or_else(PoisonError::into_inner)
+    .as_ref()
+    .map(|settings| (settings.manage_rlimit, settings.max_size))
+    .unwrap_or((true, None));
+
+  if manage_rlimit {
+    raise_core_limit(max_size).map(Some)
+  } else {
+    Ok(None)
+  }
+}
+
+
+/// Lower the soft core file size limit to zero, preventing the creation
+/// of core files.
+fn lower_core_limit() -> Result<(), (Str, Error)> {
+  let mut limit = query_core_limit()?;
+  limit.rlim_cur = 0;
+
+  Sys::set_core_limit(&limit).ctx(|| "failed to disable core dumps")
+}
+
+
+/// Restore a previously retrieved core file size limit.
+fn restore_core_limit(limit: &rlimit) -> Result<(), (Str, Error)> {
+  Sys::set_core_limit(limit)
+    .ctx(|| "failed to restore core file size limit")
+}
+
+
+/// Enable core dumps by raising the soft core file size limit
+/// (`RLIMIT_CORE`).
+///
+/// The soft limit is set to `limit` bytes or, if `None`, to the hard
+/// limit. Values exceeding the hard limit are clamped to it. Note that
+/// the kernel truncates core files exceeding the soft limit. This
+/// function fails if the hard limit is zero, as no core file can be
+/// created in that case. No panic handler is registered.
+///
+/// On success, the limit in effect before the adjustment is returned.
+///
+/// If the `disabled` feature is active, the limit is left untouched.
+pub fn enable_core_dumps(limit: Option<u64>) -> Result<CoreLimit, (Str, Error)> {
+  let previous = if cfg!(feature = "disabled") {
+    query_core_limit()?
+  } else {
+    raise_core_limit(limit)?
+  };
+  Ok(CoreLimit::from_raw(&previous))
+}
+
+
+/// Disable core dumps by lowering the soft core file size limit
+/// (`RLIMIT_CORE`) to zero.
+///
+/// This can be used to harden processes handling sensitive data. The
+/// hard limit is left untouched, meaning that the soft limit can be
+/// raised again later on (e.g., by [`enable_core_dumps`]). Contrary to
+/// most other functionality, this function takes effect even if the
+/// `disabled` feature is active.
+///
+/// On success, the limit in effect before the adjustment is returned.
+pub fn disable_core_dumps() -> Result<CoreLimit, (Str, Error)> {
+  let previous = query_core_limit()?;
+  let () = lower_core_limit()?;
+  Ok(CoreLimit::from_raw(&previous))
+}
+
+
+/// Retrieve the types of memory mappings included in core files of the
+/// calling process (see `/proc/self/coredump_filter` in `core(5)`).
+#[cfg(target_os = "linux")]
+pub fn coredump_filter() -> Result<CoredumpFilter, (Str, Error)> {
+  dump_filter::read()
+}
+
+
+/// Set the types of memory mappings included in core files of the
+/// calling process (see `/proc/self/coredump_filter` in `core(5)`).
+///
+/// The filter is inherited by child processes. No panic handler is
+/// registered. On success, the filter in effect before the adjustment
+/// is returned.
+///
+/// If the `disabled` feature is active, the filter is left untouched.
+#[cfg(target_os = "linux")]
+pub fn set_coredump_filter(filter: CoredumpFilter) -> Result<CoredumpFilter, (Str, Error)> {
+  let previous = dump_filter::read()?;
+  if !cfg!(feature = "disabled") {
+    let () = dump_filter::apply(filter)?;
+  }
+  Ok(previous)
+}
+
+
+/// Check that the given directory is usable for dumping core into.
+fn validate_dir(dir: &Path) -> Result<(), (Str, Error)> {
+  let metadata = dir
+    .metadata()
+    .map_err(Error::from)
+    .ctx(|| format!("failed to access dump directory {}", dir.display()))?;
+
+  if !metadata.is_dir() {
+    Err(IoError::new(ErrorKind::Other, "not a directory").into())
+      .ctx(|| format!("dump directory {} is invalid", dir.display()))?;
+  }
+  Ok(())
+}
+
+
+/// Check that the given directory is usable for dumping core into and
+/// that we can write to it.
+pub(crate) fn validate_writable_dir(dir: &Path) -> Result<(), (Str, Error)> {
+  let () = validate_dir(dir)?;
+
+  let path = CString::new(dir.as_os_str().as_bytes())
+    .map_err(|err| Error::from(IoError::new(ErrorKind::InvalidInput, err)))
+    .ctx(|| format!("dump directory {} is not a valid path", dir.display()))?;
+  let result = unsafe { access(path.as_ptr(), W_OK | X_OK) };
+  check(result, -1).ctx(|| format!("dump directory {} is not writable", dir.display()))
+}
+
+
+/// Check whether the process appears to be confined to a `chroot(2)`
+/// environment.
+///
+/// `None` is returned if that cannot be determined, for example,
+/// because `/proc` is not mounted.
+#[cfg(target_os = "linux")]
+pub(crate) fn is_chrooted() -> Option<bool> {
+  let root = Path::new("/").metadata().ok()?;
+  let init_root = Path::new("/proc/1/root/").metadata().ok()?;
+  Some((root.dev(), root.ino()) != (init_root.dev(), init_root.ino()))
+}
+
+#[cfg(not(target_os = "linux"))]
+pub(crate) fn is_chrooted() -> Option<bool> {
+  None
+}
+
+
+/// Determine the directory to dump core files into.
+///
+/// If candidate directories were configured, the first one that is
+/// usable and writable is picked, creating it first if `mode` is set.
+/// Candidates are made absolute. Otherwise, if `TMPDIR` is set, its
+/// value is used as-is and has to refer to a valid directory. Absent
+/// that, we try a set of well-known candidates, falling back to the
+/// current working directory. The latter caters to chroots and minimal
+/// container root file systems, which may lack `/tmp`. Fallback paths
+/// are absolute, so that they stay valid across changes of the working
+/// directory.
+///
+/// Directories created along the way are recorded in `created`.
+pub(crate) fn resolve_dir(
+  candidates: &[PathBuf],
+  mode: Option<u32>,
+  created: &mut Vec<PathBuf>,
+) -> Result<PathBuf, (Str, Error)> {
+  if !candidates.is_empty() {
+    let cwd = current_dir()
+      .map_err(Error::from)
+      .ctx(|| "failed to retrieve current working directory")?;
+
+    let mut result = Ok(PathBuf::new());
+    for dir in candidates {
+      let dir = cwd.join(dir);
+      let mut dirs = Vec::new();
+      result = mode
+        .map_or(Ok(()), |mode| create_dir(&dir, mode, &mut dirs))
+        .and_then(|()| validate_writable_dir(&dir))
+        .map(|()| dir);
+
+      if result.is_ok() {
+        created.extend(dirs);
+        break
+      }
+      // Don't leave behind directories for candidates we rejected.
+      for dir in dirs.iter().rev() {
+        let _ = remove_dir(dir);
+      }
+    }
+    return result
+  }
+
+  if var_os("TMPDIR").is_some() {
+    let dir = temp_dir();
+    let () = validate_dir(&dir)?;
+    return Ok(dir)
+  }
+
+  let mut candidates = vec![PathBuf::from("/tmp"), PathBuf::from("/var/tmp")];
+  if let Ok(cwd) = current_dir() {
+    candidates.push(cwd);
+  }
+
+  let dir = candidates
+    .into_iter()
+    .find(|dir| validate_writable_dir(dir).is_ok())
+    .ok_or_else(|| {
+      Error::from(IoError::new(
+        ErrorKind::NotFound,
+        "no usable directory found",
+      ))
+    })
+    .ctx(|| {
+      if is_chrooted() == Some(true) {
+        "failed to find dump directory inside chroot"
+      } else {
+        "failed to find dump directory"
+      }
+    })?;
+  Ok(dir)
+}
+
+
+/// Create the given directory, including missing parents, using the
+/// provided permission bits (subject to the process' umask).
+///
+/// Created directories are recorded in `created`, outermost first.
+fn create_dir(dir: &Path, mode: u32, created: &mut Vec<PathBuf>) -> Result<(), (Str, Error)> {
+  let missing = dir
+    .ancestors()
+    .take_while(|dir| !dir.as_os_str().is_empty() && !dir.exists())
+    .collect::<Vec<_>>();
+
+  for dir in missing.into_iter().rev() {
+    let () = DirBuilder::new()
+      .mode(mode)
+      .create(dir)
+      .map_err(Error::from)
+      .ctx(|| format!("failed to create dump directory {}", dir.display()))?;
+    created.push(dir.to_path_buf());
+  }
+  Ok(())
+}
+
+
+/// Retrieve the directory to dump core files into.
+///
+/// That is the directory determined at registration time or, absent a
+/// registration, the system's temp directory.
+pub(crate) fn dump_dir() -> PathBuf {
+  SETTINGS
+    .lock()
+    .unwrap_or_else(PoisonError::into_inner)
+    .as_ref()
+    .map(|settings| settings.dir.clone())
+    .unwrap_or_else(temp_dir)
+}
+
+
+/// Retrieve the directory to dump core files into, as opened at
+/// registration time.
+///
+/// Absent a registration, `None` is returned.
+pub(crate) fn dump_dir_fd() -> Option<Arc<OwnedFd>> {
+  SETTINGS
+    .lock()
+    .unwrap_or_else(PoisonError::into_inner)
+    .as_ref()
+    .map(|settings| settings.dir_fd.clone())
+}
+
+
+/// Open the given directory, for changing into it at crash time.
+fn open_dir(dir: &Path) -> Result<Arc<OwnedFd>, (Str, Error)> {
+  let file = File::open(dir)
+    .map_err(Error::from)
+    .ctx(|| format!("failed to open directory {}", dir.display()))?;
+  Ok(Arc::new(OwnedFd::from(file)))
+}
+
+
+/// Retrieve the signal to dump core with.
+///
+/// This is the one configured at registration time or, absent a
+/// registration, the default one.
+pub(crate) fn dump_signal() -> c_int {
+  SETTINGS
+    .lock()
+    .unwrap_or_else(PoisonError::into_inner)
+    .as_ref()
+    .map(|settings| settings.signal)
+    .unwrap_or(DEFAULT_SIGNAL)
+}
+
+
+/// Retrieve the fallbacks to try should raising the dump signal fail.
+///
+/// These are the ones configured at registration time or, absent a
+/// registration, the defaults.
+pub(crate) fn dump_fallbacks() -> Vec<Fallback> {
+  SETTINGS
+    .lock()
+    .unwrap_or_else(PoisonError::into_inner)
+    .as_ref()
+    .map(|settings| settings.fallbacks.clone())
+    .unwrap_or_else(|| DEFAULT_FALLBACKS.to_vec())
+}
+
+
+/// Retrieve the priorities to apply while dumping core.
+///
+/// These are the ones configured at registration time or, absent a
+/// registration, the defaults (i.e., priorities are left untouched).
+pub(crate) fn dump_priority() -> Priority {
+  SETTINGS
+    .lock()
+    .unwrap_or_else(PoisonError::into_inner)
+    .as_ref()
+    .map(|settings| settings.priority)
+    .unwrap_or_default()
+}
+
+
+/// A helper for making registration transactional.
+///
+/// The object tracks changes to global state that were applied as part
+/// of the registration and rolls them back when dropped. Once all steps
+/// succeeded, the transaction is to be committed.
+#[derive(Default)]
+struct Transaction {
+  /// The core file size limit in effect before we adjusted it.
+  limit: Option<rlimit>,
+  /// The directories we created, outermost first.
+  dirs: Vec<PathBuf>,
+  /// The changes to the crash signal handlers.
+  crash: Option<crash::Undo>,
+  /// The changes to the state of the fork handler.
+  #[cfg(target_os = "linux")]
+  fork: Option<atfork::Undo>,
+  /// The core dump filter in effect before we adjusted it.
+  #[cfg(target_os = "linux")]
+  coredump_filter: Option<CoredumpFilter>,
+}
+
+impl Transaction {
+  /// Commit the transaction, i.e., make all changes permanent.
+  fn commit(mut self) {
+    self.limit = None;
+    self.dirs.clear();
+    self.crash = None;
+    #[cfg(target_os = "linux")]
+    {
+      self.fork = None;
+      self.coredump_filter = None;
+    }
+  }
+}
+
+impl Drop for Transaction {
+  fn drop(&mut self) {
+    // We are already on an error path and would rather report the
+    // original error than one in rolling back, so ignore any errors.
+    #[cfg(target_os = "linux")]
+    {
+      if let Some(filter) = self.coredump_filter.take() {
+        let _ = dump_filter::apply(filter);
+      }
+      if let Some(fork) = self.fork.take() {
+        let () = fork.undo();
+      }
+    }
+    if let Some(crash) = self.crash.take() {
+      let () = crash.undo();
+    }
+    if let Some(limit) = self.limit.take() {
+      let _ = restore_core_limit(&limit);
+    }
+    // Directories are only removed if they are still empty.
+    for dir in self.dirs.iter().rev() {
+      let _ = remove_dir(dir);
+    }
+  }
+}
+
+
+/// A policy describing how to deal with a pre-existing handler for the
+/// signal used for dumping core (SIGQUIT, by default).
+///
+/// The handler is checked at the time of the panic, not at the time of
+/// registration. An ignored signal does not count as being handled:
+/// its default disposition is restored before dumping core, regardless
+/// of policy. The policy is also used for handlers of crash signals
+/// (see [`Builder::crash_handler_policy`]), with slightly different
+/// semantics.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum HandlerPolicy {
+  /// Raise the signal as usual, letting any pre-existing handler run.
+  ///
+  /// Whether a core file is created then depends on said handler. Many
+  /// crash reporters, for example, restore the default disposition and
+  /// re-raise the signal after they are done with their own business.
+  #[default]
+  TheirsFirst,
+  /// Restore the default disposition of the signal before raising it,
+  /// so that the system's core dumping logic takes effect.
+  ///
+  /// A pre-existing handler will not get to run, because the process
+  /// is terminated.
+  OursFirst,
+  /// Do not attempt to dump core if a handler is present, stepping aside
+  /// in favor of it.
+  Skip,
+}
+
+/// The order in which core is dumped relative to the invocation of the
+/// panic hook that was installed before registration.
+///
+/// That hook may be the default one, printing the panic message and
+/// backtrace, or one installed by crates such as `color-eyre` or
+/// `human-panic`.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum HookOrder {
+  /// Invoke the existing hook and dump core afterwards.
+  ///
+  /// This ordering preserves the existing hook's output.
+  #[default]
+  AfterExisting,
+  /// Dump core first and invoke the existing hook afterwards.
+  ///
+  /// The core then captures the process' state before the existing
+  /// hook got to run, which helps if said hook is slow, hangs, or
+  /// modifies relevant state. However, as dumping core terminates the
+  /// process, the existing hook only runs if dumping failed (or the
+  /// process survived it).
+  BeforeExisting,
+}
+
+/// The action to take for a crash signal.
+///
+/// Please refer to [`Builder::crash_signal`] for details.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CrashAction {
+  /// Dump core into the dump directory, after chaining to pre-existing
+  /// handlers as configured by [`Builder::crash_handler_policy`].
+  Dump,
+  /// Terminate the process without dumping core, by lowering the core
+  /// file size limit to zero before the default action takes place.
+  /// Pre-existing handlers are chained to as for [`CrashAction::Dump`].
+  NoDump,
+  /// Leave the signal's disposition alone, so that only pre-existing
+  /// handlers (if any) act on it.
+  ChainOnly,
+}
+
+/// A way of dumping core that is tried if raising the dump signal
+/// failed.
+///
+/// Sandboxes, e.g., in the form of seccomp filters, may deny sending
+/// signals, in which case the process would survive the dump attempt
+/// without a core file having been created.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Fallback {
+  /// Send the dump signal to the process as a whole (using `kill(2)`),
+  /// as opposed to only the panicking thread.
+  Kill,
+  /// Abort the process, which raises SIGABRT. SIGABRT terminates the
+  /// process with a core dump by default, but the core file size limit
+  /// and the working directory are the only settings still applying.
+  /// This fallback never returns.
+  Abort,
+}
+
+/// The signal to dump core with unless configured otherwise.
+///
+/// On Android, debuggerd writes tombstones for SIGABRT, but does not
+/// handle SIGQUIT.
+#[cfg(target_os = "android")]
+const DEFAULT_SIGNAL: c_int = SIGABRT;
+#[cfg(not(target_os = "android"))]
+const DEFAULT_SIGNAL: c_int = SIGQUIT;
+
+/// The fallbacks used unless configured otherwise.
+const DEFAULT_FALLBACKS: [Fallback; 2] = [Fallback::Kill, Fallback::Abort];
+
+/// Convert a raw limit value, mapping `RLIM_INFINITY` to `None`.
+// `rlim_t` is not the same type on all platforms: it is signed on
+// FreeBSD, for example, where negative values are invalid and treated
+// as no limit by the kernel.
+#[allow(clippy::useless_conversion)]
+fn convert_limit(value: rlim_t) -> Option<u64> {
+  if value == RLIM_INFINITY {
+    None
+  } else {
+    value.try_into().ok()
+  }
+}
+
+
+/// A core file size limit (`RLIMIT_CORE`).
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct CoreLimit {
+  /// The soft limit, in bytes, with `None` representing no limit.
+  pub soft: Option<u64>,
+  /// The hard limit, in bytes, with `None` representing no limit.
+  pub hard: Option<u64>,
+}
+
+impl CoreLimit {
+  /// Create a `CoreLimit` from its raw representation.
+  pub(crate) fn from_raw(limit: &rlimit) -> Self {
+    Self {
+      soft: convert_limit(limit.rlim_cur),
+      hard: convert_limit(limit.rlim_max),
+    }
+  }
+}
+
+
+/// Information about a successful panic handler registration.
+#[derive(Clone, Debug)]
+pub struct Registration {
+  /// See [`Registration::foreign_handlers`].
+  foreign_handlers: Vec<c_int>,
+  /// See [`Registration::previous_limit`].
+  previous_limit: CoreLimit,
+  /// See [`Registration::dump_dir`].
+  dump_dir: PathBuf,
+  /// See [`Registration::created_dirs`].
+  created_dirs: Vec<PathBuf>,
+  /// See [`Registration::core_path`].
+  core_path: Option<PathBuf>,
+  /// Whether a panic hook was actually installed.
+  installed: bool,
+}
+
+impl Registration {
+  /// Retrieve the core file size limit that was in effect before
+  /// registration.
+  ///
+  /// Applications may want to log this value or restore it themselves
+  /// at some point. Note that if [`Builder::lazy_rlimit`] was used, the
+  /// limit was not yet adjusted.
+  pub fn previous_limit(&self) -> CoreLimit {
+    self.previous_limit
+  }
+
+  /// Retrieve the crash signals that had a non-default disposition at
+  /// the time of registration.
+  ///
+  /// Please refer to [`foreign_crash_handlers`] for details.
+  pub fn foreign_handlers(&self) -> &[c_int] {
+    &self.foreign_handlers
+  }
+
+  /// Retrieve the directory core files are dumped into.
+  ///
+  /// This is the system's temp directory, unless that does not exist,
+  /// in which case a fallback got chosen (e.g., because the process
+  /// runs inside a chroot lacking `/tmp`).
+  pub fn dump_dir(&self) -> &Path {
+    &self.dump_dir
+  }
+
+  /// Retrieve the directories that were created as part of
+  /// registration, outermost first.
+  ///
+  /// Please refer to [`Builder::create_dir`] for details.
+  pub fn created_dirs(&self) -> &[PathBuf] {
+    &self.created_dirs
+  }
+
+  /// Retrieve the path at which the core file is expected to be
+  /// created, as predicted at registration time.
+  ///
+  /// The prediction is based on `/proc/sys/kernel/core_pattern` and
+  /// `/proc/sys/kernel/core_uses_pid` on Linux, on the `kern.corefile`
+  /// sysctl on macOS and FreeBSD, and on the `proc.curproc.corename`
+  /// sysctl on NetBSD, and on the configuration reported by
+  /// `coreadm(8)` on illumos and Solaris. On OpenBSD, core files are
+  /// always named after the program. It is not available on other
+  /// systems.
+  /// `None` is returned if core files are piped to a helper or if the
+  /// pattern contains specifiers whose values are only known at crash
+  /// time (such as `%t`).
+  pub fn core_path(&self) -> Option<&Path> {
+    self.core_path.as_deref()
+  }
+}
+
+
+/// A guard unregistering the panic handler once dropped.
+///
+/// A guard is created by [`Builder::register_scoped`] and dropping it
+/// is equivalent to calling [`unregister`], restoring the previous
+/// panic hook and core file size limit. Because registrations are
+/// stacked, guards have to be dropped in the reverse order of their
+/// creation, as naturally happens for guards bound to nested scopes.
+#[derive(Debug)]
+#[must_use = "the panic handler is unregistered once the guard is dropped"]
+pub struct Guard {
+  /// The registration to undo.
+  registration: Registration,
+}
+
+impl Guard {
+  /// Retrieve information about the registration.
+  pub fn registration(&self) -> &Registration {
+    &self.registration
+  }
+}
+
+impl Drop for Guard {
+  fn drop(&mut self) {
+    if self.registration.installed {
+      if let Err((ctx, err)) = unregister() {
+        eprintln!("{}: {}", ctx, err);
+      }
+    }
+  }
+}
+
+
+/// A predicate deciding whether to dump core for a panic.
+// `PanicInfo` got renamed to `PanicHookInfo`, but the latter is not
+// available on our minimum supported Rust version.
+#[allow(deprecated)]
+type FilterFn = dyn Fn(&std::panic::PanicInfo<'_>) -> bool + Send + Sync + 'static;
+
+/// A shareable [`FilterFn`].
+#[derive(Clone)]
+struct Filter(Arc<FilterFn>);
+
+impl Debug for Filter {
+  fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+    f.write_str("Filter(..)")
+  }
+}
+
+
+/// A callback receiving errors occurring in the panic hook.
+type ErrorSinkFn = dyn FnMut(&str, &Error) + Send + 'static;
+
+/// A shareable [`ErrorSinkFn`].
+#[derive(Clone)]
+struct ErrorSink(Arc<Mutex<ErrorSinkFn>>);
+
+impl Debug for ErrorSink {
+  fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+    f.write_str("ErrorSink(..)")
+  }
+}
+
+
+/// Report an error to the given sink or, absent one, to stderr.
+///
+/// `prefix` is prepended to `ctx`. The two are joined on the stack, as
+/// errors get reported from the panic hook, where we would rather not
+/// allocate.
+fn report_to(sink: Option<&ErrorSink>, prefix: &str, ctx: &str, err: &Error) {
+  match sink {
+    Some(ErrorSink(sink)) => {
+      let mut buffer = Buffer::<256>::new();
+      let ctx = if prefix.is_empty() {
+        ctx
+      } else {
+        let _buffer = buffer.push(prefix.as_bytes()).push(ctx.as_bytes());
+        from_utf8(buffer.as_bytes()).unwrap_or(ctx)
+      };
+      let mut sink = sink.lock().unwrap_or_else(PoisonError::into_inner);
+      sink(ctx, err)
+    },
+    None => eprintln!("{}{}: {}", prefix, ctx, err),
+  }
+}
+
+
+/// Report an error to the sink of the most recent registration or,
+/// absent one, to stderr.
+pub(crate) fn report_error(prefix: &str, ctx: &str, err: &Error) {
+  let settings = settings();
+  let sink = settings
+    .as_ref()
+    .and_then(|settings| settings.error_sink.as_ref());
+  report_to(sink, prefix, ctx, err)
+}
+
+
+/// Report a warning about the system configuration detected at
+/// registration time to the given sink or, absent one, to stderr.
+fn warn(sink: Option<&ErrorSink>, ctx: &str, msg: &'static str) {
+  let err = Error::from(IoError::new(ErrorKind::Other, msg));
+  report_to(sink, "coredump: ", ctx, &err)
+}
+
+
+/// A callback receiving the path at which a core file is expected to
+/// be created.
+type OnDumpFn = dyn FnMut(&Path) + Send + 'static;
+
+/// A shareable [`OnDumpFn`].
+#[derive(Clone)]
+struct OnDump(Arc<Mutex<OnDumpFn>>);
+
+impl Debug for OnDump {
+  fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+    f.write_str("OnDump(..)")
+  }
+}
+
+
+/// The settings of the most recent registration, to the extent they
+/// are relevant to [`reapply`].
+#[derive(Clone, Debug)]
+pub(crate) struct Settings {
+  /// The directory to dump core files into.
+  pub(crate) dir: PathBuf,
+  /// The directory to dump core files into, opened ahead of time.
+  dir_fd: Arc<OwnedFd>,
+  /// The system configuration as cached for use at crash time.
+  config: CoreConfig,
+  /// See [`Builder::dirs`].
+  candidates: Vec<PathBuf>,
+  /// See [`Builder::create_dir`].
+  dir_mode: Option<u32>,
+  /// See [`Builder::nice`].
+  priority: Priority,
+  /// See [`Builder::signal`].
+  signal: c_int,
+  /// See [`Builder::fallbacks`].
+  fallbacks: Vec<Fallback>,
+  /// The cap on the soft core file size limit.
+  max_size: Option<u64>,
+  /// See [`Builder::lazy_rlimit`].
+  lazy_rlimit: bool,
+  /// See [`Builder::manage_rlimit`].
+  manage_rlimit: bool,
+  /// The actions to take for crash signals, as resolved from
+  /// [`Builder::crash_signals`], [`Builder::abort_signal`], and
+  /// [`Builder::crash_signal`].
+  crash_actions: Vec<(c_int, CrashAction)>,
+  /// See [`Builder::alloc_error_hook`].
+  alloc_error_hook: bool,
+  /// See [`Builder::crash_handler_policy`].
+  crash_handler_policy: HandlerPolicy,
+  /// See [`Builder::watchdog`].
+  watchdog: Option<Duration>,
+  /// See [`Builder::metadata`].
+  metadata: bool,
+  /// See [`Builder::error_sink`].
+  error_sink: Option<ErrorSink>,
+  /// See [`Builder::verify_on_fork`].
+  #[cfg(target_os = "linux")]
+  verify_on_fork: bool,
+  /// See [`Builder::force_dumpable`].
+  #[cfg(target_os = "linux")]
+  force_dumpable: bool,
+}
+
+/// The settings of the most recent successful registration, if any.
+///
+/// The settings are shared, so that the panic hook can take a snapshot
+/// of them without allocating.
+pub(crate) static SETTINGS: Mutex<Option<Arc<Settings>>> = Mutex::new(None);
+
+
+/// Retrieve the settings of the most recent successful registration,
+/// if any.
+pub(crate) fn settings() -> Option<Arc<Settings>> {
+  SETTINGS
+    .lock()
+    .unwrap_or_else(PoisonError::into_inner)
+    .clone()
+}
+
+
+/// A builder for configuring the panic handler to register.
+#[derive(Clone, Debug, Default)]
+pub struct Builder {
+  /// Whether to abort the process if it is still running after the
+  /// dump attempt.
+  always_abort: bool,
+  /// The status code to exit with if the process is still running
+  /// after the dump attempt.
+  exit_code: Option<c_int>,
+  /// The time after which to forcefully kill the process if it is still
+  /// alive after the dump was triggered.
+  watchdog: Option<Duration>,
+  /// How to deal with a pre-existing handler for the dump signal.
+  handler_policy: HandlerPolicy,
+  /// When to dump core relative to the previously installed hook.
+  hook_order: HookOrder,
+  /// Whether to refrain from invoking the previously installed hook
+  /// when dumping core.
+  quiet: bool,
+  /// Whether to cooperate with libFuzzer's crash handling.
+  libfuzzer: bool,
+  /// The candidate directories to dump core files into.
+  dirs: Vec<PathBuf>,
+  /// The permission bits to use when creating the dump directory.
+  dir_mode: Option<u32>,
+  /// Whether to write crash metadata into the dump directory.
+  metadata: bool,
+  /// The path of the crash marker to write on panic.
+  crash_marker: Option<PathBuf>,
+  /// The percentage of crashes to dump core for.
+  sample: Option<u8>,
+  /// The minimum interval between two dumps.
+  rate_limit: Option<Duration>,
+  /// The predicate deciding whether to dump core for a panic.
+  filter: Option<Filter>,
+  /// The callback to report errors in the panic hook to.
+  error_sink: Option<ErrorSink>,
+  /// The callback to inform about the expected location of a core
+  /// file.
+  on_dump: Option<OnDump>,
+  /// The strings of which a panic message has to contain one for core
+  /// to be dumped.
+  messages: Vec<String>,
+  /// The prefixes of which a panic location's file has to start with
+  /// one for core to be dumped.
+  locations: Vec<String>,
+  /// The application version to include in a bug report, if one is to
+  /// be written.
+  bug_report: Option<String>,
+  /// Whether to defer raising the core file size limit until a panic
+  /// occurs.
+  lazy_rlimit: bool,
+  /// Whether to leave the core file size limit alone.
+  unmanaged_rlimit: bool,
+  /// Whether to register even if the hard core file size limit is zero.
+  allow_zero_limit: bool,
+  /// The scheduling priorities to apply while dumping core.
+  priority: Priority,
+  /// The signal to dump core with, if not the default one.
+  signal: Option<c_int>,
+  /// The fallbacks to try should raising the dump signal fail, if not
+  /// the default ones.
+  fallbacks: Option<Vec<Fallback>>,
+  /// The cap on the soft core file size limit, in bytes.
+  max_size: Option<u64>,
+  /// Whether to dump core for crash signals as well.
+  crash_signals: bool,
+  /// Whether to dump core for SIGABRT as well.
+  abort_signal: bool,
+  /// The actions to take for individual crash signals.
+  crash_actions: Vec<(c_int, CrashAction)>,
+  /// Whether to dump core when allocating memory failed.
+  alloc_error_hook: bool,
+  /// How to deal with pre-existing handlers for crash signals.
+  crash_handler_policy: HandlerPolicy,
+  /// Whether to re-verify the dump configuration in forked children.
+  #[cfg(target_os = "linux")]
+  verify_on_fork: bool,
+  /// The types of memory mappings to include in core files.
+  #[cfg(target_os = "linux")]
+  coredump_filter: Option<CoredumpFilter>,
+  /// Whether to mark the process as dumpable.
+  #[cfg(target_os = "linux")]
+  force_dumpable: bool,
+}
+
+impl Builder {
+  /// Create a new `Builder` using the default configuration.
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  /// Configure whether to abort the process should it still be running
+  /// after the attempt to dump core.
+  ///
+  /// Please refer to [`register_and_always_abort`] for details.
+  pub fn always_abort(mut self, enable: bool) -> Self {
+    self.always_abort = enable;
+    self
+  }
+
+  /// Configure a status code to exit the process with should it still
+  /// be running after the attempt to dump core.
+  ///
+  /// That is the case if dumping core failed (e.g., because sending
+  /// the signal is prohibited by a seccomp policy) or if the signal
+  /// did not terminate the process. Exiting with a distinct code makes
+  /// the crash obviously fatal to supervisors, without the second
+  /// dump attempt that [`Builder::always_abort`] entails. Crashes for
+  /// which no core is dumped on purpose (see [`Builder::sample`]) are
+  /// treated the same. The exit happens by means of
+  /// [`std::process::exit`] and takes precedence over
+  /// [`Builder::always_abort`].
+  ///
+  /// By default, the panic continues as usual.
+  pub fn exit_code(mut self, code: Option<c_int>) -> Self {
+    self.exit_code = code;
+    self
+  }
+
+  /// Configure a watchdog that forcefully kills the process (via
+  /// SIGKILL) should it still be alive `timeout` after the dump was
+  /// triggered.
+  ///
+  /// Without a watchdog, a process may linger around for an arbitrary
+  /// amount of time, for example when the dump signal got swallowed or
+  /// when writing the core file hangs (as can happen on network file
+  /// systems). Note that the process getting killed by the watchdog
+  /// likely means that no (complete) core file was written. Picking a
+  /// timeout below that of a supervisor that would kill the process
+  /// anyway at least has the process terminate in a well-defined
+  /// manner. The watchdog is armed for crash signals (see
+  /// [`Builder::crash_signals`]) as well.
+  ///
+  /// The watchdog is disabled by default.
+  pub fn watchdog(mut self, timeout: Option<Duration>) -> Self {
+    self.watchdog = timeout;
+    self
+  }
+
+  /// Configure how to deal with a pre-existing handler for the signal
+  /// used for dumping core.
+  ///
+  /// The default is [`HandlerPolicy::TheirsFirst`].
+  pub fn handler_policy(mut self, policy: HandlerPolicy) -> Self {
+    self.handler_policy = policy;
+    self
+  }
+
+  /// Configure whether to dump core before or after invoking the panic
+  /// hook installed prior to registration.
+  ///
+  /// Snapshot cores, as created for panicking tasks and in libFuzzer
+  /// mode (see [`Builder::libfuzzer`]), are always created before
+  /// invoking the previous hook, as the process lives on.
+  ///
+  /// The default is [`HookOrder::AfterExisting`].
+  pub fn hook_order(mut self, order: HookOrder) -> Self {
+    self.hook_order = order;
+    self
+  }
+
+  /// Configure whether to refrain from invoking the panic hook
+  /// installed prior to registration for panics that dump core.
+  ///
+  /// Typically, that hook is the default one, which prints the panic
+  /// message and possibly a backtrace to stderr. For end-user facing
+  /// applications such output may be undesired, while the core file is
+  /// still valuable. The hook is invoked regardless if dumping core
+  /// failed, for panics for which no core is dumped on purpose (see,
+  /// for example, [`Builder::sample`]), and in libFuzzer mode, where
+  /// it is required for the fuzzer to work.
+  ///
+  /// Quiet mode is disabled by default.
+  pub fn quiet(mut self, enable: bool) -> Self {
+    self.quiet = enable;
+    self
+  }
+
+  /// Configure whether to cooperate with libFuzzer's crash handling.
+  ///
+  /// libFuzzer (and, by extension, `cargo fuzz`) relies on panics
+  /// aborting the process, upon which it stores the crashing input for
+  /// later reproduction and minimization. Killing the process with
+  /// SIGQUIT would prevent that and so, in this mode, a snapshot core
+  /// is created by a forked copy of the process on panic instead. After
+  /// that, the previously installed panic hook (typically the one of
+  /// `libfuzzer-sys`) is invoked as usual.
+  ///
+  /// For this to work, registration has to happen after the fuzzer's
+  /// panic hook is installed, e.g., as part of the fuzz target's
+  /// initialization. Note furthermore that [AddressSanitizer][asan]
+  /// disables core dumps by default on 64 bit systems, which can be
+  /// overwritten by running with `ASAN_OPTIONS=disable_coredump=0`.
+  ///
+  /// [asan]: https://github.com/google/sanitizers/wiki/AddressSanitizerFlags
+  pub fn libfuzzer(mut self, enable: bool) -> Self {
+    self.libfuzzer = enable;
+    self
+  }
+
+  /// Configure whether to write a file with crash metadata into the
+  /// dump directory on panic.
+  ///
+  /// The metadata file is called `coredump-<pid>-<time>.txt`, with
+  /// `<time>` being the (wall clock) time of the crash in seconds since
+  /// the Unix epoch. It contains `key: value` pairs, one per line,
+  /// about the process and the panic. Among others, both wall clock
+  /// and monotonic timestamps are recorded, so that crashes can be
+  /// correlated with logs even if the system clock got adjusted.
+  ///
+  /// If [`Builder::crash_signals`] is enabled, a reduced set of
+  /// metadata is written for crash signals, as only little can be done
+  /// safely from within a signal handler: the process ID, the signal,
+  /// and, for faults, the faulting address. On Linux, SIGSEGV is
+  /// furthermore classified by a `crash-kind` of either
+  /// `stack-overflow` or `segfault`, provided that the bounds of the
+  /// crashing thread's stack are known (see
+  /// [`thread::install_altstack`]). On x86_64, aarch64, and riscv64
+  /// Linux systems, the `instruction-pointer` and `stack-pointer` at
+  /// the time of the crash are recorded as well.
+  ///
+  /// Metadata are disabled by default.
+  pub fn metadata(mut self, enable: bool) -> Self {
+    self.metadata = enable;
+    self
+  }
+
+  /// Configure a crash marker file to write on panic.
+  ///
+  /// The marker records the crash, so that the next run of the program
+  /// can learn about it using [`check_previous_crash`]. It references
+  /// the crash metadata file if [`Builder::metadata`] is enabled as
+  /// well. The marker does not need to reside in the dump directory,
+  /// but its parent directory has to exist.
+  ///
+  /// No marker is written by default.
+  pub fn crash_marker(mut self, path: Option<PathBuf>) -> Self {
+    self.crash_marker = path;
+    self
+  }
+
+  /// Configure the percentage of crashes to dump core for.
+  ///
+  /// In large fleets, capturing a core for every occurrence of the
+  /// same crash is wasteful. With sampling enabled, whether core is
+  /// dumped is decided based on a signature of the crash, derived from
+  /// the panic location (or message). The decision is deterministic:
+  /// the same crash is either always or never dumped, on every
+  /// machine. Values above 100 are treated as 100.
+  ///
+  /// For crashes that are sampled out, crash metadata (see
+  /// [`Builder::metadata`]) are written regardless, with a `dump`
+  /// entry of `sampled-out`. If [`Builder::always_abort`] is used, the
+  /// process is still aborted, but with the soft core file size limit
+  /// lowered to zero. Whenever sampling is enabled, the signature is
+  /// recorded as `crash-signature` in the metadata.
+  ///
+  /// All crashes are dumped by default.
+  pub fn sample(mut self, percent: Option<u8>) -> Self {
+    self.sample = percent;
+    self
+  }
+
+  /// Configure the minimum interval between two dumps.
+  ///
+  /// Services that crash repeatedly, for example because a supervisor
+  /// keeps restarting them, can create vast amounts of duplicate core
+  /// files in little time. With rate limiting enabled, the time of the
+  /// most recent dump is recorded in a file called `.coredump-stamp`
+  /// in the dump directory, shared by all processes dumping there, and
+  /// no core is dumped while less than `interval` has passed since
+  /// then. The interval has a granularity of seconds.
+  ///
+  /// Crashes that are rate limited are treated like those sampled out
+  /// (see [`Builder::sample`]), with a `dump` metadata entry of
+  /// `rate-limited`. Only crashes that are sampled in count towards
+  /// the rate limit.
+  ///
+  /// Dumps are not rate limited by default.
+  pub fn rate_limit(mut self, interval: Option<Duration>) -> Self {
+    self.rate_limit = interval;
+    self
+  }
+
+  /// Configure a predicate deciding whether to dump core for a panic.
+  ///
+  /// The predicate is invoked from the panic hook with information
+  /// about the panic. If it returns `false`, the panic is left to the
+  /// previously installed hook, as if no handler had been registered:
+  /// no core is dumped and no crash metadata, marker, or bug report is
+  /// written. That is useful for applications relying on
+  /// `catch_unwind` for recovering from some panics, while wanting a
+  /// core for others. The predicate should be cheap and must not
+  /// panic itself.
+  ///
+  /// By default, core is dumped for all panics.
+  #[allow(deprecated)]
+  pub fn filter<F>(mut self, filter: F) -> Self
+  where
+    F: Fn(&std::panic::PanicInfo<'_>) -> bool + Send + Sync + 'static,
+  {
+    self.filter = Some(Filter(Arc::new(filter)));
+    self
+  }
+
+  /// Configure a callback to report errors occurring in the panic hook
+  /// to.
+  ///
+  /// Errors, for example failure to dump core or to write crash
+  /// metadata, are reported along with a description of the context in
+  /// which they occurred. That allows for routing them into structured
+  /// logging. Warnings about the system configuration detected during
+  /// registration and the location of a written bug report are
+  /// reported the same way. The callback is invoked from the panic hook
+  /// and must not panic itself.
+  ///
+  /// By default, errors are printed to stderr.
+  pub fn error_sink<F>(mut self, sink: F) -> Self
+  where
+    F: FnMut(&str, &Error) + Send + 'static,
+  {
+    self.error_sink = Some(ErrorSink(Arc::new(Mutex::new(sink))));
+    self
+  }
+
+  /// Configure a callback to inform about the path at which a core
+  /// file is expected to be created.
+  ///
+  /// The callback is invoked from the panic hook right before the dump
+  /// signal is sent or, for snapshot cores (see [`Builder::libfuzzer`]),
+  /// once the forked copy of the process dumping core got created. That
+  /// allows for, say, logging the path or enqueuing the file for upload
+  /// by a separate process. It is only invoked if the path can be
+  /// predicted (see [`Registration::core_path`]); the file may not be
+  /// complete (or even exist) yet by the time it is. The callback must
+  /// not panic itself.
+  ///
+  /// By default, no callback is invoked.
+  pub fn on_dump<F>(mut self, callback: F) -> Self
+  where
+    F: FnMut(&Path) + Send + 'static,
+  {
+    self.on_dump = Some(OnDump(Arc::new(Mutex::new(callback))));
+    self
+  }
+
+  /// Configure strings of which the panic message has to contain at
+  /// least one for core to be dumped.
+  ///
+  /// Panics with other messages are treated as if declined by the
+  /// predicate set via [`Builder::filter`]. Matching is performed on
+  /// plain substrings. Payloads that are not strings are matched as
+  /// `Box<dyn Any>`.
+  ///
+  /// By default, no restriction based on the message is in place.
+  pub fn only_if_message_contains<I, S>(mut self, patterns: I) -> Self
+  where
+    I: IntoIterator<Item = S>,
+    S: Into<String>,
+  {
+    self.messages = patterns.into_iter().map(S::into).collect();
+    self
+  }
+
+  /// Configure prefixes of which the file of the panic location has to
+  /// start with at least one for core to be dumped.
+  ///
+  /// The file is reported as passed to the compiler, typically relative
+  /// to the root of the crate that panicked (e.g.,
+  /// `src/critical/mod.rs`) or, for dependencies, as an absolute path
+  /// into the Cargo registry. Restricting dumps to, say, `src/` hence
+  /// prevents panics in third-party code from creating cores. Module
+  /// paths are not available at panic time and so cannot be matched
+  /// on. Panics without a location are treated as not matching.
+  ///
+  /// Panics with other locations are treated as if declined by the
+  /// predicate set via [`Builder::filter`].
+  ///
+  /// By default, no restriction based on the location is in place.
+  pub fn only_if_location_in<I, S>(mut self, prefixes: I) -> Self
+  where
+    I: IntoIterator<Item = S>,
+    S: Into<String>,
+  {
+    self.locations = prefixes.into_iter().map(S::into).collect();
+    self
+  }
+
+  /// Configure writing a Markdown formatted bug report on panic.
+  ///
+  /// The report is meant for end users of the application to paste
+  /// into a bug tracker (e.g., as a GitHub issue). It contains the
+  /// provided application version (typically
+  /// `env!("CARGO_PKG_VERSION")`), the operating system, the panic
+  /// message and location, a symbolized backtrace, and the crash ID,
+  /// along with instructions for attaching the core file. It is called
+  /// `coredump-<pid>-<time>.md` and written into the dump directory.
+  ///
+  /// No report is written by default.
+  pub fn bug_report(mut self, version: Option<String>) -> Self {
+    self.bug_report = version;
+    self
+  }
+
+  /// Configure whether to defer raising the core file size limit from
+  /// registration time to the time of the panic.
+  ///
+  /// By default, the soft limit on the core file size (`RLIMIT_CORE`)
+  /// is raised as part of registration and stays raised for the
+  /// remainder of the process' lifetime. Some security policies flag
+  /// long-lived non-zero core file size limits. With this option
+  /// enabled the limit is only raised immediately before dumping core.
+  /// If the process survives the dump, as is the case for snapshot
+  /// cores created for panicking tasks or in libFuzzer mode, the
+  /// original limit is restored afterwards.
+  ///
+  /// Registration still fails if the hard limit does not permit the
+  /// creation of core files, unless [`Builder::allow_zero_limit`] is
+  /// used.
+  pub fn lazy_rlimit(mut self, enable: bool) -> Self {
+    self.lazy_rlimit = enable;
+    self
+  }
+
+  /// Configure the value, in bytes, to raise the soft core file size
+  /// limit (`RLIMIT_CORE`) to at most.
+  ///
+  /// By default, the soft limit is raised to the hard limit, which
+  /// commonly is unlimited. As the kernel truncates core files
+  /// exceeding the soft limit, a cap prevents the dump of a huge
+  /// process from filling up the disk, at the cost of the core file
+  /// possibly being incomplete. The `COREDUMP_MAX_SIZE` environment
+  /// variable takes precedence over this setting (see
+  /// [`register_panic_handler`]).
+  pub fn max_size(mut self, max_size: Option<u64>) -> Self {
+    self.max_size = max_size;
+    self
+  }
+
+  /// Configure whether to manage the core file size limit
+  /// (`RLIMIT_CORE`) at all.
+  ///
+  /// In some environments the limit is managed externally, for example
+  /// by systemd unit files (`LimitCORE=`), and applications should not
+  /// touch it. With management disabled, registration merely installs
+  /// the panic hook and the limit is adjusted neither at registration
+  /// time, nor when dumping core, nor by [`reapply`]. Registration
+  /// does not fail because of a zero hard limit in this case, either.
+  /// [`Builder::lazy_rlimit`], [`Builder::allow_zero_limit`], and
+  /// [`Builder::max_size`] are without effect.
+  ///
+  /// The limit is managed by default.
+  pub fn manage_rlimit(mut self, enable: bool) -> Self {
+    self.unmanaged_rlimit = !enable;
+    self
+  }
+
+  /// Configure whether to register the panic handler even if the hard
+  /// core file size limit is zero.
+  ///
+  /// By default, registration fails in this case, as no core file can
+  /// be created. With this option enabled, a warning is printed to
+  /// stderr instead and the panic handler still attempts to dump core
+  /// once a panic happens, as if [`Builder::lazy_rlimit`] were used.
+  /// That may still succeed, for instance, if an administrator raises
+  /// the limit in the meantime (e.g., using `prlimit(1)`) or if core
+  /// files are piped to a helper program, for which the limit is not
+  /// being enforced. [`Registration::previous_limit`] reports the zero
+  /// limit.
+  pub fn allow_zero_limit(mut self, enable: bool) -> Self {
+    self.allow_zero_limit = enable;
+    self
+  }
+
+  /// Configure the CPU niceness (see `setpriority(2)`) to use while
+  /// dumping core.
+  ///
+  /// Writing the core file of a large process can take a considerable
+  /// amount of time, during which colocated services compete with it
+  /// for resources. On Linux, the kernel writes the core file in the
+  /// context of the thread that received the dump signal and the
+  /// adjustment only applies to that thread. Insufficient privileges to
+  /// apply the value are silently ignored.
+  ///
+  /// By default, the niceness is left untouched.
+  pub fn nice(mut self, nice: Option<c_int>) -> Self {
+    self.priority.nice = nice;
+    self
+  }
+
+  /// Configure the I/O priority (see `ioprio_set(2)`) to use while
+  /// dumping core.
+  ///
+  /// Using [`IoPriority::Idle`], for example, prevents the writing of a
+  /// huge core file from starving other processes of disk bandwidth.
+  /// The same remarks as for [`Builder::nice`] apply.
+  ///
+  /// By default, the I/O priority is left untouched.
+  #[cfg(target_os = "linux")]
+  pub fn io_priority(mut self, priority: Option<IoPriority>) -> Self {
+    self.priority.io = priority;
+    self
+  }
+
+  /// Configure the signal to dump core with.
+  ///
+  /// The signal has to be one whose default action is to terminate
+  /// the process with a core dump (e.g., SIGABRT or SIGSEGV; see
+  /// `signal(7)`), otherwise no core file is created. Using a signal
+  /// other than SIGQUIT can be useful if the application (or a
+  /// runtime it embeds) repurposes SIGQUIT, as the JVM does, for
+  /// example. The chosen signal is also what the `%s` specifier in
+  /// the system's `core_pattern` expands to.
+  ///
+  /// By default, SIGQUIT is used. On Android, SIGABRT is, because
+  /// debuggerd only writes tombstones for it (see
+  /// [`Builder::register`]).
+  pub fn signal(mut self, signal: Option<c_int>) -> Self {
+    self.signal = signal;
+    self
+  }
+
+  /// Configure the fallbacks to try, in order, should raising the dump
+  /// signal fail.
+  ///
+  /// Please refer to [`Fallback`] for the available options. An empty
+  /// list disables fallbacks, making the failure get reported like any
+  /// other.
+  ///
+  /// By default, [`Fallback::Kill`] is tried first and
+  /// [`Fallback::Abort`] second.
+  pub fn fallbacks<I>(mut self, fallbacks: I) -> Self
+  where
+    I: IntoIterator<Item = Fallback>,
+  {
+    self.fallbacks = Some(fallbacks.into_iter().collect());
+    self
+  }
+
+  /// Configure whether to dump core for crashes other than panics.
+  ///
+  /// Panics are only one class of crashes. Memory errors in `unsafe`
+  /// code or in native libraries, for example, result in signals that
+  /// never reach the panic hook. If enabled, handlers for SIGSEGV,
+  /// SIGBUS, SIGILL, SIGFPE, and SIGABRT are installed that raise the
+  /// core file size limit (subject to [`Builder::manage_rlimit`] and
+  /// [`Builder::max_size`]), change the working directory to the dump
+  /// directory, and re-raise the signal with its default disposition,
+  /// which terminates the process with a core dump. Handlers
+  /// previously installed for these signals are chained to as
+  /// configured by [`Builder::crash_handler_policy`] and reinstated by
+  /// [`unregister`].
+  ///
+  /// The handlers run on the alternate signal stack of the crashing
+  /// thread, so that stack overflows can be handled. Registration sets
+  /// up such a stack for the calling thread, if it has none. Please
+  /// refer to [`thread::install_altstack`] for other threads.
+  ///
+  /// Crash signals are left alone by default.
+  pub fn crash_signals(mut self, enable: bool) -> Self {
+    self.crash_signals = enable;
+    self
+  }
+
+  /// Configure whether to dump core for SIGABRT.
+  ///
+  /// Processes get aborted by [`std::process::abort`], by C code
+  /// calling `abort(3)` (e.g., on failed assertions), and by the Rust
+  /// runtime itself, for instance when panicking while panicking. The
+  /// latter includes panics in a panic hook (ours included, e.g.,
+  /// because of a panicking [`Builder::filter`]) and panics while
+  /// formatting the panic message, say, in a `Display` implementation.
+  /// None of these reach the panic hook. If enabled, a handler for SIGABRT
+  /// is installed that prepares the process for dumping core the same
+  /// way [`Builder::crash_signals`] does for all crash signals, which
+  /// it is a subset of. That is useful if handlers for other crash
+  /// signals are unwanted, because a runtime relies on them, say.
+  ///
+  /// Note that panics in programs built with `panic = "abort"` still
+  /// invoke the panic hook and so dump core regardless of this
+  /// setting. If the hook declines to (e.g., because of
+  /// [`Builder::filter`]), the runtime aborts the process afterwards,
+  /// which then results in a core dump if this setting is enabled.
+  ///
+  /// SIGABRT is left alone by default.
+  pub fn abort_signal(mut self, enable: bool) -> Self {
+    self.abort_signal = enable;
+    self
+  }
+
+  /// Configure the action to take for an individual crash signal.
+  ///
+  /// `signal` has to be one of SIGSEGV, SIGBUS, SIGILL, SIGFPE, and
+  /// SIGABRT, otherwise registration fails. The action takes precedence
+  /// over [`Builder::crash_signals`] and [`Builder::abort_signal`],
+  /// which merely establish defaults. That allows for, say, dumping
+  /// core for memory errors while leaving aborts to a crash reporter:
+  /// ```rust
+  /// # use libc::SIGABRT;
+  /// # use libc::SIGSEGV;
+  /// # use coredump::Builder;
+  /// # use coredump::CrashAction;
+  /// let builder = Builder::new()
+  ///   .crash_signal(SIGSEGV, CrashAction::Dump)
+  ///   .crash_signal(SIGABRT, CrashAction::ChainOnly);
+  /// ```
+  ///
+  /// Configuring the action for a signal again replaces the earlier
+  /// one.
+  pub fn crash_signal(mut self, signal: c_int, action: CrashAction) -> Self {
+    self.crash_actions.retain(|(other, _)| *other != signal);
+    self.crash_actions.push((signal, action));
+    self
+  }
+
+  /// Configure whether to dump core when allocating memory failed.
+  ///
+  /// Allocation failures abort the process without invoking the panic
+  /// hook. If enabled, a hook is installed with
+  /// [`std::alloc::set_alloc_error_hook`] that prepares the process for
+  /// dumping core the same way [`Builder::abort_signal`] does, before
+  /// the runtime aborts it. As memory is likely exhausted at this
+  /// point, the hook does not allocate and solely relies on state set
+  /// up during registration.
+  ///
+  /// This functionality is only available with the `alloc-error-hook`
+  /// feature, which requires a nightly toolchain. Without it, enabling
+  /// [`Builder::abort_signal`] is the closest alternative.
+  ///
+  /// The allocation error hook is left alone by default.
+  #[cfg(feature = "alloc-error-hook")]
+  pub fn alloc_error_hook(mut self, enable: bool) -> Self {
+    self.alloc_error_hook = enable;
+    self
+  }
+
+  /// Configure how to deal with handlers for crash signals that were
+  /// installed before ours (see [`Builder::crash_signals`]).
+  ///
+  /// Runtimes such as the JVM handle SIGSEGV as part of their regular
+  /// operation and sanitizers install handlers for reporting crashes,
+  /// among others. The policy is applied as follows:
+  /// - [`HandlerPolicy::TheirsFirst`]: the pre-existing handler is
+  ///   invoked first. If it returns with our handler still in place,
+  ///   the signal is considered taken care of and nothing else is done.
+  ///   If it restored the default disposition (as the Rust standard
+  ///   library's handler does, for example), core is dumped as usual.
+  /// - [`HandlerPolicy::OursFirst`]: core is dumped without invoking
+  ///   the pre-existing handler.
+  /// - [`HandlerPolicy::Skip`]: the pre-existing handler is invoked in
+  ///   place of ours, which only acts on signals that had no handler.
+  ///
+  /// The default is [`HandlerPolicy::TheirsFirst`].
+  pub fn crash_handler_policy(mut self, policy: HandlerPolicy) -> Self {
+    self.crash_handler_policy = policy;
+    self
+  }
+
+  /// Configure whether to re-verify the dump configuration in forked
+  /// children.
+  ///
+  /// Daemonizing processes frequently fork and then change their
+  /// working directory or drop privileges, which can invalidate
+  /// assumptions made at registration time. If enabled, a fork handler
+  /// (see `pthread_atfork(3)`) is installed that checks in every forked
+  /// child that the dump directory is still accessible, that a relative
+  /// dump directory still refers to the same location, and that the
+  /// process is still dumpable (see `PR_GET_DUMPABLE` in `prctl(2)`).
+  /// Problems are reported on stderr.
+  ///
+  /// Note that fork handlers cannot be uninstalled.
+  #[cfg(target_os = "linux")]
+  pub fn verify_on_fork(mut self, enable: bool) -> Self {
+    self.verify_on_fork = enable;
+    self
+  }
+
+  /// Configure the types of memory mappings to include in core files.
+  ///
+  /// The filter is written to `/proc/self/coredump_filter` during
+  /// registration and is the main lever for controlling the size of
+  /// core files: excluding, say, large shared memory regions may shrink
+  /// them considerably. Should registration fail, the previous filter
+  /// is restored. Note that the filter is inherited by child processes
+  /// and is left in place on [`unregister`].
+  ///
+  /// By default, the filter in effect is left alone.
+  #[cfg(target_os = "linux")]
+  pub fn coredump_filter(mut self, filter: Option<CoredumpFilter>) -> Self {
+    self.coredump_filter = filter;
+    self
+  }
+
+  /// Configure whether to mark the process as dumpable.
+  ///
+  /// The kernel clears a process' dumpable flag (see `PR_SET_DUMPABLE`
+  /// in `prctl(2)`) when it changes credentials, e.g., by dropping
+  /// privileges or by executing a set-user-ID binary. Unless
+  /// `/proc/sys/fs/suid_dumpable` says otherwise, no core file is
+  /// created for such a process, irrespective of the core file size
+  /// limit. If enabled, the flag is set during registration as well as
+  /// by [`reapply`], which is meant to be invoked after dropping
+  /// privileges. Without it, registration merely warns about the
+  /// process not being dumpable.
+  ///
+  /// **Security note:** The dumpable flag protects privileged state.
+  /// With it set, the resulting core file as well as the process
+  /// itself (by means of `ptrace(2)` and `/proc/<pid>`) become
+  /// accessible to the process' (unprivileged) owner, potentially
+  /// exposing secrets such as keys or password hashes that the process
+  /// read while privileged. Only enable this setting if that is
+  /// acceptable, ideally at the explicit request of an operator.
+  ///
+  /// The flag is left alone by default.
+  #[cfg(target_os = "linux")]
+  pub fn force_dumpable(mut self, enable: bool) -> Self {
+    self.force_dumpable = enable;
+    self
+  }
+
+  /// Configure the directory to dump core files into.
+  ///
+  /// By default, the system's temp directory is used (with fallbacks
+  /// should it not exist). A relative path is interpreted relative to
+  /// the working directory at the time of registration. Note that
+  /// system configuration (`core_pattern`) may still direct core files
+  /// elsewhere.
+  pub fn dir(mut self, dir: Option<PathBuf>) -> Self {
+    self.dirs = dir.into_iter().collect();
+    self
+  }
+
+  /// Configure an ordered list of candidate directories to dump core
+  /// files into.
+  ///
+  /// At registration time, the first candidate that is a directory we
+  /// can write to is picked (see [`Registration::dump_dir`]). If
+  /// [`Builder::create_dir`] is used as well, missing candidates are
+  /// created as they are tried. Registration fails if none of them is
+  /// usable, reporting the error for the last one.
+  ///
+  /// This method supersedes [`Builder::dir`] and vice versa.
+  pub fn dirs<I>(mut self, candidates: I) -> Self
+  where
+    I: IntoIterator<Item = PathBuf>,
+  {
+    self.dirs = candidates.into_iter().collect();
+    self
+  }
+
+  /// Configure whether to create the dump directory set via
+  /// [`Builder::dir`] (or [`Builder::dirs`]), including any missing
+  /// parents, should it not exist.
+  ///
+  /// Directories are created with the provided permission bits (e.g.,
+  /// `0o700`), subject to the process' umask. The created paths are
+  /// reported via [`Registration::created_dirs`]. If registration
+  /// fails, directories created by it are removed again.
+  ///
+  /// By default, a missing dump directory causes registration to fail.
+  pub fn create_dir(mut self, mode: Option<u32>) -> Self {
+    self.dir_mode = mode;
+    self
+  }
+
+  /// Register a panic handler with the configured settings, returning
+  /// a guard unregistering it once dropped.
+  ///
+  /// This is useful for libraries, test suites, and plugins that must
+  /// not leave behind global state. Please refer to [`Guard`] for
+  /// details.
+  pub fn register_scoped(self) -> Result<Guard, (Str, Error)> {
+    let registration = self.register()?;
+    Ok(Guard { registration })
+  }
+
+  /// Register a panic handler with the configured settings.
+  ///
+  /// Please refer to [`register_panic_handler`] for general remarks,
+  /// including on environment variables taking precedence over the
+  /// configured settings.
+  ///
+  /// Registration is all or nothing: should it fail, the core file
+  /// size limit is restored, directories created by it are removed,
+  /// and neither the panic hook, the handlers for crash signals (see
+  /// [`Builder::crash_signals`]), nor the state used by the fork
+  /// handler (see [`Builder::verify_on_fork`]) are changed.
+  ///
+  /// On macOS and FreeBSD, registration fails if core dumps are
+  /// disabled system wide (via the `kern.coredump` sysctl). On these
+  /// systems as well as on NetBSD, it also fails if the directory core
+  /// files are written to (as configured via `kern.corefile` or
+  /// `proc.curproc.corename`, respectively) is not writable.
+  ///
+  /// On Linux, registration fails when running under WSL1, which does
+  /// not support core dumps. Under WSL2, a warning is printed if core
+  /// files are piped to a helper program that does not exist.
+  ///
+  /// On Android, crashes are handled by debuggerd, which has a
+  /// tombstone written into `/data/tombstones` (and a summary logged to
+  /// the `crash` log buffer) rather than a core file. The panic message
+  /// is recorded as the tombstone's abort message.
+  pub fn register(mut self) -> Result<Registration, (Str, Error)> {
+    let overrides = if cfg!(feature = "disabled") {
+      Overrides::default()
+    } else {
+      Overrides::read()?
+    };
+
+    if cfg!(feature = "disabled") || overrides.disable {
+      // Nothing is changed, so the limit in effect now is the one that
+      // was in effect "before registration".
+      let registration = Registration {
+        foreign_handlers: Vec::new(),
+        previous_limit: CoreLimit::from_raw(&query_core_limit()?),
+        dump_dir: temp_dir(),
+        created_dirs: Vec::new(),
+        core_path: None,
+        installed: false,
+      };
+      return Ok(registration)
+    }
+
+    if let Some(dir) = overrides.dir {
+      self.dirs = vec![dir];
+    }
+    if let Some(max_size) = overrides.max_size {
+      self.max_size = Some(max_size);
+    }
+
+    #[cfg(target_os = "linux")]
+    if wsl_version() == Some(1) {
+      Err(IoError::new(ErrorKind::Unsupported, "running under WSL1").into())
+        .ctx(|| "core dumps are not supported")?;
+    }
+
+    let foreign_handlers = foreign_crash_handlers()?;
+    let crash_actions =
+      crash::actions(self.crash_signals, self.abort_signal, &self.crash_actions)?;
+
+    let signal = self.signal.unwrap_or(DEFAULT_SIGNAL);
+    // Blocking is inherited by threads spawned later on and by child
+    // processes, so it likely applies to the panicking thread as well.
+    // We unblock the signal before dumping core, but the blocking
+    // hints at a configuration problem worth knowing about.
+    if is_blocked(signal).ctx(|| "failed to inquire signal mask")? {
+      warn(
+        self.error_sink.as_ref(),
+        &format!("signal {} is blocked", signal),
+        "it will be unblocked for dumping core",
+      );
+    }
+
+    // Registration has to be all or nothing: should any of the steps
+    // below fail, we roll back the changes applied up to that point.
+    // Steps that cannot be rolled back have to come last.
+    let mut transaction = Transaction::default();
+    let limit = query_core_limit()?;
+    let tolerate = limit.rlim_max == 0 && self.allow_zero_limit;
+    let previous_limit = if self.unmanaged_rlimit {
+      // The limit is none of our business.
+      self.lazy_rlimit = false;
+      limit
+    } else if tolerate {
+      warn(
+        self.error_sink.as_ref(),
+        "hard core file size limit is zero",
+        "core files may not get created",
+      );
+      // The limit may get raised externally in the meantime, so try
+      // adjusting it once a panic happens.
+      self.lazy_rlimit = true;
+      limit
+    } else if self.lazy_rlimit {
+      core_limit()?
+    } else {
+      let limit = raise_core_limit(self.max_size)?;
+      transaction.limit = Some(limit);
+      limit
+    };
+
+    let dir = resolve_dir(&self.dirs, self.dir_mode, &mut transaction.dirs)?;
+    let dir_fd = open_dir(&dir)?;
+
+    if crash_actions.iter().any(|(signal, _)| *signal == SIGSEGV) {
+      let () = altstack::install()?;
+      let () = crash::record_stack_bounds();
+    }
+    if !crash_actions.is_empty() || self.alloc_error_hook {
+      transaction.crash = Some(crash::install(
+        &dir,
+        &crash_actions,
+        !self.unmanaged_rlimit,
+        self.max_size,
+        self.metadata,
+        self.watchdog,
+        self.crash_handler_policy,
+      )?);
+    }
+
+    #[cfg(target_os = "linux")]
+    if self.verify_on_fork {
+      transaction.fork = Some(atfork::install(&dir)?);
+    }
+
+    let config = CoreConfig::read(&dir, signal);
+    #[cfg(any(target_os = "macos", target_os = "freebsd", target_os = "netbsd"))]
+    let () = sysctl::check_config(&config)?;
+    #[cfg(target_os = "linux")]
+    if wsl_version().is_some() {
+      if let Some(helper) = config.core_pattern.as_deref().and_then(pipe_helper) {
+        if !Path::new(helper).exists() {
+          warn(
+            self.error_sink.as_ref(),
+            &format!("core pattern helper {} does not exist", helper),
+            "core files may not get created",
+          );
+        }
+      }
+    }
+    #[cfg(target_os = "linux")]
+    {
+      if let Some(filter) = self.coredump_filter {
+        let previous = dump_filter::read()?;
+        let () = dump_filter::apply(filter)?;
+        transaction.coredump_filter = Some(previous);
+      }
+      // Marking the process as dumpable is not rolled back, so it has
+      // to be the last fallible step.
+      if self.force_dumpable {
+        let () = make_dumpable()?;
+      } else if unsafe { prctl(PR_GET_DUMPABLE) } == 0 {
+        warn(
+          self.error_sink.as_ref(),
+          "process is not dumpable",
+          "core files will not get created",
+        );
+      }
+    }
+    let core_path = config.core_path.clone();
+    let settings = Settings {
+      dir: dir.clone(),
+      dir_fd,
+      config,
+      candidates: self.dirs.clone(),
+      dir_mode: self.dir_mode,
+      priority: self.priority,
+      signal,
+      fallbacks: self
+        .fallbacks
+        .clone()
+        .unwrap_or_else(|| DEFAULT_FALLBACKS.to_vec()),
+      max_size: self.max_size,
+      lazy_rlimit: self.lazy_rlimit,
+      manage_rlimit: !self.unmanaged_rlimit,
+      crash_actions,
+      alloc_error_hook: self.alloc_error_hook,
+      crash_handler_policy: self.crash_handler_policy,
+      watchdog: self.watchdog,
+      metadata: self.metadata,
+      error_sink: self.error_sink.clone(),
+      #[cfg(target_os = "linux")]
+      verify_on_fork: self.verify_on_fork,
+      #[cfg(target_os = "linux")]
+      force_dumpable: self.force_dumpable,
+    };
+
+    // Installation of the hooks is infallible and so it is performed
+    // last.
+    #[cfg(feature = "alloc-error-hook")]
+    crash::set_alloc_error_hook(self.alloc_error_hook);
+    install_hook(self, transaction.limit);
+    let created_dirs = transaction.dirs.clone();
+    transaction.commit();
+    *SETTINGS.lock().unwrap_or_else(PoisonError::into_inner) = Some(Arc::new(settings));
+
+    let registration = Registration {
+      foreign_handlers,
+      previous_limit: CoreLimit::from_raw(&previous_limit),
+      dump_dir: dir,
+      created_dirs,
+      core_path,
+      installed: true,
+    };
+    Ok(registration)
+  }
+}
+
+
+/// The number of threads dumping core from within our panic hook.
+///
+/// The hook is never re-entered on the thread running it: the Rust
+/// runtime aborts the process on panics in a panic hook. A panic on
+/// another thread while a dump is in progress, however, may well have
+/// been caused by the dump attempt (e.g., by a foreign signal handler
+/// we chained to). Only the dump step itself is covered, so that
+/// unrelated concurrent panics are handled as usual.
+static DUMPING: AtomicUsize = AtomicUsize::new(0);
+
+
+/// Dump core and terminate the process, with as few moving parts as
+/// possible.
+///
+/// This function is used as a fallback when the regular dump path is
+/// not trustworthy. It only performs async-signal-safe operations and
+/// disregards any configuration.
+fn dump_core_minimal() -> ! {
+  unsafe {
+    if let Ok(mut limit) = Sys::core_limit() {
+      limit.rlim_cur = limit.rlim_max;
+      let _ = Sys::set_core_limit(&limit);
+    }
+
+    // We may have been re-entered from a signal handler for SIGQUIT, in
+    // which case the signal is blocked.
+    let _ = SavedAction::reset(SIGQUIT);
+    let _ = unblock(SIGQUIT);
+
+    let _ = raise(SIGQUIT);
+  }
+  // SIGABRT terminates the process with a core dump by default as well.
+  abort()
+}
+
+
+/// Install the panic hook dumping core.
+///
+/// `limit` is the core file size limit that was in effect before
+/// registration, if registration adjusted it.
+fn install_hook(builder: Builder, limit: Option<rlimit>) {
+  let Builder {
+    always_abort,
+    exit_code,
+    watchdog,
+    handler_policy,
+    hook_order,
+    quiet,
+    libfuzzer,
+    metadata,
+    crash_marker,
+    sample,
+    rate_limit,
+    filter,
+    error_sink,
+    on_dump,
+    messages,
+    locations,
+    bug_report,
+    lazy_rlimit,
+    priority,
+    signal,
+    fallbacks,
+    max_size,
+    ..
+  } = builder;
+  let signal = signal.unwrap_or(DEFAULT_SIGNAL);
+  let fallbacks = fallbacks.unwrap_or_else(|| DEFAULT_FALLBACKS.to_vec());
+
+  // The default panic handler is nice in that it allows for retrieving
+  // the backtrace at the time of the panic on the user's discretion. We
+  // want to preserve this functionality and cannot easily reimplement
+  // it without pulling in additional dependencies. Hence, we
+  // effectively just wrap it by adding a step afterwards.
+  hook::install(limit, |id, default_panic| {
+    Box::new(move |panic_info| {
+      if !hook::is_active(id) {
+        // A more recent registration's hook is in charge or we got
+        // unregistered while being wrapped by a foreign hook.
+        default_panic(panic_info);
+        return
+      }
+
+      let task = task::current();
+      let report = |prefix: &str, ctx: &str, err: &Error| {
+        report_to(error_sink.as_ref(), prefix, ctx, err)
+      };
+      let notify = |path: Option<&Path>| {
+        if let (Some(OnDump(callback)), Some(path)) = (&on_dump, path) {
+          let mut callback = callback.lock().unwrap_or_else(PoisonError::into_inner);
+          callback(path)
+        }
+      };
+
+      let payload = panic_info.payload();
+      let message = if let Some(message) = payload.downcast_ref::<&str>() {
+        message
+      } else if let Some(message) = payload.downcast_ref::<String>() {
+        message.as_str()
+      } else {
+        "Box<dyn Any>"
+      };
+
+      let declined = is_suppressed()
+        || thread::is_opted_out()
+        || !filter::matches(message, panic_info.location(), &messages, &locations)
+        || filter
+          .as_ref()
+          .map(|Filter(filter)| !filter(panic_info))
+          .unwrap_or(false);
+      if declined {
+        default_panic(panic_info);
+        return
+      }
+
+      if DUMPING.load(Ordering::SeqCst) > 0 {
+        // Another thread is dumping core and we may have panicked on
+        // behalf of something it invoked (e.g., a foreign signal
+        // handler). Don't risk running into the same problem again.
+        if libfuzzer || task.is_some() {
+          // A snapshot is in progress already.
+          default_panic(panic_info);
+          return
+        }
+        dump_core_minimal()
+      }
+
+      // Everything depending on system configuration has been cached
+      // ahead of time, so that we don't have to consult `/proc` here.
+      // We merely borrow from a snapshot of the settings, to not have
+      // to allocate.
+      let settings = settings();
+      let fallback_dir;
+      let fallback_config;
+      let (dump_dir, dump_dir_fd, config) = match &settings {
+        Some(settings) => (
+          settings.dir.as_path(),
+          Some(&*settings.dir_fd),
+          &settings.config,
+        ),
+        None => {
+          fallback_dir = temp_dir();
+          fallback_config = CoreConfig::default();
+          (fallback_dir.as_path(), None, &fallback_config)
+        },
+      };
+      let core_path = config.core_path.as_deref();
+
+      let signature = sample.map(|_| sample::signature(panic_info.location(), message));
+      let sampled_out = match (signature, sample) {
+        (Some(signature), Some(percent)) => !sample::is_sampled(signature, percent),
+        _ => false,
+      };
+      // If no core is to be dumped, the reason for skipping it.
+      let skipped = if sampled_out {
+        Some("sampled-out")
+      } else if !rate_limit
+        .map(|interval| rate::acquire(dump_dir, interval))
+        .unwrap_or(true)
+      {
+        Some("rate-limited")
+      } else {
+        None
+      };
+
+      let metadata = if metadata || skipped.is_some() {
+        let mut metadata = Metadata::collect();
+        if let Some(task) = &task {
+          metadata.add("task", task.to_string());
+        }
+
+        if let Some(pattern) = &config.core_pattern {
+          metadata.add("core-pattern", pattern.clone());
+        }
+        if let Some(path) = core_path {
+          metadata.add("core-path", path.display().to_string());
+        }
+
+        metadata.add("panic-message", message.to_string());
+        if let Some(location) = panic_info.location() {
+          metadata.add("panic-location", location.to_string());
+        }
+        if let Some(signature) = signature {
+          metadata.add("crash-signature", format!("{:016x}", signature));
+        }
+        if let Some(reason) = skipped {
+          metadata.add("dump", reason.to_string());
+        }
+
+        match metadata.write(dump_dir) {
+          Ok(path) => Some(path),
+          Err((ctx, err)) => {
+            report("failed to write crash metadata: ", &ctx, &err);
+            None
+          },
+        }
+      } else {
+        None
+      };
+
+      if let Some(marker) = &crash_marker {
+        if let Err((ctx, err)) = marker::write(marker, dump_dir, metadata.as_deref()) {
+          report("", &ctx, &err);
+        }
+      }
+
+      if let Some(version) = &bug_report {
+        let crash = markdown::Crash {
+          version,
+          message,
+          location: panic_info.location(),
+          dump_dir,
+          core_path,
+          metadata: metadata.as_deref(),
+        };
+        match crash.write(dump_dir) {
+          Ok(path) => {
+            let path = IoError::new(ErrorKind::Other, path.display().to_string());
+            report("", "a bug report was written", &Error::from(path))
+          },
+          Err((ctx, err)) => report("", &ctx, &err),
+        }
+      }
+
+      if skipped.is_some() {
+        default_panic(panic_info);
+        if !libfuzzer && task.is_none() {
+          if let Some(code) = exit_code {
+            exit(code)
+          }
+          if always_abort {
+            // Make sure that SIGABRT does not produce a core file behind
+            // our back, including by means of our own handler for it.
+            let _previous = SavedAction::reset(SIGABRT);
+            if let Err((ctx, err)) = lower_core_limit() {
+              report("", &ctx, &err);
+            }
+            abort()
+          }
+        }
+        return
+      }
+
+      // With a lazily raised limit we keep the window during which core
+      // files can be created as small as possible, raising the limit only
+      // right before dumping.
+      let raise_limit = || {
+        if lazy_rlimit {
+          match raise_core_limit(max_size) {
+            Ok(limit) => Some(limit),
+            Err((ctx, err)) => {
+              report("failed to enable core dumps: ", &ctx, &err);
+              None
+            },
+          }
+        } else {
+          None
+        }
+      };
+
+      if libfuzzer || task.is_some() {
+        // In both cases we don't want to terminate the process: the
+        // fuzzer's hook is expected to abort the process (and we have to
+        // have our core created before that happens) and a panicking task
+        // is likely to get caught by the runtime.
+        let limit = raise_limit();
+        let _count = DUMPING.fetch_add(1, Ordering::SeqCst);
+        let result = dump_snapshot(dump_dir, priority, signal);
+        let _count = DUMPING.fetch_sub(1, Ordering::SeqCst);
+        match &result {
+          Ok(child) => {
+            // The core belongs to the forked copy, so its ID is the
+            // relevant one.
+            let path = (*child)
+              .try_into()
+              .ok()
+              .and_then(|child| config.predict_for(dump_dir, signal, child));
+            notify(path.as_deref())
+          },
+          Err((ctx, err)) => report("failed to dump core: ", ctx, err),
+        }
+        // As the process lives on, restore the original limit.
+        if let Some(limit) = limit {
+          if let Err((ctx, err)) = restore_core_limit(&limit) {
+            report("", &ctx, &err);
+          }
+        }
+        if !quiet || libfuzzer || result.is_err() {
+          default_panic(panic_info);
+        }
+        return
+      }
+
+      // On Android, the message ends up in the tombstone.
+      #[cfg(target_os = "android")]
+      android::set_abort_message(message, panic_info.location());
+
+      if hook_order == HookOrder::AfterExisting && !quiet {
+        default_panic(panic_info);
+      }
+      let _limit = raise_limit();
+
+      // We have no real way to bubble up the error, so we can only print
+      // it. Strictly speaking we should use the same output that the
+      // default panic handler would use, but we can't access the
+      // underlying object. So just print it to stderr.
+      let _count = DUMPING.fetch_add(1, Ordering::SeqCst);
+      let result = dump_core_and_quit(
+        dump_dir,
+        dump_dir_fd,
+        watchdog,
+        handler_policy,
+        priority,
+        signal,
+        &fallbacks,
+        || notify(core_path),
+      );
+      let _count = DUMPING.fetch_sub(1, Ordering::SeqCst);
+      if let Err((ctx, err)) = result {
+        report("failed to dump core: ", &ctx, &err);
+      }
+
+      // If we are still around, dumping core failed and we let the
+      // previous hook have its say after all.
+      if hook_order == HookOrder::BeforeExisting || quiet {
+        default_panic(panic_info);
+      }
+
+      if let Some(code) = exit_code {
+        // Same story as below, but the user asked for a plain exit.
+        exit(code)
+      }
+
+      if always_abort {
+        // Either we failed to send the signal or it did not terminate us
+        // (e.g., because it is ignored or blocked). `abort` raises
+        // SIGABRT, which by default terminates the process with a core
+        // dump as well. Note that our working directory may already be
+        // the dump directory at this point, depending on how far we got.
+        abort()
+      }
+    })
+  })
+}
+
+
+/// Register a panic handler that will cause the program to dump core.
+///
+/// Note that creating a coredump is best effort, as the process largely
+/// depends on system configuration. For example, on a Linux system the
+/// kernel needs to have coredump support and coredump must not have
+/// been prohibited (e.g., caused by a zero core file size rlimit).
+/// Furthermore, the name of the resulting core file may be generic and
+/// not reflect the program that crashed. On Linux it can be inquired
+/// via `/proc/sys/kernel/core_pattern`.
+///
+/// Use [`Builder`] for more control over the registered handler.
+///
+/// This function is idempotent: if a panic handler is registered
+/// already (by any means), it does nothing and reports success. That
+/// way, both a library and the application using it can call it
+/// safely. Use [`Builder::register`] to replace an existing
+/// registration with a differently configured one.
+///
+/// Operators can adjust the behavior at registration time, without
+/// rebuilding the program, by means of the following environment
+/// variables, which take precedence over any settings made via
+/// [`Builder`]:
+/// - `COREDUMP_DISABLE`: if set to a truthy value (see
+///   [`register_if_env`]), no panic handler is registered and success
+///   is reported
+/// - `COREDUMP_DIR`: the directory to dump core files into
+/// - `COREDUMP_MAX_SIZE`: the maximum value, in bytes, to raise the
+///   soft core file size limit to; note that the kernel truncates core
+///   files exceeding the limit
+///
+/// If the `disabled` feature is active, this function (as well as
+/// related functionality) does nothing and reports success. That way,
+/// applications can make core dumping optional without having to
+/// conditionally compile calls into this crate.
+pub fn register_panic_handler() -> Result<(), (Str, Error)> {
+  /// A lock serializing concurrent invocations, so that only one of
+  /// them registers.
+  static LOCK: Mutex<()> = Mutex::new(());
+
+  let _guard = LOCK.lock().unwrap_or_else(PoisonError::into_inner);
+  if !hook::is_registered() {
+    let _registration = Builder::new().register()?;
+  }
+  Ok(())
+}
+
+
+/// Register a panic handler that will cause the program to dump core
+/// and that guarantees that no panic ever unwinds.
+///
+/// This function is similar to [`register_panic_handler`], but it
+/// pairs registration with the semantics of
+/// [`std::panic::always_abort`][always-abort]: once the hook ran, the
+/// process will not continue, not even if dumping core via SIGQUIT did
+/// not succeed. In that case the process is aborted, which (by virtue
+/// of SIGABRT's default disposition) provides a second chance for a
+/// core file to be created.
+///
+/// Because panic hooks run before any unwinding takes place, this
+/// behavior also extends to panics in contexts where unwinding is not
+/// permitted, such as while already unwinding or when crossing an
+/// `extern "C"` boundary.
+///
+/// [always-abort]: https://doc.rust-lang.org/std/panic/fn.always_abort.html
+pub fn register_and_always_abort() -> Result<(), (Str, Error)> {
+  let _registration = Builder::new().always_abort(true).register()?;
+  Ok(())
+}
+
+
+/// Register a panic handler that will cause the program to dump core,
+/// but only for panics for which `predicate` returns `true`.
+///
+/// Please refer to [`Builder::filter`] for details.
+///
+/// ```rust,no_run
+/// // Dump core for all panics but ones from a specific source file.
+/// coredump::register_panic_handler_with(|panic_info| {
+///   panic_info
+///     .location()
+///     .map(|location| location.file() != "src/recoverable.rs")
+///     .unwrap_or(true)
+/// })
+/// .unwrap();
+/// ```
+#[allow(deprecated)]
+pub fn register_panic_handler_with<F>(predicate: F) -> Result<(), (Str, Error)>
+where
+  F: Fn(&std::panic::PanicInfo<'_>) -> bool + Send + Sync + 'static,
+{
+  let _registration = Builder::new().filter(predicate).register()?;
+  Ok(())
+}
+
+
+/// Register a panic handler that will cause the program to dump core,
+/// but only if the environment variable `name` is set to a truthy
+/// value.
+///
+/// Values considered falsy are the empty string, `0`, `false`, `no`,
+/// and `off` (compared case-insensitively). Any other value enables
+/// registration. The return value indicates whether a panic handler
+/// was registered.
+///
+/// ```rust,no_run
+/// if coredump::register_if_env("MYAPP_COREDUMP").unwrap() {
+///   eprintln!("core dumps on panic enabled");
+/// }
+/// ```
+pub fn register_if_env<S>(name: S) -> Result<bool, (Str, Error)>
+where
+  S: AsRef<OsStr>,
+{
+  let enabled = var_os(name)
+    .map(|value| env::is_truthy(&value))
+    .unwrap_or(false);
+
+  if enabled {
+    let () = register_panic_handler()?;
+  }
+  Ok(enabled)
+}
+
+
+/// Check whether the process is dumpable.
+///
+/// If the flag cannot be inquired, as may be the case in restricted
+/// environments, we optimistically assume the process to be dumpable.
+#[cfg(target_os = "linux")]
+fn check_dumpable() -> Result<(), (Str, Error)> {
+  let result = unsafe { prctl(PR_GET_DUMPABLE) };
+  if result == 0 {
+    Err(IoError::new(ErrorKind::Other, "dumpable flag is not set").into())
+      .ctx(|| "process is not dumpable")?;
+  }
+  Ok(())
+}
+
+
+/// Mark the process as dumpable.
+#[cfg(target_os = "linux")]
+fn make_dumpable() -> Result<(), (Str, Error)> {
+  check(unsafe { prctl(PR_SET_DUMPABLE, 1) }, -1).ctx(|| "failed to make process dumpable")
+}
+
+
+/// Re-apply the system configuration performed as part of the most
+/// recent panic handler registration.
+///
+/// Daemonization typically involves forking, closing file descriptors,
+/// changing the working directory to `/`, and dropping privileges. All
+/// of these can invalidate the configuration established at
+/// registration time. This function re-runs dump directory validation
+/// and, unless [`Builder::lazy_rlimit`] was used or management of the
+/// limit disabled (see [`Builder::manage_rlimit`]), raises the core
+/// file size limit once more. If [`Builder::verify_on_fork`] or
+/// [`Builder::crash_signals`] (or [`Builder::abort_signal`]) was used,
+/// the state used by the fork handler or the crash signal handlers,
+/// respectively, is refreshed. On Linux, it also checks that the
+/// process is still dumpable (after marking it as such, if
+/// [`Builder::force_dumpable`] was used), on macOS, FreeBSD, and NetBSD
+/// that core dumps are enabled and the directory core files are written
+/// to is writable.
+///
+/// When using the [`daemonize`][daemonize] crate, for example, it would
+/// be invoked right after successful daemonization:
+/// ```rust,ignore
+/// coredump::register_panic_handler().unwrap();
+///
+/// let () = daemonize::Daemonize::new().start().unwrap();
+/// coredump::reapply().unwrap();
+/// ```
+///
+/// Should any of the steps fail, the changes applied by the previous
+/// ones are rolled back where possible, leaving the configuration
+/// established earlier in effect.
+///
+/// It is an error to call this function without a prior successful
+/// registration.
+///
+/// [daemonize]: https://crates.io/crates/daemonize
+pub fn reapply() -> Result<(), (Str, Error)> {
+  if cfg!(feature = "disabled") {
+    return Ok(())
+  }
+
+  let mut settings = settings()
+    .map(|settings| Settings::clone(&settings))
+    .ok_or_else(|| {
+      Error::from(IoError::new(
+        ErrorKind::Other,
+        "no panic handler registered",
+      ))
+    })
+    .ctx(|| "failed to re-apply configuration")?;
+
+  // As for registration, a failure leaves the previous configuration
+  // in place and steps that cannot be rolled back have to come last.
+  let mut transaction = Transaction::default();
+  let dir = resolve_dir(&settings.candidates, settings.dir_mode, &mut transaction.dirs)?;
+
+  if settings.manage_rlimit && !settings.lazy_rlimit {
+    transaction.limit = Some(raise_core_limit(settings.max_size)?);
+  }
+
+  if !settings.crash_actions.is_empty() || settings.alloc_error_hook {
+    transaction.crash = Some(crash::install(
+      &dir,
+      &settings.crash_actions,
+      settings.manage_rlimit,
+      settings.max_size,
+      settings.metadata,
+      settings.watchdog,
+      settings.crash_handler_policy,
+    )?);
+  }
+
+  #[cfg(target_os = "linux")]
+  if settings.verify_on_fork {
+    transaction.fork = Some(atfork::install(&dir)?);
+  }
+
+  settings.config = CoreConfig::read(&dir, settings.signal);
+  #[cfg(any(target_os = "macos", target_os = "freebsd", target_os = "netbsd"))]
+  let () = sysctl::check_config(&settings.config)?;
+  settings.dir_fd = open_dir(&dir)?;
+
+  #[cfg(target_os = "linux")]
+  {
+    if settings.force_dumpable {
+      let () = make_dumpable()?;
+    }
+    check_dumpable()?;
+  }
+
+  let () = transaction.commit();
+  settings.dir = dir;
+  *SETTINGS.lock().unwrap_or_else(PoisonError::into_inner) = Some(Arc::new(settings));
+  Ok(())
+}
+
+
+/// Refresh the cached system configuration relevant to dumping core.
+///
+/// In order to not depend on `/proc` being accessible at crash time,
+/// the panic handler reads `/proc/sys/kernel/core_pattern` and
+/// `/proc/sys/kernel/core_uses_pid` once at registration time and
+/// predicts the path of the core file based on them. This function
+/// re-reads these values, which may be necessary if the system's
+/// configuration was changed or if the process' ID, user, or group
+/// changed (e.g., as part of daemonization). Note that [`reapply`]
+/// implies a refresh.
+///
+/// It is an error to call this function without a prior successful
+/// registration.
+pub fn refresh() -> Result<(), (Str, Error)> {
+  if cfg!(feature = "disabled") {
+    return Ok(())
+  }
+
+  let mut settings = SETTINGS.lock().unwrap_or_else(PoisonError::into_inner);
+  let settings = settings
+    .as_mut()
+    .ok_or_else(|| {
+      Error::from(IoError::new(
+        ErrorKind::Other,
+        "no panic handler registered",
+      ))
+    })
+    .ctx(|| "failed to refresh configuration")?;
+
+  let config = CoreConfig::read(&settings.dir, settings.signal);
+  Arc::make_mut(settings).config = config;
+  Ok(())
+}
+
+
+/// Unregister the panic handler installed by the most recent
+/// registration.
+///
+/// Registrations are stacked: unregistering reinstates the panic hook
+/// that was in place before the most recent registration along with
+/// the settings of the registration before it, if any. That makes
+/// repeated register/unregister cycles, as they commonly happen in
+/// test suites or plugin hosts, work as expected.
+///
+/// If another party installed a panic hook on top of ours in the
+/// meantime, that hook is left in place and ours is merely deactivated
+/// (it forwards to the hook it replaced), because it cannot be removed
+/// from the middle of the chain. In this case `false` is returned,
+/// otherwise `true`.
+///
+/// If the registration raised the soft core file size limit, the limit
+/// in effect before it is restored as well (to the extent permitted by
+/// the current hard limit). Handlers for crash signals (see
+/// [`Builder::crash_signals`]) are uninstalled, unless the registration
+/// before it asked for them as well. Other changes performed as part of
+/// registration, such as the creation of directories, are not
+/// reverted. It is an error to call this function without a prior
+/// successful registration.
+pub fn unregister() -> Result<bool, (Str, Error)> {
+  if cfg!(feature = "disabled") {
+    return Ok(true)
+  }
+
+  let hook::Removed {
+    restored,
+    settings,
+    limit,
+  } = hook::remove()
+    .ok_or_else(|| {
+      Error::from(IoError::new(
+        ErrorKind::Other,
+        "no panic handler registered",
+      ))
+    })
+    .ctx(|| "failed to unregister panic handler")?;
+
+  // Crash signal handlers are shared by all registrations, so bring
+  // them in line with the one that is in effect now.
+  match &settings {
+    Some(settings) if !settings.crash_actions.is_empty() || settings.alloc_error_hook => {
+      let _undo = crash::install(
+        &settings.dir,
+        &settings.crash_actions,
+        settings.manage_rlimit,
+        settings.max_size,
+        settings.metadata,
+        settings.watchdog,
+        settings.crash_handler_policy,
+      )?;
+    },
+    _ => crash::uninstall()?,
+  }
+  #[cfg(feature = "alloc-error-hook")]
+  crash::set_alloc_error_hook(
+    settings
+      .as_ref()
+      .map(|settings| settings.alloc_error_hook)
+      .unwrap_or(false),
+  );
+  *SETTINGS.lock().unwrap_or_else(PoisonError::into_inner) = settings;
+
+  if let Some(previous) = limit {
+    // The hard limit may have been lowered in the meantime, in which
+    // case we can't go back to a higher soft limit.
+    let mut limit = query_core_limit()?;
+    limit.rlim_cur = previous.rlim_cur.min(limit.rlim_max);
+    let () = restore_core_limit(&limit)?;
+  }
+  Ok(restored)
+}
+
+
+/// Reinstate the panic handler of the most recent registration, should
+/// another party have replaced it.
+///
+/// Frameworks and libraries installing a panic hook of their own via
+/// [`std::panic::set_hook`] after our registration silently drop the
+/// panic handler. This function installs it again, on top of the hook
+/// that replaced it. The latter is invoked in place of the hook that
+/// was in effect before registration (see [`Builder::hook_order`] and
+/// [`Builder::quiet`]). Should it forward to the hook it replaced
+/// itself (e.g., because it got installed via
+/// `std::panic::update_hook`), that forwarding is cut short at our
+/// panic handler, which is not invoked twice.
+///
+/// `true` is returned if the panic handler had to be reinstated,
+/// `false` if it was still installed. It is an error to call this
+/// function without a prior successful registration.
+pub fn reassert_hook() -> Result<bool, (Str, Error)> {
+  if cfg!(feature = "disabled") {
+    return Ok(false)
+  }
+
+  hook::reassert()
+    .ok_or_else(|| {
+      Error::from(IoError::new(
+        ErrorKind::Other,
+        "no panic handler registered",
+      ))
+    })
+    .ctx(|| "failed to reassert panic hook")
+}
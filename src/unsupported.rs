@@ -0,0 +1,79 @@
+// unsupported.rs
+
+// Copyright (C) 2026 Daniel Mueller <deso@posteo.net>
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+//! Stand-ins for the registration functionality on platforms that do
+//! not support dumping core, as provided by the `best-effort` feature.
+//!
+//! Each function fails with [`Error::Unsupported`] without having any
+//! effect, so that callers can decide whether to treat the lack of
+//! support as an error or to ignore it.
+
+use std::ffi::OsStr;
+
+use crate::Error;
+use crate::Str;
+use crate::WithCtx;
+
+
+/// Report that registration is not supported.
+fn unsupported<T>() -> Result<T, (Str, Error)> {
+  Err(Error::Unsupported).ctx(|| "failed to register panic handler")
+}
+
+
+/// Register a panic handler that will cause the program to dump core.
+///
+/// Dumping core is not supported on this platform and so this function
+/// always fails with [`Error::Unsupported`].
+pub fn register_panic_handler() -> Result<(), (Str, Error)> {
+  unsupported()
+}
+
+
+/// Register a panic handler that will cause the program to dump core
+/// and that guarantees that no panic ever unwinds.
+///
+/// Dumping core is not supported on this platform and so this function
+/// always fails with [`Error::Unsupported`].
+pub fn register_and_always_abort() -> Result<(), (Str, Error)> {
+  unsupported()
+}
+
+
+/// Register a panic handler that will cause the program to dump core,
+/// but only for panics for which `predicate` returns `true`.
+///
+/// Dumping core is not supported on this platform and so this function
+/// always fails with [`Error::Unsupported`].
+#[allow(deprecated)]
+pub fn register_panic_handler_with<F>(_predicate: F) -> Result<(), (Str, Error)>
+where
+  F: Fn(&std::panic::PanicInfo<'_>) -> bool + Send + Sync + 'static,
+{
+  unsupported()
+}
+
+
+/// Register a panic handler that will cause the program to dump core,
+/// but only if the environment variable `name` is set to a truthy
+/// value.
+///
+/// Dumping core is not supported on this platform and so this function
+/// always fails with [`Error::Unsupported`].
+pub fn register_if_env<S>(_name: S) -> Result<bool, (Str, Error)>
+where
+  S: AsRef<OsStr>,
+{
+  unsupported()
+}
+
+
+/// Unregister the panic handler registered most recently.
+///
+/// As no panic handler can be registered on this platform, there is
+/// never one to unregister and `false` is reported.
+pub fn unregister() -> Result<bool, (Str, Error)> {
+  Ok(false)
+}
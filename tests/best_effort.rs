@@ -0,0 +1,19 @@
+// best_effort.rs
+
+// Copyright (C) 2026 Daniel Mueller <deso@posteo.net>
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+#![cfg(all(not(unix), feature = "best-effort"))]
+
+use coredump::register_panic_handler;
+use coredump::unregister;
+use coredump::Error;
+
+
+/// Check that registration fails gracefully on unsupported platforms.
+#[test]
+fn register_unsupported() {
+  let (_, err) = register_panic_handler().unwrap_err();
+  assert!(matches!(err, Error::Unsupported), "{}", err);
+  assert!(!unregister().unwrap());
+}
@@ -3,73 +3,486 @@
 // Copyright (C) 2019 Daniel Mueller <deso@posteo.net>
 // SPDX-License-Identifier: GPL-3.0-or-later
 
-// Note that this file should only contain a single test. The test in
-// here invokes itself and having multiple test running in parallel
-// while that is happening is probably a bad idea.
+//! Tests for registering and unregistering the panic handler.
 
-use std::env::current_exe;
+mod util;
+
+use std::env::remove_var;
+use std::env::set_var;
 use std::env::temp_dir;
 use std::env::var_os;
-use std::fs::read_to_string;
-use std::fs::remove_file;
-use std::io::ErrorKind;
+use std::fs::create_dir_all;
+use std::fs::remove_dir_all;
+use std::io::stderr;
+use std::io::Write as _;
+use std::os::unix::fs::PermissionsExt;
 use std::os::unix::process::ExitStatusExt;
-use std::process::Command;
+use std::panic::catch_unwind;
+use std::panic::set_hook;
+use std::panic::take_hook;
+use std::path::PathBuf;
+use std::process::id as pid;
+use std::sync::atomic::AtomicBool;
+use std::sync::atomic::Ordering;
 
+use libc::getrlimit;
+use libc::rlimit;
+use libc::setrlimit;
+use libc::RLIMIT_CORE;
 use libc::SIGQUIT;
 
+use coredump::reassert_hook;
+use coredump::register_if_env;
 use coredump::register_panic_handler;
+use coredump::unregister;
+use coredump::Builder;
+
+const FOREIGN_MARKER: &str = "FOREIGN HOOK";
+
+
+#[cfg(not(any(
+  target_os = "linux",
+  target_os = "android",
+  target_os = "macos",
+  target_os = "freebsd",
+  target_os = "netbsd",
+  target_os = "openbsd",
+  target_os = "illumos",
+  target_os = "solaris"
+)))]
+compile_error!(
+  "only Linux, Android, macOS, the BSDs, illumos, and Solaris are supported currently"
+);
+
 
-const CHILD_MARKER: &str = "PANICING_CHILD";
+fn core_limit() -> rlimit {
+  let mut limit = rlimit {
+    rlim_cur: 0,
+    rlim_max: 0,
+  };
+  assert_eq!(unsafe { getrlimit(RLIMIT_CORE, &mut limit) }, 0);
+  limit
+}
 
 
-#[cfg(not(target_os = "linux"))]
-compile_error!("only Linux is supported currently");
+/// Retrieve the address of the currently installed panic hook.
+fn current_hook() -> usize {
+  let hook = take_hook();
+  let address = &*hook as *const _ as *const () as usize;
+  let () = set_hook(hook);
+  address
+}
 
 
 #[test]
 #[cfg(target_os = "linux")]
 fn dump_core() {
-  if var_os(CHILD_MARKER).is_none() {
-    // Note that the core pattern presumably could be many things and
-    // half of them would make our test not succeed. We just use it to
-    // do something slightly more clever than hardcoding "core", but
-    // ultimately are willing to only put so much effort into this
-    // test...
-    let tmp_dir = temp_dir();
-    let core_pattern = read_to_string("/proc/sys/kernel/core_pattern").unwrap();
-    // A core file pattern beginning with a pipe symbol means that the
-    // core dump is actually redirected to a program. That's a system
-    // configuration we probably cannot and most likely do not want to
-    // change, so if the system is configured this way (systemd enabled
-    // systems likely are), this test cannot run.
-    if core_pattern.starts_with('|') {
+  if let Some(_guard) = util::parent() {
+    let core_file = match util::core_file() {
+      Some(core_file) => core_file,
+      None => return,
+    };
+
+    let rc = util::child("dump_core").status().unwrap();
+
+    assert!(!rc.success());
+    assert_eq!(rc.signal().unwrap(), SIGQUIT);
+    let () = util::assert_core(&core_file);
+  } else {
+    register_panic_handler().unwrap();
+    panic!("induced panic");
+  }
+}
+
+
+/// Check that registration does not touch the system when the
+/// `disabled` feature is active.
+#[test]
+#[cfg(feature = "disabled")]
+fn registration_is_noop() {
+  use coredump::reapply;
+
+  if util::isolate("registration_is_noop") {
+    return
+  }
+
+  let mut limit = core_limit();
+  limit.rlim_cur = 0;
+  assert_eq!(unsafe { setrlimit(RLIMIT_CORE, &limit) }, 0);
+
+  let () = register_panic_handler().unwrap();
+  let () = reapply().unwrap();
+  assert_eq!(core_limit().rlim_cur, 0);
+}
+
+
+/// Check that repeated calls of `register_panic_handler` register only
+/// once.
+#[test]
+fn register_repeatedly() {
+  if util::isolate("register_repeatedly") {
+    return
+  }
+
+  if core_limit().rlim_max == 0 {
+    return
+  }
+
+  let () = register_panic_handler().unwrap();
+  let hook = current_hook();
+  let () = register_panic_handler().unwrap();
+  assert_eq!(current_hook(), hook);
+
+  assert!(unregister().unwrap());
+  assert!(unregister().is_err());
+}
+
+
+/// Check that registration happens only if the environment variable is
+/// set to a truthy value.
+#[test]
+fn register_conditionally() {
+  const VAR: &str = "COREDUMP_TEST_REGISTER_IF_ENV";
+
+  if util::isolate("register_conditionally") {
+    return
+  }
+
+  if core_limit().rlim_max == 0 {
+    return
+  }
+
+  remove_var(VAR);
+  assert!(!register_if_env(VAR).unwrap());
+
+  for value in ["", "0", "false", "No", " OFF "] {
+    set_var(VAR, value);
+    assert!(!register_if_env(VAR).unwrap(), "{:?}", value);
+  }
+
+  set_var(VAR, "1");
+  assert!(register_if_env(VAR).unwrap());
+}
+
+
+/// Check that environment variables override the configuration used
+/// for registration.
+#[test]
+fn env_overrides() {
+  if util::isolate("env_overrides") {
+    return
+  }
+
+  let mut limit = core_limit();
+  if limit.rlim_max == 0 {
+    return
+  }
+
+  limit.rlim_cur = 0;
+  assert_eq!(unsafe { setrlimit(RLIMIT_CORE, &limit) }, 0);
+
+  set_var("COREDUMP_DISABLE", "1");
+  let _registration = Builder::new().register().unwrap();
+  assert_eq!(core_limit().rlim_cur, 0);
+  remove_var("COREDUMP_DISABLE");
+
+  set_var("COREDUMP_MAX_SIZE", "lots");
+  let (ctx, _err) = Builder::new().register().unwrap_err();
+  assert!(ctx.contains("COREDUMP_MAX_SIZE"), "{}", ctx);
+  assert_eq!(core_limit().rlim_cur, 0);
+
+  let dir = temp_dir();
+  set_var("COREDUMP_MAX_SIZE", "4096");
+  set_var("COREDUMP_DIR", &dir);
+  let registration = Builder::new()
+    .dir(Some("/nonexistent".into()))
+    .register()
+    .unwrap();
+  assert_eq!(registration.dump_dir(), dir);
+  assert_eq!(core_limit().rlim_cur, limit.rlim_max.min(4096));
+}
+
+
+/// Check that dropping a guard restores the previous panic hook and
+/// core file size limit.
+#[test]
+fn scoped_registration() {
+  if util::isolate("scoped_registration") {
+    return
+  }
+
+  let mut limit = core_limit();
+  if limit.rlim_max == 0 {
+    return
+  }
+
+  limit.rlim_cur = 0;
+  assert_eq!(unsafe { setrlimit(RLIMIT_CORE, &limit) }, 0);
+
+  let value = 42;
+  let () = set_hook(Box::new(move |_| assert_eq!(value, 42)));
+  let original = current_hook();
+
+  {
+    let guard = Builder::new().register_scoped().unwrap();
+    assert_eq!(guard.registration().previous_limit().soft, Some(0));
+    assert_eq!(core_limit().rlim_cur, limit.rlim_max);
+    assert_ne!(current_hook(), original);
+  }
+
+  assert_eq!(current_hook(), original);
+  assert_eq!(core_limit().rlim_cur, 0);
+}
+
+
+/// Check that repeated registration and unregistration restore the
+/// right panic hooks, even with foreign hooks installed in between.
+#[test]
+fn register_unregister() {
+  static CALLED: AtomicBool = AtomicBool::new(false);
+
+  if util::isolate("register_unregister") {
+    return
+  }
+
+  if core_limit().rlim_max == 0 {
+    return
+  }
+
+  assert!(unregister().is_err());
+
+  // Capture something so that the hook is not zero-sized and has a
+  // unique address.
+  let value = true;
+  let () = set_hook(Box::new(move |_| CALLED.store(value, Ordering::SeqCst)));
+  let original = current_hook();
+
+  let _registration = Builder::new().register().unwrap();
+  assert_ne!(current_hook(), original);
+  assert!(unregister().unwrap());
+  assert_eq!(current_hook(), original);
+
+  let _registration = Builder::new().register().unwrap();
+  let previous = take_hook();
+  let () = set_hook(Box::new(move |panic_info| previous(panic_info)));
+  let foreign = current_hook();
+
+  let _registration = Builder::new().register().unwrap();
+  assert!(unregister().unwrap());
+  assert_eq!(current_hook(), foreign);
+  // Our first hook is wrapped by the foreign one and can only be
+  // deactivated.
+  assert!(!unregister().unwrap());
+  assert_eq!(current_hook(), foreign);
+  assert!(unregister().is_err());
+
+  // With our hook deactivated, the panic should make it to the original
+  // hook without dumping core.
+  let _ = catch_unwind(|| panic!("induced panic"));
+  assert!(CALLED.load(Ordering::SeqCst));
+}
+
+
+/// Check that the panic handler dumps core again once reasserted after
+/// a foreign hook replaced it, and that the foreign hook is still run.
+#[test]
+fn reassert_after_foreign_hook() {
+  if let Some(_guard) = util::parent() {
+    let output = util::child("reassert_after_foreign_hook").output().unwrap();
+
+    assert_eq!(output.status.signal(), Some(SIGQUIT));
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert_eq!(stderr.matches(FOREIGN_MARKER).count(), 1, "{}", stderr);
+    let () = util::remove_core();
+  } else {
+    let () = register_panic_handler().unwrap();
+    assert!(!reassert_hook().unwrap());
+
+    // Install a hook that forwards to the one it replaced, similar to
+    // what `std::panic::update_hook` does.
+    let previous = take_hook();
+    let () = set_hook(Box::new(move |panic_info| {
+      let _ = writeln!(stderr(), "{}", FOREIGN_MARKER);
+      previous(panic_info)
+    }));
+
+    assert!(reassert_hook().unwrap());
+    assert!(!reassert_hook().unwrap());
+    panic!("induced panic");
+  }
+}
+
+
+/// Check that a failed registration does not leave the core file size
+/// limit adjusted or the panic hook replaced.
+#[test]
+fn rollback_on_failure() {
+  if let Some(_guard) = util::parent() {
+    if core_limit().rlim_max == 0 {
       return
     }
-    let core_file = tmp_dir.join(core_pattern.trim_end());
 
-    match remove_file(&core_file) {
-      Ok(()) => (),
-      Err(ref err) if err.kind() == ErrorKind::NotFound => (),
-      Err(err) => panic!("unexpected error: {}", err),
-    };
+    let rc = util::child("rollback_on_failure")
+      // Point the dump directory to something that does not exist in
+      // order to make registration fail.
+      .env("TMPDIR", "/proc/self/does-not-exist")
+      .status()
+      .unwrap();
+
+    assert!(rc.success());
+  } else {
+    let mut limit = core_limit();
+    limit.rlim_cur = 0;
+    assert_eq!(unsafe { setrlimit(RLIMIT_CORE, &limit) }, 0);
+
+    // Capture something so that the hook is not zero-sized and has a
+    // unique address.
+    let value = 42;
+    let () = set_hook(Box::new(move |_| assert_eq!(value, 42)));
+    let hook = current_hook();
+
+    let _err = register_panic_handler().unwrap_err();
+    assert_eq!(core_limit().rlim_cur, 0);
+    assert_eq!(current_hook(), hook);
+  }
+}
+
+
+/// Check that the core file path is predicted at registration time and
+/// that the cached configuration can be refreshed.
+#[test]
+#[cfg(target_os = "linux")]
+fn predict_and_refresh() {
+  use std::fs::read_to_string;
+
+  use coredump::refresh;
+
+  if util::isolate("predict_and_refresh") {
+    return
+  }
+
+  if core_limit().rlim_max == 0 {
+    return
+  }
+
+  assert!(refresh().is_err());
+
+  let dir = temp_dir();
+  let registration = Builder::new().dir(Some(dir.clone())).register().unwrap();
 
-    let rc = Command::new(current_exe().unwrap())
-      .env_clear()
-      .env(CHILD_MARKER, "true")
+  let pattern = read_to_string("/proc/sys/kernel/core_pattern").unwrap();
+  let pattern = pattern.trim_end();
+  let uses_pid = read_to_string("/proc/sys/kernel/core_uses_pid").unwrap();
+
+  if pattern.starts_with('|') {
+    assert_eq!(registration.core_path(), None);
+  } else if !pattern.contains('%') {
+    let mut expected = dir.join(pattern);
+    if uses_pid.trim() != "0" {
+      expected = dir.join(format!("{}.{}", pattern, pid()));
+    }
+    assert_eq!(registration.core_path(), Some(expected.as_path()));
+  } else if pattern.contains("%t") {
+    assert_eq!(registration.core_path(), None);
+  }
+
+  let () = refresh().unwrap();
+}
+
+
+/// Check that a missing dump directory is created as part of
+/// registration.
+#[test]
+fn create_missing_dir() {
+  if util::isolate("create_missing_dir") {
+    return
+  }
+
+  if core_limit().rlim_max == 0 {
+    return
+  }
+
+  let outer = temp_dir().join(format!("coredump-create-dir-{}", pid()));
+  let inner = outer.join("nested");
+  let _ = remove_dir_all(&outer);
+
+  let registration = Builder::new()
+    .dir(Some(inner.clone()))
+    .create_dir(Some(0o700))
+    .register()
+    .unwrap();
+
+  assert_eq!(registration.dump_dir(), inner);
+  assert_eq!(registration.created_dirs(), [outer.clone(), inner.clone()]);
+
+  let mode = inner.metadata().unwrap().permissions().mode();
+  assert_eq!(mode & 0o777, 0o700);
+
+  let () = remove_dir_all(&outer).unwrap();
+}
+
+
+/// Check that the first usable candidate directory is picked.
+#[test]
+fn pick_first_usable_dir() {
+  if util::isolate("pick_first_usable_dir") {
+    return
+  }
+
+  if core_limit().rlim_max == 0 {
+    return
+  }
+
+  let candidates = vec![
+    PathBuf::from("/proc/self/does-not-exist"),
+    // A file, not a directory.
+    PathBuf::from("/proc/self/status"),
+    temp_dir(),
+  ];
+  let registration = Builder::new()
+    .dirs(candidates)
+    .create_dir(Some(0o700))
+    .register()
+    .unwrap();
+
+  assert_eq!(registration.dump_dir(), temp_dir());
+  assert_eq!(registration.created_dirs(), &[] as &[PathBuf]);
+}
+
+
+/// Check that core is dumped into the directory determined at
+/// registration time, even if it got renamed since.
+#[test]
+fn dump_into_renamed_dir() {
+  use std::fs::read_to_string;
+  use std::fs::rename;
+
+  const DIR_VAR: &str = "DUMP_DIR";
+
+  if let Some(_guard) = util::parent() {
+    let core_pattern = read_to_string("/proc/sys/kernel/core_pattern").unwrap();
+    // See `util::core_file` for why we bail out here. An absolute
+    // pattern does not depend on the working directory.
+    if core_pattern.starts_with('|') || core_pattern.starts_with('/') {
+      return
+    }
+
+    let dir = temp_dir().join(format!("coredump-renamed-dir-{}", pid()));
+    let () = create_dir_all(&dir).unwrap();
+    let rc = util::child("dump_into_renamed_dir")
+      .env(DIR_VAR, &dir)
       .status()
       .unwrap();
 
-    assert!(!rc.success());
-    assert_eq!(rc.signal().unwrap(), SIGQUIT);
-    assert!(
-      core_file.exists(),
-      "core file {} does not exist",
-      core_file.display(),
-    );
-    let _ = remove_file(&core_file);
+    assert_eq!(rc.signal(), Some(SIGQUIT));
+    assert!(rc.core_dumped());
+    let renamed = dir.with_extension("renamed");
+    let () = util::assert_core(&renamed.join(core_pattern.trim_end()));
+    let _ = remove_dir_all(&renamed);
   } else {
-    register_panic_handler().unwrap();
+    let dir = PathBuf::from(var_os(DIR_VAR).unwrap());
+    let _registration = Builder::new().dir(Some(dir.clone())).register().unwrap();
+    let () = rename(&dir, dir.with_extension("renamed")).unwrap();
     panic!("induced panic");
   }
 }
@@ -0,0 +1,573 @@
+// crash.rs
+
+// Copyright (C) 2026 Daniel Mueller <deso@posteo.net>
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+//! Tests for dumping core on crash signals and other fatal conditions
+//! not reported as panics.
+
+mod util;
+
+use std::mem::zeroed;
+use std::os::raw::c_int;
+use std::os::raw::c_void;
+use std::os::unix::process::ExitStatusExt;
+use std::process::abort;
+use std::ptr::null;
+use std::ptr::null_mut;
+use std::sync::atomic::AtomicUsize;
+use std::sync::atomic::Ordering;
+
+use libc::raise;
+use libc::sigaction;
+use libc::sighandler_t;
+use libc::siginfo_t;
+use libc::signal;
+use libc::SIGABRT;
+use libc::SIGFPE;
+use libc::SIGUSR1;
+
+use coredump::signal::SavedAction;
+use coredump::Builder;
+use coredump::CrashAction;
+
+/// The number of times the pre-existing crash signal handler got
+/// invoked.
+static INVOCATIONS: AtomicUsize = AtomicUsize::new(0);
+static FOREIGN: AtomicUsize = AtomicUsize::new(0);
+static OURS: AtomicUsize = AtomicUsize::new(0);
+static mut PREVIOUS: Option<SavedAction> = None;
+/// Whether SIGUSR2 was blocked while the chained handler ran.
+static BLOCKED_IN_CHAINED: AtomicUsize = AtomicUsize::new(0);
+/// Whether SIGUSR2 was blocked after chaining returned.
+static BLOCKED_AFTER_CHAIN: AtomicUsize = AtomicUsize::new(0);
+static mut MASKED: Option<SavedAction> = None;
+
+
+/// Retrieve the handler currently installed for `signal`.
+fn handler(signal: c_int) -> sighandler_t {
+  let mut action = unsafe { zeroed::<sigaction>() };
+  assert_eq!(unsafe { sigaction(signal, null(), &mut action) }, 0);
+  action.sa_sigaction
+}
+
+/// Disable core dumps for the time being. With a lazily raised limit,
+/// the handler has to raise it for a core to be created.
+#[cfg(target_os = "linux")]
+fn disable_core_dumps() {
+  use libc::getrlimit;
+  use libc::rlimit;
+  use libc::setrlimit;
+  use libc::RLIMIT_CORE;
+
+  let mut limit = rlimit {
+    rlim_cur: 0,
+    rlim_max: 0,
+  };
+  assert_eq!(unsafe { getrlimit(RLIMIT_CORE, &mut limit) }, 0);
+  limit.rlim_cur = 0;
+  assert_eq!(unsafe { setrlimit(RLIMIT_CORE, &limit) }, 0);
+}
+
+
+/// Check that core is dumped for crash signals other than the one
+/// used for panics, if requested.
+#[test]
+#[cfg(target_os = "linux")]
+fn dump_core_for_crash_signal() {
+  use libc::SIGSEGV;
+
+  if let Some(_guard) = util::parent() {
+    let core_file = match util::core_file() {
+      Some(core_file) => core_file,
+      None => return,
+    };
+
+    let rc = util::child("dump_core_for_crash_signal").status().unwrap();
+
+    assert_eq!(rc.signal(), Some(SIGSEGV));
+    assert!(rc.core_dumped());
+    let () = util::assert_core(&core_file);
+  } else {
+    let () = disable_core_dumps();
+    let _registration = Builder::new()
+      .lazy_rlimit(true)
+      .crash_signals(true)
+      .register()
+      .unwrap();
+    let _rc = unsafe { raise(SIGSEGV) };
+    unreachable!();
+  }
+}
+
+
+/// Check that core is dumped for SIGABRT, if requested, without other
+/// crash signals being handled.
+#[test]
+#[cfg(target_os = "linux")]
+fn dump_core_on_abort() {
+  use libc::SIGSEGV;
+
+  if let Some(_guard) = util::parent() {
+    let core_file = match util::core_file() {
+      Some(core_file) => core_file,
+      None => return,
+    };
+
+    let rc = util::child("dump_core_on_abort").status().unwrap();
+
+    assert_eq!(rc.signal(), Some(SIGABRT));
+    assert!(rc.core_dumped());
+    let () = util::assert_core(&core_file);
+  } else {
+    let () = disable_core_dumps();
+    let before = handler(SIGSEGV);
+    let _registration = Builder::new()
+      .lazy_rlimit(true)
+      .abort_signal(true)
+      .register()
+      .unwrap();
+
+    // The Rust runtime installs a handler for SIGSEGV, which has to be
+    // left in place.
+    let after = handler(SIGSEGV);
+    assert_eq!(after, before);
+
+    abort()
+  }
+}
+
+
+/// A type whose `Display` implementation panics.
+#[cfg(target_os = "linux")]
+struct Panicking;
+
+#[cfg(target_os = "linux")]
+impl std::fmt::Display for Panicking {
+  fn fmt(&self, _f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    panic!("panic while formatting")
+  }
+}
+
+
+/// Check that a panic while formatting the panic message, which makes
+/// the Rust runtime abort the process before any panic hook runs,
+/// still results in a core dump, if requested.
+#[test]
+#[cfg(target_os = "linux")]
+fn dump_core_for_panicking_display() {
+  if let Some(_guard) = util::parent() {
+    let core_file = match util::core_file() {
+      Some(core_file) => core_file,
+      None => return,
+    };
+
+    let rc = util::child("dump_core_for_panicking_display")
+      .status()
+      .unwrap();
+
+    assert_eq!(rc.signal(), Some(SIGABRT));
+    assert!(rc.core_dumped());
+    let () = util::assert_core(&core_file);
+  } else {
+    let () = disable_core_dumps();
+    let _registration = Builder::new()
+      .lazy_rlimit(true)
+      .abort_signal(true)
+      .register()
+      .unwrap();
+    panic!("{}", Panicking)
+  }
+}
+
+
+/// Check that the watchdog armed for crash signals does not keep the
+/// output of the process open, delaying readers until it expires.
+#[test]
+#[cfg(target_os = "linux")]
+fn crash_watchdog_releases_output() {
+  use std::time::Duration;
+  use std::time::Instant;
+
+  use libc::SIGSEGV;
+
+  if let Some(_guard) = util::parent() {
+    let start = Instant::now();
+    let output = util::child("crash_watchdog_releases_output")
+      .output()
+      .unwrap();
+    let elapsed = start.elapsed();
+
+    assert_eq!(output.status.signal(), Some(SIGSEGV));
+    assert!(elapsed < Duration::from_secs(5), "{:?}", elapsed);
+    let () = util::remove_core();
+  } else {
+    let _registration = Builder::new()
+      .crash_signals(true)
+      .watchdog(Some(Duration::from_secs(10)))
+      .register()
+      .unwrap();
+    let _rc = unsafe { raise(SIGSEGV) };
+    unreachable!();
+  }
+}
+
+
+/// Check that actions configured for individual crash signals take
+/// precedence over the ones established for all of them.
+#[test]
+fn per_signal_actions() {
+  if let Some(_guard) = util::parent() {
+    let rc = util::child("per_signal_actions").status().unwrap();
+
+    assert_eq!(rc.signal(), Some(SIGABRT));
+    assert!(!rc.core_dumped());
+  } else {
+    let before = handler(SIGFPE);
+    let _registration = Builder::new()
+      .crash_signals(true)
+      .crash_signal(SIGABRT, CrashAction::NoDump)
+      .crash_signal(SIGFPE, CrashAction::ChainOnly)
+      .register()
+      .unwrap();
+
+    let after = handler(SIGFPE);
+    assert_eq!(after, before);
+
+    abort()
+  }
+}
+
+
+/// A handler taking care of the signal, as a runtime relying on it for
+/// its operation would.
+extern "C" fn handle_signal(_signal: c_int) {
+  let _count = INVOCATIONS.fetch_add(1, Ordering::Relaxed);
+}
+
+
+/// Check that pre-existing handlers for crash signals are chained to
+/// as configured.
+#[test]
+#[cfg(target_os = "linux")]
+fn chain_to_previous_handler() {
+  use std::env::var_os;
+  use std::process::exit;
+
+  use coredump::HandlerPolicy;
+
+  use util::CHILD_MARKER;
+
+  if let Some(_guard) = util::parent() {
+    let run = |policy| {
+      util::child("chain_to_previous_handler")
+        .env(CHILD_MARKER, policy)
+        .status()
+        .unwrap()
+    };
+
+    // The pre-existing handler takes care of the signal and the
+    // process lives on.
+    assert_eq!(run("theirs").code(), Some(42));
+    assert_eq!(run("skip").code(), Some(42));
+    // We dump core without consulting it.
+    let status = run("ours");
+    assert_eq!(status.signal(), Some(SIGFPE));
+    assert!(status.core_dumped());
+    let () = util::remove_core();
+  } else {
+    let policy = match var_os(CHILD_MARKER).unwrap().to_str().unwrap() {
+      "theirs" => HandlerPolicy::TheirsFirst,
+      "ours" => HandlerPolicy::OursFirst,
+      "skip" => HandlerPolicy::Skip,
+      policy => panic!("unexpected policy: {}", policy),
+    };
+
+    let handler = handle_signal as extern "C" fn(c_int) as sighandler_t;
+    let _previous = unsafe { signal(SIGFPE, handler) };
+    let _registration = Builder::new()
+      .crash_signals(true)
+      .crash_handler_policy(policy)
+      .register()
+      .unwrap();
+
+    let _rc = unsafe { raise(SIGFPE) };
+    assert_eq!(INVOCATIONS.load(Ordering::Relaxed), 1);
+    exit(42)
+  }
+}
+
+
+/// Recurse until the stack overflows.
+#[cfg(target_os = "linux")]
+fn recurse(depth: u64) -> u64 {
+  use std::ptr::read_volatile;
+
+  let buffer = [depth; 128];
+  // Call through an opaque function pointer and use the buffer after
+  // the call, so that the compiler can neither turn the recursion into
+  // a loop nor elide the buffer.
+  let next = unsafe { read_volatile(&(recurse as fn(u64) -> u64)) };
+  next(depth + 1) + unsafe { read_volatile(&buffer[(depth % 128) as usize]) }
+}
+
+
+/// Check that core is dumped for a stack overflow on a thread with an
+/// alternate signal stack set up by us and that the crash is
+/// classified as such.
+#[test]
+#[cfg(target_os = "linux")]
+fn dump_core_on_stack_overflow() {
+  use std::env::temp_dir;
+  use std::fs::read_dir;
+  use std::fs::read_to_string;
+  use std::fs::remove_file;
+  use std::ptr::null_mut;
+  use std::thread::Builder as ThreadBuilder;
+
+  use libc::sigaltstack;
+  use libc::stack_t;
+  use libc::SIGSEGV;
+  use libc::SS_DISABLE;
+
+  use coredump::thread::install_altstack;
+  use coredump::HandlerPolicy;
+
+  if let Some(_guard) = util::parent() {
+    let core_file = match util::core_file() {
+      Some(core_file) => core_file,
+      None => return,
+    };
+
+    let mut child = util::child("dump_core_on_stack_overflow").spawn().unwrap();
+    let rc = child.wait().unwrap();
+
+    assert_eq!(rc.signal(), Some(SIGSEGV));
+    assert!(rc.core_dumped());
+    let () = util::assert_core(&core_file);
+
+    let prefix = format!("coredump-{}-", child.id());
+    let metadata = read_dir(temp_dir())
+      .unwrap()
+      .map(|entry| entry.unwrap().path())
+      .find(|path| {
+        path
+          .file_name()
+          .and_then(|name| name.to_str())
+          .map(|name| name.starts_with(&prefix) && name.ends_with(".txt"))
+          .unwrap_or(false)
+      })
+      .expect("no metadata file found");
+    let content = read_to_string(&metadata).unwrap();
+    let _ = remove_file(&metadata);
+    assert!(content.contains("signal: 11\n"), "{}", content);
+    assert!(content.contains("crash-kind: stack-overflow\n"), "{}", content);
+    if cfg!(any(
+      target_arch = "x86_64",
+      target_arch = "aarch64",
+      target_arch = "riscv64"
+    )) {
+      assert!(content.contains("instruction-pointer: 0x"), "{}", content);
+      assert!(content.contains("stack-pointer: 0x"), "{}", content);
+    }
+  } else {
+    let () = disable_core_dumps();
+    let _registration = Builder::new()
+      .lazy_rlimit(true)
+      .metadata(true)
+      .crash_signals(true)
+      // The standard library's handler would report the overflow and
+      // abort, raising SIGABRT.
+      .crash_handler_policy(HandlerPolicy::OursFirst)
+      .register()
+      .unwrap();
+
+    let thread = ThreadBuilder::new()
+      .stack_size(256 * 1024)
+      .spawn(|| {
+        // Threads spawned by the standard library may have an
+        // alternate signal stack already. Get rid of it, so that ours
+        // is used.
+        let mut stack = unsafe { zeroed::<stack_t>() };
+        stack.ss_flags = SS_DISABLE;
+        let rc = unsafe { sigaltstack(&stack, null_mut()) };
+        assert_eq!(rc, 0);
+
+        let () = install_altstack().unwrap();
+        recurse(0)
+      })
+      .unwrap();
+    let _result = thread.join();
+    unreachable!();
+  }
+}
+
+
+/// Check that core is dumped when allocating memory failed, if
+/// requested.
+#[test]
+#[cfg(all(target_os = "linux", feature = "alloc-error-hook"))]
+fn dump_core_on_alloc_error() {
+  use std::alloc::handle_alloc_error;
+  use std::alloc::Layout;
+
+  if let Some(_guard) = util::parent() {
+    let core_file = match util::core_file() {
+      Some(core_file) => core_file,
+      None => return,
+    };
+
+    let output = util::child("dump_core_on_alloc_error").output().unwrap();
+
+    let rc = output.status;
+    assert_eq!(rc.signal(), Some(SIGABRT));
+    assert!(rc.core_dumped());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+      stderr.contains("memory allocation of 8 bytes failed"),
+      "{}",
+      stderr
+    );
+    let () = util::assert_core(&core_file);
+  } else {
+    let () = disable_core_dumps();
+    let _registration = Builder::new()
+      .lazy_rlimit(true)
+      .alloc_error_hook(true)
+      .register()
+      .unwrap();
+    handle_alloc_error(Layout::new::<u64>())
+  }
+}
+
+
+extern "C" fn foreign_handler(_signal: c_int) {
+  let _ = FOREIGN.fetch_add(1, Ordering::SeqCst);
+}
+
+extern "C" fn our_handler(signal: c_int, info: *mut siginfo_t, context: *mut c_void) {
+  let _ = OURS.fetch_add(1, Ordering::SeqCst);
+  if let Some(previous) = unsafe { PREVIOUS } {
+    assert!(unsafe { previous.chain(signal, info, context) });
+  }
+}
+
+
+/// Check that we can install a handler on top of another one, chain to
+/// it, and restore it afterwards.
+#[test]
+fn install_chain_restore() {
+  if util::isolate("install_chain_restore") {
+    return
+  }
+
+  let default = SavedAction::save(SIGUSR1).unwrap();
+  assert!(default.is_default());
+  assert!(!default.is_ignored());
+
+  let handler = foreign_handler as extern "C" fn(c_int);
+  let _ = unsafe { signal(SIGUSR1, handler as sighandler_t) };
+  let previous = SavedAction::install(SIGUSR1, our_handler, 0).unwrap();
+  assert_eq!(previous.signal(), SIGUSR1);
+  assert!(!previous.is_default());
+  unsafe { PREVIOUS = Some(previous) };
+
+  assert_eq!(unsafe { raise(SIGUSR1) }, 0);
+  assert_eq!(OURS.load(Ordering::SeqCst), 1);
+  assert_eq!(FOREIGN.load(Ordering::SeqCst), 1);
+
+  previous.restore().unwrap();
+  assert_eq!(unsafe { raise(SIGUSR1) }, 0);
+  assert_eq!(OURS.load(Ordering::SeqCst), 1);
+  assert_eq!(FOREIGN.load(Ordering::SeqCst), 2);
+
+  default.restore().unwrap();
+  assert!(SavedAction::save(SIGUSR1).unwrap().is_default());
+}
+
+
+/// Check that the handler the Rust standard library installs for
+/// reporting stack overflows is not considered foreign.
+#[test]
+fn ignore_std_handler() {
+  use libc::SIGBUS;
+  use libc::SIGSEGV;
+  use libc::SIG_DFL;
+
+  use coredump::foreign_crash_handlers;
+
+  if util::isolate("ignore_std_handler") {
+    return
+  }
+
+  // We run as part of a regular Rust program, so the standard library
+  // should have installed its handler.
+  assert_ne!(handler(SIGSEGV), SIG_DFL);
+  assert_eq!(handler(SIGSEGV), handler(SIGBUS));
+
+  let foreign = foreign_crash_handlers().unwrap();
+  assert!(!foreign.contains(&SIGSEGV), "{:?}", foreign);
+  assert!(!foreign.contains(&SIGBUS), "{:?}", foreign);
+
+  let ignore = util::ignore as extern "C" fn(c_int);
+  let _ = unsafe { signal(SIGSEGV, ignore as sighandler_t) };
+  let foreign = foreign_crash_handlers().unwrap();
+  assert!(foreign.contains(&SIGSEGV), "{:?}", foreign);
+}
+
+
+/// Check whether `signal` is blocked on the calling thread.
+fn is_blocked(signal: c_int) -> bool {
+  use libc::pthread_sigmask;
+  use libc::sigismember;
+  use libc::sigset_t;
+  use libc::SIG_BLOCK;
+
+  let mut set = unsafe { zeroed::<sigset_t>() };
+  assert_eq!(unsafe { pthread_sigmask(SIG_BLOCK, null(), &mut set) }, 0);
+  unsafe { sigismember(&set, signal) == 1 }
+}
+
+extern "C" fn masked_handler(_signal: c_int) {
+  let blocked = is_blocked(libc::SIGUSR2) as usize;
+  let () = BLOCKED_IN_CHAINED.store(blocked, Ordering::SeqCst);
+}
+
+extern "C" fn chaining_handler(signal: c_int, info: *mut siginfo_t, context: *mut c_void) {
+  if let Some(previous) = unsafe { MASKED } {
+    assert!(unsafe { previous.chain(signal, info, context) });
+  }
+  let blocked = is_blocked(libc::SIGUSR2) as usize;
+  let () = BLOCKED_AFTER_CHAIN.store(blocked, Ordering::SeqCst);
+}
+
+
+/// Check that chaining to a saved handler honors its signal mask and
+/// its `SA_RESETHAND` flag.
+#[test]
+fn chain_with_mask_and_flags() {
+  use libc::sigaddset;
+  use libc::sigemptyset;
+  use libc::SA_RESETHAND;
+  use libc::SIGUSR2;
+
+  if util::isolate("chain_with_mask_and_flags") {
+    return
+  }
+
+  let mut action = unsafe { zeroed::<sigaction>() };
+  action.sa_sigaction = masked_handler as extern "C" fn(c_int) as sighandler_t;
+  action.sa_flags = SA_RESETHAND;
+  assert_eq!(unsafe { sigemptyset(&mut action.sa_mask) }, 0);
+  assert_eq!(unsafe { sigaddset(&mut action.sa_mask, SIGUSR2) }, 0);
+  assert_eq!(unsafe { sigaction(SIGUSR1, &action, null_mut()) }, 0);
+
+  let previous = SavedAction::install(SIGUSR1, chaining_handler, 0).unwrap();
+  unsafe { MASKED = Some(previous) };
+
+  assert_eq!(unsafe { raise(SIGUSR1) }, 0);
+  assert_eq!(BLOCKED_IN_CHAINED.load(Ordering::SeqCst), 1);
+  assert_eq!(BLOCKED_AFTER_CHAIN.load(Ordering::SeqCst), 0);
+  // The chained handler requested to be reset, which removed ours.
+  assert!(SavedAction::save(SIGUSR1).unwrap().is_default());
+}
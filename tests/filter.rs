@@ -0,0 +1,256 @@
+// filter.rs
+
+// Copyright (C) 2026 Daniel Mueller <deso@posteo.net>
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+//! Tests for deciding which panics dump core.
+
+mod util;
+
+use std::env::temp_dir;
+use std::fs::read_dir;
+use std::fs::read_to_string;
+use std::fs::remove_dir_all;
+use std::fs::remove_file;
+use std::os::unix::process::ExitStatusExt;
+use std::panic::catch_unwind;
+use std::sync::Arc;
+use std::sync::Barrier;
+use std::thread::sleep;
+use std::thread::spawn;
+use std::time::Duration;
+
+use libc::SIGQUIT;
+
+use coredump::register_panic_handler;
+use coredump::register_panic_handler_with;
+use coredump::suppress;
+use coredump::thread::opt_out;
+use coredump::unregister;
+use coredump::with_coredumps_disabled;
+use coredump::Builder;
+
+
+/// Check that core is only dumped for panics accepted by the
+/// user-provided predicate.
+#[test]
+fn filter_panics() {
+  if let Some(_guard) = util::parent() {
+    let rc = util::child("filter_panics").status().unwrap();
+
+    assert!(!rc.success());
+    assert_eq!(rc.signal().unwrap(), SIGQUIT);
+    let () = util::remove_core();
+  } else {
+    register_panic_handler_with(|panic_info| {
+      panic_info.payload().downcast_ref::<&str>() != Some(&"recoverable")
+    })
+    .unwrap();
+
+    let result = catch_unwind(|| panic!("recoverable"));
+    assert!(result.is_err());
+
+    panic!("fatal");
+  }
+}
+
+
+/// Check that concurrent panics declined by the filter do not dump
+/// core.
+#[test]
+fn decline_concurrent_panics() {
+  if let Some(_guard) = util::parent() {
+    let rc = util::child("decline_concurrent_panics").status().unwrap();
+
+    // Had core been dumped, the child would have been killed by
+    // SIGQUIT.
+    assert!(rc.success(), "{:?}", rc);
+  } else {
+    register_panic_handler_with(|_panic_info| {
+      // Keep the hook busy, so that the panics overlap.
+      sleep(Duration::from_millis(200));
+      false
+    })
+    .unwrap();
+
+    let barrier = Arc::new(Barrier::new(2));
+    let workers = (0..2)
+      .map(|_| {
+        let barrier = barrier.clone();
+        spawn(move || {
+          let _leader = barrier.wait();
+          panic!("worker panic")
+        })
+      })
+      .collect::<Vec<_>>();
+
+    for worker in workers {
+      assert!(worker.join().is_err());
+    }
+  }
+}
+
+
+/// Check that core is only dumped for panics with matching message and
+/// location.
+#[test]
+fn filter_message_location() {
+  if let Some(_guard) = util::parent() {
+    let rc = util::child("filter_message_location").status().unwrap();
+
+    assert!(!rc.success());
+    assert_eq!(rc.signal().unwrap(), SIGQUIT);
+    let () = util::remove_core();
+  } else {
+    let _registration = Builder::new()
+      .only_if_message_contains(["fatal"])
+      .only_if_location_in(["src/"])
+      .register()
+      .unwrap();
+
+    // The location is in `tests/` and so does not match.
+    let result = catch_unwind(|| panic!("fatal"));
+    assert!(result.is_err());
+    assert!(unregister().unwrap());
+
+    let _registration = Builder::new()
+      .only_if_message_contains(["fatal", "deadly"])
+      .only_if_location_in(["tests/"])
+      .register()
+      .unwrap();
+
+    let result = catch_unwind(|| panic!("benign"));
+    assert!(result.is_err());
+
+    panic!("a fatal error occurred");
+  }
+}
+
+
+/// Check that no core is dumped for crashes that are sampled out, but
+/// that metadata are recorded for them.
+#[test]
+fn sample_out() {
+  if let Some(_guard) = util::parent() {
+    let mut child = util::child("sample_out").spawn().unwrap();
+    let prefix = format!("coredump-{}-", child.id());
+    let rc = child.wait().unwrap();
+    // Had core been dumped, the child would have been killed by
+    // SIGQUIT.
+    assert!(rc.success());
+
+    let path = read_dir(temp_dir())
+      .unwrap()
+      .map(|entry| entry.unwrap().path())
+      .find(|path| {
+        let name = path.file_name().unwrap().to_string_lossy();
+        name.starts_with(&prefix) && name.ends_with(".txt")
+      })
+      .expect("metadata file not found");
+
+    let metadata = read_to_string(&path).unwrap();
+    let _ = remove_file(&path);
+
+    assert!(metadata.contains("panic-message: induced panic\n"));
+    assert!(metadata.contains("crash-signature: "));
+    assert!(metadata.contains("dump: sampled-out\n"));
+  } else {
+    Builder::new().sample(Some(0)).register().unwrap();
+
+    let _ = catch_unwind(|| panic!("induced panic"));
+  }
+}
+
+
+/// Check that a second crash within the configured interval does not
+/// dump core.
+#[test]
+fn rate_limit() {
+  let dir = temp_dir().join("coredump-rate-limit-test");
+
+  if let Some(_guard) = util::parent() {
+    let _ = remove_dir_all(&dir);
+
+    let run = || util::child("rate_limit").status().unwrap();
+
+    let rc = run();
+    assert_eq!(rc.signal(), Some(SIGQUIT));
+
+    // Had core been dumped, the child would have been killed by
+    // SIGQUIT.
+    let rc = run();
+    assert!(rc.success());
+
+    let metadata = read_dir(&dir)
+      .unwrap()
+      .map(|entry| entry.unwrap().path())
+      .filter(|path| path.extension().map(|ext| ext == "txt").unwrap_or(false))
+      .map(|path| read_to_string(path).unwrap())
+      .collect::<Vec<_>>();
+    let _ = remove_dir_all(&dir);
+
+    assert_eq!(metadata.len(), 1);
+    assert!(metadata[0].contains("dump: rate-limited\n"));
+  } else {
+    Builder::new()
+      .dir(Some(dir))
+      .create_dir(Some(0o700))
+      .rate_limit(Some(Duration::from_secs(3600)))
+      .register()
+      .unwrap();
+
+    let _ = catch_unwind(|| panic!("induced panic"));
+  }
+}
+
+
+/// Check that no core is dumped while suppressed, but that dumps are
+/// re-enabled afterwards.
+#[test]
+fn suppress_temporarily() {
+  if let Some(_guard) = util::parent() {
+    let rc = util::child("suppress_temporarily").status().unwrap();
+
+    assert!(!rc.success());
+    assert_eq!(rc.signal().unwrap(), SIGQUIT);
+    let () = util::remove_core();
+  } else {
+    let () = register_panic_handler().unwrap();
+
+    let result = with_coredumps_disabled(|| catch_unwind(|| panic!("expected")));
+    assert!(result.is_err());
+
+    {
+      let _outer = suppress();
+      let _inner = suppress();
+      let result = catch_unwind(|| panic!("expected"));
+      assert!(result.is_err());
+    }
+
+    panic!("induced panic");
+  }
+}
+
+
+/// Check that panics on threads that opted out do not dump core, while
+/// panics on other threads still do.
+#[test]
+fn opt_out_thread() {
+  if let Some(_guard) = util::parent() {
+    let rc = util::child("opt_out_thread").status().unwrap();
+
+    assert!(!rc.success());
+    assert_eq!(rc.signal().unwrap(), SIGQUIT);
+    let () = util::remove_core();
+  } else {
+    let () = register_panic_handler().unwrap();
+
+    let worker = spawn(|| {
+      let () = opt_out();
+      panic!("worker panic")
+    });
+    assert!(worker.join().is_err());
+
+    panic!("induced panic");
+  }
+}
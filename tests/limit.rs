@@ -0,0 +1,231 @@
+// limit.rs
+
+// Copyright (C) 2026 Daniel Mueller <deso@posteo.net>
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+//! Tests for the management of the core file size limit.
+
+mod util;
+
+use std::os::unix::process::ExitStatusExt;
+
+use libc::getrlimit;
+use libc::rlimit;
+use libc::setrlimit;
+use libc::RLIMIT_CORE;
+use libc::SIGQUIT;
+
+use coredump::disable_core_dumps;
+use coredump::enable_core_dumps;
+use coredump::reapply;
+use coredump::Builder;
+
+
+fn core_limit() -> rlimit {
+  let mut limit = rlimit {
+    rlim_cur: 0,
+    rlim_max: 0,
+  };
+  assert_eq!(unsafe { getrlimit(RLIMIT_CORE, &mut limit) }, 0);
+  limit
+}
+
+
+/// Check that the soft core file size limit can be adjusted without
+/// registering a panic handler.
+#[test]
+fn adjust_limit() {
+  if util::isolate("adjust_limit") {
+    return
+  }
+
+  let limit = core_limit();
+  if limit.rlim_max == 0 {
+    assert!(enable_core_dumps(None).is_err());
+    return
+  }
+
+  let _previous = disable_core_dumps().unwrap();
+  assert_eq!(core_limit().rlim_cur, 0);
+
+  let previous = enable_core_dumps(Some(1024)).unwrap();
+  assert_eq!(previous.soft, Some(0));
+  assert_eq!(core_limit().rlim_cur, limit.rlim_max.min(1024));
+
+  let previous = enable_core_dumps(None).unwrap();
+  assert_eq!(previous.soft, Some(limit.rlim_max.min(1024)));
+  assert_eq!(core_limit().rlim_cur, limit.rlim_max);
+
+  let previous = disable_core_dumps().unwrap();
+  assert_eq!(previous.soft, previous.hard);
+  assert_eq!(core_limit().rlim_cur, 0);
+}
+
+
+/// Check that core file size limits beyond 4 GiB are represented
+/// faithfully, even on 32 bit systems.
+#[test]
+#[cfg(target_os = "linux")]
+fn adjust_large_limit() {
+  use libc::getrlimit64;
+  use libc::rlimit64;
+
+  const SIZE: u64 = 6 << 30;
+
+  if util::isolate("adjust_large_limit") {
+    return
+  }
+
+  let mut limit = rlimit64 {
+    rlim_cur: 0,
+    rlim_max: 0,
+  };
+  assert_eq!(unsafe { getrlimit64(RLIMIT_CORE, &mut limit) }, 0);
+  if limit.rlim_max < SIZE {
+    return
+  }
+
+  let _previous = enable_core_dumps(Some(SIZE)).unwrap();
+  assert_eq!(unsafe { getrlimit64(RLIMIT_CORE, &mut limit) }, 0);
+  assert_eq!(limit.rlim_cur, SIZE);
+
+  let previous = disable_core_dumps().unwrap();
+  assert_eq!(previous.soft, Some(SIZE));
+}
+
+
+/// Check that the core file size limit is only raised once a panic
+/// happens.
+#[test]
+fn raise_limit_lazily() {
+  if let Some(_guard) = util::parent() {
+    if core_limit().rlim_max == 0 {
+      return
+    }
+
+    let rc = util::child("raise_limit_lazily").status().unwrap();
+
+    assert_eq!(rc.signal().unwrap(), SIGQUIT);
+    assert!(rc.core_dumped());
+    let () = util::remove_core();
+  } else {
+    let mut limit = core_limit();
+    limit.rlim_cur = 0;
+    assert_eq!(unsafe { setrlimit(RLIMIT_CORE, &limit) }, 0);
+
+    let registration = Builder::new().lazy_rlimit(true).register().unwrap();
+    assert_eq!(core_limit().rlim_cur, 0);
+    assert_eq!(registration.previous_limit().soft, Some(0));
+
+    panic!("induced panic");
+  }
+}
+
+
+/// Check that a lazily raised core file size limit is restored after
+/// a snapshot core got created.
+#[test]
+#[cfg(target_os = "linux")]
+fn restore_limit_after_snapshot() {
+  use std::panic::set_hook;
+  use std::process::exit;
+
+  if let Some(_guard) = util::parent() {
+    if core_limit().rlim_max == 0 {
+      return
+    }
+
+    let rc = util::child("restore_limit_after_snapshot").status().unwrap();
+
+    assert_eq!(rc.code(), Some(42));
+    let () = util::remove_core();
+  } else {
+    let mut limit = core_limit();
+    limit.rlim_cur = 0;
+    assert_eq!(unsafe { setrlimit(RLIMIT_CORE, &limit) }, 0);
+
+    // This hook gets invoked after the snapshot core was created.
+    set_hook(Box::new(|_| {
+      let code = if core_limit().rlim_cur == 0 { 42 } else { 1 };
+      exit(code)
+    }));
+
+    Builder::new()
+      .lazy_rlimit(true)
+      .libfuzzer(true)
+      .register()
+      .unwrap();
+    panic!("induced panic");
+  }
+}
+
+
+/// Check that the core file size limit is left alone if management of
+/// it is disabled.
+#[test]
+fn unmanaged_limit() {
+  if util::isolate("unmanaged_limit") {
+    return
+  }
+
+  let mut limit = core_limit();
+  limit.rlim_cur = 0;
+  assert_eq!(unsafe { setrlimit(RLIMIT_CORE, &limit) }, 0);
+
+  let registration = Builder::new().manage_rlimit(false).register().unwrap();
+  assert_eq!(registration.previous_limit().soft, Some(0));
+  assert_eq!(core_limit().rlim_cur, 0);
+
+  let () = reapply().unwrap();
+  assert_eq!(core_limit().rlim_cur, 0);
+}
+
+
+/// Check that the soft core file size limit is capped at the
+/// configured value, also when re-applying the configuration.
+#[test]
+fn cap_limit() {
+  if util::isolate("cap_limit") {
+    return
+  }
+
+  let mut limit = core_limit();
+  if limit.rlim_max == 0 {
+    return
+  }
+
+  let _registration = Builder::new().max_size(Some(1 << 20)).register().unwrap();
+  assert_eq!(core_limit().rlim_cur, limit.rlim_max.min(1 << 20));
+
+  limit.rlim_cur = 0;
+  assert_eq!(unsafe { setrlimit(RLIMIT_CORE, &limit) }, 0);
+  let () = reapply().unwrap();
+  assert_eq!(core_limit().rlim_cur, limit.rlim_max.min(1 << 20));
+}
+
+
+/// Check that we can register and attempt a dump despite a zero hard
+/// core file size limit, if asked to.
+#[test]
+fn register_with_zero_limit() {
+  if let Some(_guard) = util::parent() {
+    let rc = util::child("register_with_zero_limit").status().unwrap();
+
+    assert_eq!(rc.signal().unwrap(), SIGQUIT);
+    assert!(!rc.core_dumped());
+  } else {
+    // Lowering the hard limit is irreversible for unprivileged
+    // processes, but we are in a throw-away child.
+    let limit = rlimit {
+      rlim_cur: 0,
+      rlim_max: 0,
+    };
+    assert_eq!(unsafe { setrlimit(RLIMIT_CORE, &limit) }, 0);
+
+    let _err = Builder::new().register().unwrap_err();
+    let registration = Builder::new().allow_zero_limit(true).register().unwrap();
+    assert_eq!(registration.previous_limit().hard, Some(0));
+
+    panic!("induced panic");
+  }
+}
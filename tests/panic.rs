@@ -0,0 +1,769 @@
+// panic.rs
+
+// Copyright (C) 2026 Daniel Mueller <deso@posteo.net>
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+//! Tests for the behavior of the panic hook.
+
+mod util;
+
+use std::env::temp_dir;
+use std::env::var_os;
+use std::fs::create_dir_all;
+use std::fs::remove_dir;
+use std::io::stderr;
+use std::io::Error;
+use std::io::Write as _;
+use std::mem::zeroed;
+use std::os::raw::c_int;
+use std::os::unix::process::CommandExt as _;
+use std::os::unix::process::ExitStatusExt;
+use std::panic::catch_unwind;
+use std::panic::set_hook;
+use std::process::id as pid;
+use std::ptr::null_mut;
+use std::sync::atomic::AtomicUsize;
+use std::sync::atomic::Ordering;
+use std::thread::sleep;
+use std::thread::spawn;
+use std::time::Duration;
+
+use libc::_exit;
+use libc::pthread_self;
+use libc::pthread_sigmask;
+use libc::sigaddset;
+use libc::sigemptyset;
+use libc::sighandler_t;
+use libc::signal;
+use libc::sigset_t;
+use libc::SIGABRT;
+use libc::SIGKILL;
+use libc::SIGQUIT;
+use libc::SIG_BLOCK;
+use libc::SIG_IGN;
+
+use coredump::register_and_always_abort;
+use coredump::register_panic_handler;
+use coredump::Builder;
+use coredump::HandlerPolicy;
+use coredump::HookOrder;
+
+use util::ignore;
+use util::CHILD_MARKER;
+
+const PREVIOUS_HOOK: &str = "previous hook invoked";
+
+/// The exit code signaling that the dump signal arrived on the
+/// panicking thread.
+const SAME_THREAD: c_int = 42;
+
+/// The ID of the thread that panics.
+static PANICKING: AtomicUsize = AtomicUsize::new(0);
+
+
+/// Install [`ignore`] as the handler for the dump signal, so that we
+/// survive the dump attempt.
+fn ignore_dump_signal() {
+  let _ = unsafe { signal(SIGQUIT, ignore as extern "C" fn(c_int) as sighandler_t) };
+}
+
+
+/// Check that the process is terminated by the configured dump signal.
+#[test]
+fn custom_signal() {
+  if let Some(_guard) = util::parent() {
+    let rc = util::child("custom_signal").status().unwrap();
+
+    assert!(!rc.success());
+    assert_eq!(rc.signal().unwrap(), SIGABRT);
+  } else {
+    let _registration = Builder::new().signal(Some(SIGABRT)).register().unwrap();
+
+    panic!("induced panic");
+  }
+}
+
+
+/// Check that a panic terminates the process even if the dump signal
+/// is handled.
+#[test]
+fn abort_on_handled_signal() {
+  if let Some(_guard) = util::parent() {
+    let rc = util::child("abort_on_handled_signal").status().unwrap();
+
+    assert!(!rc.success());
+    assert_eq!(rc.signal().unwrap(), SIGABRT);
+  } else {
+    let () = ignore_dump_signal();
+    register_and_always_abort().unwrap();
+    panic!("induced panic");
+  }
+}
+
+
+/// Check that a panic exits the process with the configured status
+/// code if the dump signal is handled.
+#[test]
+fn exit_on_handled_signal() {
+  if let Some(_guard) = util::parent() {
+    let rc = util::child("exit_on_handled_signal").status().unwrap();
+
+    assert_eq!(rc.code(), Some(70));
+  } else {
+    let () = ignore_dump_signal();
+    let _registration = Builder::new()
+      .always_abort(true)
+      .exit_code(Some(70))
+      .register()
+      .unwrap();
+    panic!("induced panic");
+  }
+}
+
+
+/// Check that the watchdog kills a process that survived the dump
+/// attempt.
+#[test]
+fn watchdog_kills_survivor() {
+  if let Some(_guard) = util::parent() {
+    let rc = util::child("watchdog_kills_survivor").status().unwrap();
+
+    assert!(!rc.success());
+    assert_eq!(rc.signal().unwrap(), SIGKILL);
+  } else {
+    let () = ignore_dump_signal();
+    Builder::new()
+      .watchdog(Some(Duration::from_millis(100)))
+      .register()
+      .unwrap();
+
+    let _ = catch_unwind(|| panic!("induced panic"));
+    // Linger around waiting for the watchdog to kill us.
+    sleep(Duration::from_secs(30));
+  }
+}
+
+
+/// Check that the watchdog does not keep the output of the process it
+/// watches open, delaying readers until it expires.
+#[test]
+fn watchdog_releases_output() {
+  use std::time::Instant;
+
+  if let Some(_guard) = util::parent() {
+    let start = Instant::now();
+    let output = util::child("watchdog_releases_output").output().unwrap();
+    let elapsed = start.elapsed();
+
+    assert_eq!(output.status.signal(), Some(SIGQUIT));
+    assert!(elapsed < Duration::from_secs(5), "{:?}", elapsed);
+    let () = util::remove_core();
+  } else {
+    Builder::new()
+      .watchdog(Some(Duration::from_secs(10)))
+      .register()
+      .unwrap();
+
+    panic!("induced panic");
+  }
+}
+
+
+/// Check that the `OursFirst` policy overrides a pre-existing SIGQUIT
+/// disposition.
+#[test]
+fn override_foreign_handler() {
+  if let Some(_guard) = util::parent() {
+    let rc = util::child("override_foreign_handler").status().unwrap();
+
+    assert!(!rc.success());
+    assert_eq!(rc.signal().unwrap(), SIGQUIT);
+    let () = util::remove_core();
+  } else {
+    let () = ignore_dump_signal();
+    let registration = Builder::new()
+      .handler_policy(HandlerPolicy::OursFirst)
+      .register()
+      .unwrap();
+    assert!(registration.foreign_handlers().contains(&SIGQUIT));
+
+    panic!("induced panic");
+  }
+}
+
+
+/// Check that the previously installed hook is only invoked before
+/// dumping core if so configured.
+#[test]
+fn hook_order() {
+  if let Some(_guard) = util::parent() {
+    let run = |order| {
+      let output = util::child("hook_order")
+        .env(CHILD_MARKER, order)
+        .output()
+        .unwrap();
+      assert_eq!(output.status.signal(), Some(SIGQUIT));
+      String::from_utf8(output.stderr).unwrap()
+    };
+
+    assert!(run("after").contains(PREVIOUS_HOOK));
+    assert!(!run("before").contains(PREVIOUS_HOOK));
+    let () = util::remove_core();
+  } else {
+    let order = if var_os(CHILD_MARKER).unwrap() == "before" {
+      HookOrder::BeforeExisting
+    } else {
+      HookOrder::AfterExisting
+    };
+
+    // Output of `eprintln!` is captured by the test harness, so write
+    // to stderr directly.
+    let () = set_hook(Box::new(|_| {
+      let _ = writeln!(stderr(), "{}", PREVIOUS_HOOK);
+    }));
+    let _registration = Builder::new().hook_order(order).register().unwrap();
+    panic!("induced panic");
+  }
+}
+
+
+/// Check that the previously installed hook is not invoked in quiet
+/// mode, unless dumping core fails.
+#[test]
+fn quiet() {
+  if let Some(_guard) = util::parent() {
+    let run = |mode| util::child("quiet").env(CHILD_MARKER, mode).output().unwrap();
+
+    let output = run("dump");
+    assert_eq!(output.status.signal(), Some(SIGQUIT));
+    assert!(!String::from_utf8(output.stderr)
+      .unwrap()
+      .contains(PREVIOUS_HOOK));
+
+    let output = run("fail");
+    assert_eq!(output.status.code(), Some(70));
+    assert!(String::from_utf8(output.stderr)
+      .unwrap()
+      .contains(PREVIOUS_HOOK));
+
+    let () = util::remove_core();
+  } else {
+    if var_os(CHILD_MARKER).unwrap() == "fail" {
+      let () = ignore_dump_signal();
+    }
+
+    // Output of `eprintln!` is captured by the test harness, so write
+    // to stderr directly.
+    let () = set_hook(Box::new(|_| {
+      let _ = writeln!(stderr(), "{}", PREVIOUS_HOOK);
+    }));
+    let _registration = Builder::new()
+      .quiet(true)
+      .exit_code(Some(70))
+      .register()
+      .unwrap();
+    panic!("induced panic");
+  }
+}
+
+
+/// A handler for the dump signal reporting whether it runs on the
+/// panicking thread.
+extern "C" fn report(_signal: c_int) {
+  let code = if unsafe { pthread_self() } as usize == PANICKING.load(Ordering::SeqCst) {
+    SAME_THREAD
+  } else {
+    1
+  };
+  unsafe { _exit(code) }
+}
+
+
+/// Check that the dump signal is delivered to the thread that
+/// panicked.
+#[test]
+fn signal_panicking_thread() {
+  if let Some(_guard) = util::parent() {
+    let rc = util::child("signal_panicking_thread").status().unwrap();
+
+    assert_eq!(rc.code(), Some(SAME_THREAD));
+  } else {
+    let _ = unsafe { signal(SIGQUIT, report as extern "C" fn(c_int) as sighandler_t) };
+    let () = register_panic_handler().unwrap();
+
+    let worker = spawn(|| {
+      let () = PANICKING.store(unsafe { pthread_self() } as usize, Ordering::SeqCst);
+      panic!("worker panic")
+    });
+    let _ = worker.join();
+    unreachable!()
+  }
+}
+
+
+/// A handler for the dump signal panicking on another thread.
+///
+/// A panic on the thread running the panic hook would make the Rust
+/// runtime abort right away, without invoking the hook again.
+extern "C" fn panicking_handler(_signal: c_int) {
+  let _ = spawn(|| panic!("panic in signal handler")).join();
+}
+
+
+/// Check that a panic happening while the panic hook runs still results
+/// in the process being terminated by the dump signal.
+#[test]
+fn dump_on_reentrance() {
+  if let Some(_guard) = util::parent() {
+    let rc = util::child("dump_on_reentrance").status().unwrap();
+
+    assert_eq!(rc.signal().unwrap(), SIGQUIT);
+    let () = util::remove_core();
+  } else {
+    // The default `TheirsFirst` policy makes the panic hook invoke this
+    // handler, which then causes a panic in turn.
+    let handler = panicking_handler as extern "C" fn(c_int);
+    let _ = unsafe { signal(SIGQUIT, handler as sighandler_t) };
+    register_panic_handler().unwrap();
+
+    panic!("induced panic");
+  }
+}
+
+
+/// Check that core is dumped even if the dump signal is blocked, as it
+/// may be when inherited from the parent process.
+#[test]
+fn dump_despite_blocked_signal() {
+  if let Some(_guard) = util::parent() {
+    let mut command = util::child("dump_despite_blocked_signal");
+    // Output of `eprintln!` is captured by the test harness, unless
+    // told otherwise.
+    let _command = command.arg("--nocapture");
+    // The signal mask is inherited across `exec`, so that the signal
+    // is blocked on all threads of the child.
+    let _command = unsafe {
+      command.pre_exec(|| {
+        let mut set = zeroed::<sigset_t>();
+        let _ = sigemptyset(&mut set);
+        let _ = sigaddset(&mut set, SIGQUIT);
+        match pthread_sigmask(SIG_BLOCK, &set, null_mut()) {
+          0 => Ok(()),
+          err => Err(Error::from_raw_os_error(err)),
+        }
+      })
+    };
+    let output = command.output().unwrap();
+
+    assert_eq!(output.status.signal(), Some(SIGQUIT));
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(stderr.contains("signal 3 is blocked"), "{}", stderr);
+    let () = util::remove_core();
+  } else {
+    let () = register_panic_handler().unwrap();
+    panic!("induced panic");
+  }
+}
+
+
+/// Check that core is dumped even if the dump signal is ignored, as it
+/// may be when inherited from the parent process.
+#[test]
+fn dump_despite_ignored_signal() {
+  if let Some(_guard) = util::parent() {
+    let rc = util::child("dump_despite_ignored_signal").status().unwrap();
+
+    assert_eq!(rc.signal(), Some(SIGQUIT));
+    let () = util::remove_core();
+  } else {
+    let _ = unsafe { signal(SIGQUIT, SIG_IGN) };
+    let () = register_panic_handler().unwrap();
+    panic!("induced panic");
+  }
+}
+
+
+/// Install a seccomp filter making `tgkill(2)`, which `raise(3)` is
+/// based on, fail with `EPERM`.
+#[cfg(target_os = "linux")]
+fn deny_tgkill() {
+  use libc::prctl;
+  use libc::sock_filter;
+  use libc::sock_fprog;
+  use libc::SYS_tgkill;
+  use libc::BPF_ABS;
+  use libc::BPF_JEQ;
+  use libc::BPF_JMP;
+  use libc::BPF_K;
+  use libc::BPF_LD;
+  use libc::BPF_RET;
+  use libc::BPF_W;
+  use libc::EPERM;
+  use libc::PR_SET_NO_NEW_PRIVS;
+  use libc::PR_SET_SECCOMP;
+  use libc::SECCOMP_MODE_FILTER;
+  use libc::SECCOMP_RET_ALLOW;
+  use libc::SECCOMP_RET_ERRNO;
+
+  let filter = [
+    // Load the system call number.
+    sock_filter {
+      code: (BPF_LD | BPF_W | BPF_ABS) as _,
+      jt: 0,
+      jf: 0,
+      k: 0,
+    },
+    sock_filter {
+      code: (BPF_JMP | BPF_JEQ | BPF_K) as _,
+      jt: 1,
+      jf: 0,
+      k: SYS_tgkill as _,
+    },
+    sock_filter {
+      code: BPF_RET as _,
+      jt: 0,
+      jf: 0,
+      k: SECCOMP_RET_ALLOW,
+    },
+    sock_filter {
+      code: BPF_RET as _,
+      jt: 0,
+      jf: 0,
+      k: SECCOMP_RET_ERRNO | EPERM as u32,
+    },
+  ];
+  let program = sock_fprog {
+    len: filter.len() as _,
+    filter: filter.as_ptr() as *mut _,
+  };
+
+  assert_eq!(unsafe { prctl(PR_SET_NO_NEW_PRIVS, 1, 0, 0, 0) }, 0);
+  assert_eq!(
+    unsafe { prctl(PR_SET_SECCOMP, SECCOMP_MODE_FILTER, &program) },
+    0
+  );
+}
+
+
+/// Check that core is still dumped if raising the dump signal is
+/// denied, by falling back to sending it to the process.
+#[test]
+#[cfg(target_os = "linux")]
+fn fall_back_to_kill() {
+  use coredump::Fallback;
+
+  if let Some(_guard) = util::parent() {
+    let rc = util::child("fall_back_to_kill").status().unwrap();
+
+    assert_eq!(rc.signal(), Some(SIGQUIT));
+    let () = util::remove_core();
+  } else {
+    let _registration = Builder::new()
+      .fallbacks([Fallback::Kill])
+      .register()
+      .unwrap();
+    let () = deny_tgkill();
+    panic!("induced panic");
+  }
+}
+
+
+/// Check that the process is aborted with a message if raising the dump
+/// signal is denied and aborting is the configured fallback.
+#[test]
+#[cfg(target_os = "linux")]
+fn fall_back_to_abort() {
+  use libc::rlimit;
+  use libc::setrlimit;
+  use libc::RLIMIT_CORE;
+
+  use coredump::Fallback;
+
+  if let Some(_guard) = util::parent() {
+    let output = util::child("fall_back_to_abort").output().unwrap();
+
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    let expected = format!(
+      "coredump: failed to raise signal {} (error {}); aborting\n",
+      SIGQUIT,
+      libc::EPERM
+    );
+    assert!(stderr.contains(&expected), "{}", stderr);
+  } else {
+    let _registration = Builder::new()
+      .fallbacks([Fallback::Abort])
+      .register()
+      .unwrap();
+    // With `raise` failing, `abort` may resort to other means of
+    // terminating us; just make sure none of them litters the working
+    // directory with a core file.
+    let limit = rlimit {
+      rlim_cur: 0,
+      rlim_max: 0,
+    };
+    assert_eq!(unsafe { setrlimit(RLIMIT_CORE, &limit) }, 0);
+    let () = deny_tgkill();
+    panic!("induced panic");
+  }
+}
+
+
+/// Check that errors in the panic hook are reported to the configured
+/// sink.
+#[test]
+fn report_to_sink() {
+  if let Some(_guard) = util::parent() {
+    let output = util::child("report_to_sink").output().unwrap();
+
+    assert_eq!(output.status.code(), Some(70));
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(
+      stderr.contains("sink: failed to dump core: failed to change working directory"),
+      "{}",
+      stderr
+    );
+  } else {
+    let dir = temp_dir().join(format!("coredump-error-sink-{}", pid()));
+    let () = create_dir_all(&dir).unwrap();
+    let _registration = Builder::new()
+      .dir(Some(dir.clone()))
+      .exit_code(Some(70))
+      .error_sink(|ctx, err| {
+        // Output of `eprintln!` is captured by the test harness, so
+        // write to stderr directly.
+        let _ = writeln!(stderr(), "sink: {}: {}", ctx, err);
+      })
+      .register()
+      .unwrap();
+    // With the dump directory gone, dumping core fails.
+    let () = remove_dir(&dir).unwrap();
+    panic!("induced panic");
+  }
+}
+
+
+/// Check that warnings issued during registration are reported to the
+/// configured sink.
+#[test]
+fn report_warning_to_sink() {
+  if let Some(_guard) = util::parent() {
+    let mut command = util::child("report_warning_to_sink");
+    let _command = unsafe {
+      command.pre_exec(|| {
+        let mut set = zeroed::<sigset_t>();
+        let _ = sigemptyset(&mut set);
+        let _ = sigaddset(&mut set, SIGQUIT);
+        match pthread_sigmask(SIG_BLOCK, &set, null_mut()) {
+          0 => Ok(()),
+          err => Err(Error::from_raw_os_error(err)),
+        }
+      })
+    };
+    let output = command.output().unwrap();
+
+    assert_eq!(output.status.code(), Some(0));
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(
+      stderr.contains("sink: coredump: signal 3 is blocked"),
+      "{}",
+      stderr
+    );
+  } else {
+    let _registration = Builder::new()
+      .error_sink(|ctx, err| {
+        let _ = writeln!(stderr(), "sink: {}: {}", ctx, err);
+      })
+      .register()
+      .unwrap();
+  }
+}
+
+
+/// Check that in libFuzzer mode we create a core file and then defer to
+/// the previously installed panic hook.
+#[test]
+#[cfg(target_os = "linux")]
+fn snapshot_and_chain() {
+  use std::process::exit;
+
+  if let Some(_guard) = util::parent() {
+    let core_file = match util::core_file() {
+      Some(core_file) => core_file,
+      None => return,
+    };
+
+    let rc = util::child("snapshot_and_chain").status().unwrap();
+
+    assert_eq!(rc.code(), Some(42));
+    let () = util::assert_core(&core_file);
+  } else {
+    // Emulate the panic hook installed by `libfuzzer-sys`. We exit
+    // instead of aborting in order to not litter the working directory
+    // with core files.
+    set_hook(Box::new(|_| exit(42)));
+
+    Builder::new().libfuzzer(true).register().unwrap();
+    panic!("induced panic");
+  }
+}
+
+
+/// Check that the `on_dump` callback is informed about the path of the
+/// core file created.
+#[test]
+#[cfg(target_os = "linux")]
+fn report_core_path() {
+  use std::fs::remove_file;
+  use std::path::PathBuf;
+  use std::process::exit;
+
+  if let Some(_guard) = util::parent() {
+    let core_file = match util::core_file() {
+      Some(core_file) => core_file,
+      None => return,
+    };
+    if core_file.to_str().unwrap().contains('%') {
+      return
+    }
+
+    let output = util::child("report_core_path").output().unwrap();
+
+    assert_eq!(output.status.code(), Some(42));
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    let path = stderr
+      .lines()
+      .find_map(|line| line.strip_prefix("on_dump: "))
+      .map(PathBuf::from)
+      .unwrap_or_else(|| panic!("callback was not invoked: {}", stderr));
+    // The kernel may append the PID of the dumping process.
+    assert!(
+      path.to_str().unwrap().starts_with(core_file.to_str().unwrap()),
+      "{}",
+      path.display()
+    );
+    assert!(path.exists(), "core file {} does not exist", path.display());
+    let _ = remove_file(&path);
+  } else {
+    // Exit instead of aborting in order to not litter the working
+    // directory with core files.
+    set_hook(Box::new(|_| exit(42)));
+
+    let _registration = Builder::new()
+      .libfuzzer(true)
+      .on_dump(|path| {
+        // Output of `eprintln!` is captured by the test harness, so
+        // write to stderr directly.
+        let _ = writeln!(stderr(), "on_dump: {}", path.display());
+      })
+      .register()
+      .unwrap();
+    panic!("induced panic");
+  }
+}
+
+
+/// Check that adjusted priorities only apply to the process creating a
+/// snapshot core.
+#[test]
+#[cfg(target_os = "linux")]
+fn snapshot_with_priority() {
+  use std::process::exit;
+
+  use libc::getpriority;
+  use libc::PRIO_PROCESS;
+
+  use coredump::IoPriority;
+
+  if let Some(_guard) = util::parent() {
+    let core_file = match util::core_file() {
+      Some(core_file) => core_file,
+      None => return,
+    };
+
+    let rc = util::child("snapshot_with_priority").status().unwrap();
+
+    assert_eq!(rc.code(), Some(42));
+    let () = util::assert_core(&core_file);
+  } else {
+    let nice = unsafe { getpriority(PRIO_PROCESS, 0) };
+    // This hook gets invoked after the snapshot core was created.
+    set_hook(Box::new(move |_| {
+      let code = if unsafe { getpriority(PRIO_PROCESS, 0) } == nice {
+        42
+      } else {
+        1
+      };
+      exit(code)
+    }));
+
+    Builder::new()
+      .libfuzzer(true)
+      .nice(Some(19))
+      .io_priority(Some(IoPriority::Idle))
+      .register()
+      .unwrap();
+    panic!("induced panic");
+  }
+}
+
+
+/// Check that a panic in a wrapped task does not terminate the process
+/// and records the task in the crash metadata.
+#[test]
+fn task_panic() {
+  use std::fs::read_dir;
+  use std::fs::read_to_string;
+  use std::fs::remove_file;
+  use std::future::Future;
+  use std::panic::AssertUnwindSafe;
+  use std::pin::Pin;
+  use std::ptr::null;
+  use std::task::Context;
+  use std::task::Poll;
+  use std::task::RawWaker;
+  use std::task::RawWakerVTable;
+  use std::task::Waker;
+
+  use coredump::task::wrap;
+
+  fn noop_waker() -> Waker {
+    fn clone(_: *const ()) -> RawWaker {
+      RawWaker::new(null(), &VTABLE)
+    }
+    fn noop(_: *const ()) {}
+
+    static VTABLE: RawWakerVTable = RawWakerVTable::new(clone, noop, noop, noop);
+    unsafe { Waker::from_raw(RawWaker::new(null(), &VTABLE)) }
+  }
+
+  if util::isolate("task_panic") {
+    return
+  }
+
+  Builder::new().metadata(true).register().unwrap();
+
+  let mut task = Box::pin(wrap("worker", async {
+    panic!("induced panic");
+  }));
+  let waker = noop_waker();
+  let mut cx = Context::from_waker(&waker);
+  let result = catch_unwind(AssertUnwindSafe(|| {
+    let _poll: Poll<()> = Future::poll(Pin::as_mut(&mut task), &mut cx);
+  }));
+  assert!(result.is_err());
+
+  let prefix = format!("coredump-{}-", pid());
+  let path = read_dir(temp_dir())
+    .unwrap()
+    .map(|entry| entry.unwrap().path())
+    .find(|path| {
+      let name = path.file_name().unwrap().to_string_lossy();
+      name.starts_with(&prefix) && name.ends_with(".txt")
+    })
+    .expect("metadata file not found");
+
+  let metadata = read_to_string(&path).unwrap();
+  let _ = remove_file(&path);
+  assert!(metadata.contains("task: worker\n"), "{}", metadata);
+}
@@ -0,0 +1,213 @@
+// report.rs
+
+// Copyright (C) 2026 Daniel Mueller <deso@posteo.net>
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+//! Tests for the information recorded about crashes and the system.
+
+mod util;
+
+use std::env::temp_dir;
+use std::env::var_os;
+use std::fs::read_dir;
+use std::fs::read_to_string;
+use std::fs::remove_file;
+use std::os::raw::c_int;
+use std::panic::catch_unwind;
+use std::path::PathBuf;
+use std::process::id as pid;
+use std::process::Command;
+
+use libc::sighandler_t;
+use libc::signal;
+use libc::SIGQUIT;
+
+use coredump::check_previous_crash;
+use coredump::Builder;
+use coredump::Report;
+
+use util::ignore;
+
+const MARKER_PATH: &str = "MARKER_PATH";
+
+
+/// Run `test` in a child surviving the dump attempt and return the
+/// file it wrote with the given extension.
+fn crash_file(test: &str, extension: &str) -> PathBuf {
+  let mut child = util::child(test).spawn().unwrap();
+  let prefix = format!("coredump-{}-", child.id());
+  let rc = child.wait().unwrap();
+  assert!(rc.success());
+
+  read_dir(temp_dir())
+    .unwrap()
+    .map(|entry| entry.unwrap().path())
+    .find(|path| {
+      let name = path.file_name().unwrap().to_string_lossy();
+      name.starts_with(&prefix) && name.ends_with(extension)
+    })
+    .unwrap_or_else(|| panic!("{} file not found", extension))
+}
+
+
+/// Check that crash metadata are written on panic.
+#[test]
+fn write_metadata() {
+  if let Some(_guard) = util::parent() {
+    let path = crash_file("write_metadata", ".txt");
+    let metadata = read_to_string(&path).unwrap();
+    let _ = remove_file(&path);
+
+    assert!(metadata.contains("panic-message: induced panic\n"));
+    assert!(metadata.contains("panic-location: tests/report.rs:"));
+    assert!(metadata.contains("time-realtime: "));
+    assert!(metadata.contains("time-monotonic: "));
+    #[cfg(target_os = "linux")]
+    {
+      assert!(metadata.contains("process-start-time: "));
+      assert!(metadata.contains("process-uptime: "));
+      assert!(metadata.contains("core-pattern: "));
+    }
+  } else {
+    let _ = unsafe { signal(SIGQUIT, ignore as extern "C" fn(c_int) as sighandler_t) };
+    Builder::new().metadata(true).register().unwrap();
+
+    let _ = catch_unwind(|| panic!("induced panic"));
+  }
+}
+
+
+/// Check that a Markdown bug report is written on panic.
+#[test]
+fn write_bug_report() {
+  if let Some(_guard) = util::parent() {
+    let path = crash_file("write_bug_report", ".md");
+    let report = read_to_string(&path).unwrap();
+    let _ = remove_file(&path);
+
+    assert!(report.starts_with("## Crash report\n"));
+    assert!(report.contains(" 1.2.3\n"));
+    assert!(report.contains("```\ninduced panic\n```"));
+    assert!(report.contains("at `tests/report.rs:"));
+    assert!(report.contains("### Backtrace"));
+    assert!(report.contains("### Attachments"));
+  } else {
+    let _ = unsafe { signal(SIGQUIT, ignore as extern "C" fn(c_int) as sighandler_t) };
+    Builder::new()
+      .bug_report(Some("1.2.3".to_string()))
+      .register()
+      .unwrap();
+
+    let _ = catch_unwind(|| panic!("induced panic"));
+  }
+}
+
+
+/// Check that a crash marker written on panic is picked up by
+/// `check_previous_crash`.
+#[test]
+fn report_previous_crash() {
+  if let Some(_guard) = util::parent() {
+    let marker = temp_dir().join(format!("coredump-marker-{}", pid()));
+    let _ = remove_file(&marker);
+    assert_eq!(check_previous_crash(&marker).unwrap(), None);
+
+    let mut child = util::child("report_previous_crash")
+      .env(MARKER_PATH, &marker)
+      .spawn()
+      .unwrap();
+    let child_pid = child.id();
+    let rc = child.wait().unwrap();
+    assert!(rc.success());
+
+    let info = check_previous_crash(&marker).unwrap().unwrap();
+    assert_eq!(info.pid, child_pid);
+    assert!(
+      info.id.starts_with(&format!("{}-", child_pid)),
+      "{}",
+      info.id
+    );
+    assert_eq!(info.dump_dir, temp_dir());
+
+    let metadata = info.metadata.unwrap();
+    assert!(metadata.exists(), "{}", metadata.display());
+    let _ = remove_file(&metadata);
+
+    // The marker is consumed by the check.
+    assert!(!marker.exists());
+    assert_eq!(check_previous_crash(&marker).unwrap(), None);
+  } else {
+    let _ = unsafe { signal(SIGQUIT, ignore as extern "C" fn(c_int) as sighandler_t) };
+    Builder::new()
+      .metadata(true)
+      .crash_marker(var_os(MARKER_PATH).map(Into::into))
+      .register()
+      .unwrap();
+
+    let _ = catch_unwind(|| panic!("induced panic"));
+  }
+}
+
+
+/// Check that the `coredump-check` program reports the same
+/// information as the library, in both output formats.
+#[test]
+fn check_report() {
+  let report = Report::collect();
+  let program = env!("CARGO_BIN_EXE_coredump-check");
+
+  let output = Command::new(program).output().unwrap();
+  let text = String::from_utf8(output.stdout).unwrap();
+  assert_eq!(
+    output.status.code() == Some(0),
+    report.problems().is_empty()
+  );
+  assert!(text.contains("core file size limit: "), "{}", text);
+
+  let output = Command::new(program).arg("--json").output().unwrap();
+  let json = String::from_utf8(output.stdout).unwrap();
+  let json = json.trim_end();
+  assert!(json.starts_with('{') && json.ends_with('}'), "{}", json);
+  assert!(json.contains("\"registered\":false"), "{}", json);
+  if let Some(pattern) = &report.core_pattern {
+    assert!(
+      json.contains(&format!("\"core_pattern\":\"{}\"", pattern)),
+      "{}",
+      json
+    );
+  }
+
+  // A dry run never changes anything and so does not require
+  // privileges.
+  let output = Command::new(program)
+    .args(["--fix", "--dry-run"])
+    .output()
+    .unwrap();
+  let stderr = String::from_utf8(output.stderr).unwrap();
+  assert_ne!(output.status.code(), Some(2));
+  assert!(
+    stderr == "nothing to fix\n" || stderr.starts_with("would change "),
+    "{}",
+    stderr
+  );
+
+  let output = Command::new(program).arg("--bogus").output().unwrap();
+  assert_eq!(output.status.code(), Some(2));
+}
+
+
+/// Check that the report identifies the C library we got built for.
+#[test]
+#[cfg(target_os = "linux")]
+fn identify_libc() {
+  let report = Report::collect();
+  let libc = report.libc.unwrap();
+
+  if cfg!(target_env = "gnu") {
+    assert!(libc.starts_with("glibc 2."), "{}", libc);
+  } else if cfg!(target_env = "musl") {
+    assert_eq!(libc, "musl");
+  } else if cfg!(target_env = "uclibc") {
+    assert_eq!(libc, "uClibc-ng");
+  }
+}
@@ -0,0 +1,421 @@
+// system.rs
+
+// Copyright (C) 2026 Daniel Mueller <deso@posteo.net>
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+//! Tests for the interaction with system specific core dump
+//! facilities.
+
+mod util;
+
+
+/// The exit code signaling that the system is not configured for
+/// dumping core.
+#[cfg(not(target_os = "android"))]
+const UNSUPPORTED: i32 = 77;
+
+
+/// Check that core is dumped under the name predicted based on the
+/// system's naming scheme (e.g., `kern.corefile`, `<program>.core`, or
+/// `core`).
+#[test]
+#[cfg(any(
+  target_os = "macos",
+  target_os = "freebsd",
+  target_os = "netbsd",
+  target_os = "openbsd",
+  target_os = "illumos",
+  target_os = "solaris"
+))]
+fn dump_core_under_predicted_name() {
+  use std::fs::remove_file;
+  use std::io::stdout;
+  use std::io::Write as _;
+  use std::os::unix::process::ExitStatusExt;
+  use std::path::Path;
+  use std::process::exit;
+
+  use libc::SIGQUIT;
+
+  use coredump::Builder;
+
+  if let Some(_guard) = util::parent() {
+    let output = util::child("dump_core_under_predicted_name")
+      .output()
+      .unwrap();
+
+    // Core dumps may be disabled or the directory core files are
+    // written to (e.g., `/cores` on macOS) not be writable for us, in
+    // which case registration fails. There is nothing to test then.
+    if output.status.code() == Some(UNSUPPORTED) {
+      return
+    }
+
+    assert_eq!(output.status.signal(), Some(SIGQUIT));
+    assert!(output.status.core_dumped());
+
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let core_path = stdout
+      .lines()
+      .find_map(|line| line.strip_prefix("core-file: "));
+    if let Some(core_path) = core_path {
+      let core_file = Path::new(core_path);
+      assert!(
+        core_file.exists(),
+        "core file {} does not exist",
+        core_file.display(),
+      );
+      let _ = remove_file(core_file);
+    }
+  } else {
+    let registration = match Builder::new().register() {
+      Ok(registration) => registration,
+      Err(_) => exit(UNSUPPORTED),
+    };
+    // Report the predicted path of the core file to the parent. Output
+    // of the test harness may precede it on the same line.
+    if let Some(core_path) = registration.core_path() {
+      let _ = writeln!(stdout(), "\ncore-file: {}", core_path.display());
+    }
+    panic!("induced panic");
+  }
+}
+
+
+/// Check that panics are routed to debuggerd by means of SIGABRT.
+#[test]
+#[cfg(target_os = "android")]
+fn dump_tombstone() {
+  use std::os::unix::process::ExitStatusExt;
+
+  use libc::SIGABRT;
+
+  use coredump::register_panic_handler;
+
+  if let Some(_guard) = util::parent() {
+    let rc = util::child("dump_tombstone").status().unwrap();
+
+    assert_eq!(rc.signal(), Some(SIGABRT));
+  } else {
+    let () = register_panic_handler().unwrap();
+    panic!("induced panic");
+  }
+}
+
+
+/// Check that core is dumped at the location reported by
+/// `core_location`.
+#[test]
+#[cfg(target_os = "linux")]
+fn dump_core_at_reported_location() {
+  use std::env::temp_dir;
+  use std::fs::read_to_string;
+  use std::fs::remove_file;
+  use std::io::stdout;
+  use std::io::Write as _;
+  use std::os::unix::process::ExitStatusExt;
+  use std::path::Path;
+  use std::process::exit;
+
+  use libc::SIGQUIT;
+
+  use coredump::core_location;
+  use coredump::Builder;
+  use coredump::CoreLocation;
+
+  if let Some(_guard) = util::parent() {
+    let output = util::child("dump_core_at_reported_location")
+      .output()
+      .unwrap();
+
+    if output.status.code() == Some(UNSUPPORTED) {
+      return
+    }
+
+    assert_eq!(output.status.signal(), Some(SIGQUIT));
+
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let find = |prefix| stdout.lines().find_map(|line| line.strip_prefix(prefix));
+    if let Some(core_path) = find("core-file: ") {
+      assert!(output.status.core_dumped());
+      let core_file = Path::new(core_path);
+      assert!(
+        core_file.exists(),
+        "core file {} does not exist",
+        core_file.display(),
+      );
+      let _ = remove_file(core_file);
+    } else if let Some(command) = find("core-pipe: ") {
+      let pattern = read_to_string("/proc/sys/kernel/core_pattern").unwrap();
+      assert_eq!(pattern.trim().strip_prefix('|').unwrap(), command);
+    }
+    // Otherwise the location could not be determined, e.g., because
+    // the pattern contains a specifier we do not know about.
+  } else {
+    let _registration = match Builder::new().dir(Some(temp_dir())).register() {
+      Ok(registration) => registration,
+      Err(_) => exit(UNSUPPORTED),
+    };
+    // Report the location of the core file to the parent. As the dump
+    // happens in quick succession, a pattern containing the time of
+    // the dump is not expanded, lest we race with the clock. Output of
+    // the test harness may precede it on the same line.
+    match core_location(None) {
+      Some(CoreLocation::File(path)) => {
+        let _ = writeln!(stdout(), "\ncore-file: {}", path.display());
+      },
+      Some(CoreLocation::Pipe(command)) => {
+        let _ = writeln!(stdout(), "\ncore-pipe: {}", command);
+      },
+      _ => (),
+    }
+    panic!("induced panic");
+  }
+}
+
+
+/// Check that problems with the dump configuration are reported in
+/// forked children.
+#[test]
+#[cfg(target_os = "linux")]
+fn verify_in_forked_child() {
+  use std::env::temp_dir;
+  use std::fs::create_dir_all;
+  use std::fs::remove_dir;
+  use std::process::id as pid;
+  use std::ptr::null_mut;
+
+  use libc::_exit;
+  use libc::fork;
+  use libc::waitpid;
+
+  use coredump::Builder;
+
+  if let Some(_guard) = util::parent() {
+    let dir = temp_dir().join(format!("coredump-atfork-{}", pid()));
+    let () = create_dir_all(&dir).unwrap();
+
+    let output = util::child("verify_in_forked_child")
+      .env("TMPDIR", &dir)
+      .output()
+      .unwrap();
+    let _ = remove_dir(&dir);
+
+    assert!(output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    let expected = format!(
+      "coredump: dump directory {} is not accessible in forked child",
+      dir.display()
+    );
+    assert!(stderr.contains(&expected), "{}", stderr);
+  } else {
+    Builder::new().verify_on_fork(true).register().unwrap();
+    // Removing the dump directory invalidates the configuration.
+    let () = remove_dir(temp_dir()).unwrap();
+
+    let child = unsafe { fork() };
+    assert!(child >= 0);
+    if child == 0 {
+      unsafe { _exit(0) }
+    }
+    assert_eq!(unsafe { waitpid(child, null_mut(), 0) }, child);
+  }
+}
+
+
+/// Check that the configuration can be re-applied in a forked child,
+/// as is common when daemonizing.
+#[test]
+#[cfg(target_os = "linux")]
+fn reapply_in_forked_child() {
+  use libc::_exit;
+  use libc::alarm;
+  use libc::fork;
+  use libc::waitpid;
+  use libc::WEXITSTATUS;
+  use libc::WIFEXITED;
+
+  use coredump::reapply;
+  use coredump::Builder;
+
+  if util::isolate("reapply_in_forked_child") {
+    return
+  }
+
+  Builder::new().verify_on_fork(true).register().unwrap();
+
+  let child = unsafe { fork() };
+  assert!(child >= 0);
+  if child == 0 {
+    // Should we hang, make sure to not do so forever.
+    let _remaining = unsafe { alarm(10) };
+    let code = if reapply().is_ok() { 0 } else { 1 };
+    unsafe { _exit(code) }
+  }
+
+  let mut status = 0;
+  assert_eq!(unsafe { waitpid(child, &mut status, 0) }, child);
+  assert!(WIFEXITED(status), "{:#x}", status);
+  assert_eq!(WEXITSTATUS(status), 0);
+}
+
+
+/// Check that a failure to re-apply the configuration leaves the state
+/// verified in forked children unchanged.
+#[test]
+#[cfg(target_os = "linux")]
+fn keep_verification_on_failure() {
+  use std::env::temp_dir;
+  use std::fs::create_dir;
+  use std::fs::remove_dir;
+  use std::process::id as pid;
+  use std::ptr::null_mut;
+
+  use libc::_exit;
+  use libc::fork;
+  use libc::prctl;
+  use libc::waitpid;
+  use libc::PR_SET_DUMPABLE;
+
+  use coredump::reapply;
+  use coredump::Builder;
+
+  if let Some(_guard) = util::parent() {
+    let output = util::child("keep_verification_on_failure").output().unwrap();
+
+    assert!(output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    // The removed directory is still the one being verified.
+    assert!(stderr.contains("coredump-reapply-"), "{}", stderr);
+    assert!(stderr.contains("is not accessible in forked child"), "{}", stderr);
+  } else {
+    let dir = temp_dir().join(format!("coredump-reapply-{}", pid()));
+    let () = create_dir(&dir).unwrap();
+
+    Builder::new()
+      .dirs([dir.clone(), temp_dir()])
+      .verify_on_fork(true)
+      .register()
+      .unwrap();
+    // Re-applying picks the fallback directory, but fails eventually,
+    // because the process is not dumpable.
+    let () = remove_dir(&dir).unwrap();
+    assert_eq!(unsafe { prctl(PR_SET_DUMPABLE, 0) }, 0);
+    let _err = reapply().unwrap_err();
+    assert_eq!(unsafe { prctl(PR_SET_DUMPABLE, 1) }, 0);
+
+    let child = unsafe { fork() };
+    assert!(child >= 0);
+    if child == 0 {
+      unsafe { _exit(0) }
+    }
+    assert_eq!(unsafe { waitpid(child, null_mut(), 0) }, child);
+  }
+}
+
+
+/// Check that helper processes forked by us do not run the fork
+/// handler verifying the dump configuration.
+#[test]
+#[cfg(target_os = "linux")]
+fn skip_verification_in_watchdog() {
+  use std::os::raw::c_int;
+  use std::os::unix::process::ExitStatusExt;
+  use std::panic::catch_unwind;
+  use std::thread::sleep;
+  use std::time::Duration;
+
+  use libc::prctl;
+  use libc::sighandler_t;
+  use libc::signal;
+  use libc::PR_SET_DUMPABLE;
+  use libc::SIGKILL;
+  use libc::SIGQUIT;
+
+  use coredump::Builder;
+
+  if let Some(_guard) = util::parent() {
+    let output = util::child("skip_verification_in_watchdog")
+      .output()
+      .unwrap();
+
+    assert_eq!(output.status.signal(), Some(SIGKILL));
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(!stderr.contains("forked child"), "{}", stderr);
+  } else {
+    let _ = unsafe { signal(SIGQUIT, util::ignore as extern "C" fn(c_int) as sighandler_t) };
+    Builder::new()
+      .verify_on_fork(true)
+      .watchdog(Some(Duration::from_millis(100)))
+      .register()
+      .unwrap();
+    // A forked child running the verification would complain.
+    assert_eq!(unsafe { prctl(PR_SET_DUMPABLE, 0) }, 0);
+
+    let _ = catch_unwind(|| panic!("induced panic"));
+    // Linger around waiting for the watchdog to kill us.
+    sleep(Duration::from_secs(30));
+  }
+}
+
+
+/// Check that the core dump filter can be adjusted, both directly and
+/// as part of registration.
+#[test]
+#[cfg(target_os = "linux")]
+fn adjust_filter() {
+  use coredump::coredump_filter;
+  use coredump::set_coredump_filter;
+  use coredump::Builder;
+  use coredump::CoredumpFilter;
+
+  if util::isolate("adjust_filter") {
+    return
+  }
+
+  let original = coredump_filter().unwrap();
+
+  let filter = CoredumpFilter::default() | CoredumpFilter::FILE_PRIVATE;
+  let previous = set_coredump_filter(filter).unwrap();
+  assert_eq!(previous, original);
+  assert_eq!(coredump_filter().unwrap(), filter);
+
+  let filter = filter - CoredumpFilter::ANON_SHARED;
+  assert!(!filter.contains(CoredumpFilter::ANON_SHARED));
+  let _registration = Builder::new()
+    .coredump_filter(Some(filter))
+    .register()
+    .unwrap();
+  assert_eq!(coredump_filter().unwrap(), filter);
+
+  let _previous = set_coredump_filter(original).unwrap();
+  assert_eq!(coredump_filter().unwrap(), original);
+}
+
+
+/// Check that the process is marked as dumpable during registration
+/// and when re-applying the configuration, if requested.
+#[test]
+#[cfg(target_os = "linux")]
+fn force_dumpable() {
+  use libc::prctl;
+  use libc::PR_GET_DUMPABLE;
+  use libc::PR_SET_DUMPABLE;
+
+  use coredump::reapply;
+  use coredump::Builder;
+
+  if util::isolate("force_dumpable") {
+    return
+  }
+
+  assert_eq!(unsafe { prctl(PR_SET_DUMPABLE, 0) }, 0);
+  assert_eq!(unsafe { prctl(PR_GET_DUMPABLE) }, 0);
+
+  let _registration = Builder::new().force_dumpable(true).register().unwrap();
+  assert_eq!(unsafe { prctl(PR_GET_DUMPABLE) }, 1);
+
+  assert_eq!(unsafe { prctl(PR_SET_DUMPABLE, 0) }, 0);
+  let () = reapply().unwrap();
+  assert_eq!(unsafe { prctl(PR_GET_DUMPABLE) }, 1);
+}
@@ -0,0 +1,137 @@
+// trigger.rs
+
+// Copyright (C) 2026 Daniel Mueller <deso@posteo.net>
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+//! Tests for dumping core on demand, without a panic being involved.
+
+#![cfg(target_os = "linux")]
+
+mod util;
+
+use std::fs::remove_file;
+use std::os::unix::process::ExitStatusExt;
+
+use libc::SIGQUIT;
+
+use coredump::core_assert;
+use coredump::core_assert_eq;
+use coredump::core_assert_ne;
+use coredump::ensure_core;
+use coredump::trigger_core_dump;
+
+
+/// Check that a core can be dumped on demand, both with and without
+/// terminating the process.
+#[test]
+fn trigger() {
+  if let Some(_guard) = util::parent() {
+    let core_file = match util::core_file() {
+      Some(core_file) => core_file,
+      None => return,
+    };
+
+    let rc = util::child("trigger").status().unwrap();
+
+    assert!(!rc.success());
+    assert_eq!(rc.signal().unwrap(), SIGQUIT);
+    let _ = remove_file(&core_file);
+  } else {
+    let core_path = trigger_core_dump(false).unwrap().unwrap();
+    assert!(
+      core_path.exists(),
+      "core file {} does not exist",
+      core_path.display(),
+    );
+    let _ = remove_file(&core_path);
+
+    let _ = trigger_core_dump(true);
+    unreachable!()
+  }
+}
+
+
+/// Check that a failing assertion creates a core file and panics.
+#[test]
+fn assert_dumps_core() {
+  if let Some(_guard) = util::parent() {
+    let core_file = match util::core_file() {
+      Some(core_file) => core_file,
+      None => return,
+    };
+
+    let output = util::child("assert_dumps_core").output().unwrap();
+
+    assert!(!output.status.success());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("assertion `left == right` failed"));
+    let () = util::assert_core(&core_file);
+  } else {
+    core_assert!(true);
+    core_assert_ne!(1, 2, "{} and {} should differ", 1, 2);
+    core_assert_eq!(1, 2);
+  }
+}
+
+
+/// Check that a violated invariant creates a core file without
+/// terminating the program or leaving the core file size limit raised.
+#[test]
+fn ensure_dumps_core() {
+  use libc::getrlimit;
+  use libc::rlimit;
+  use libc::setrlimit;
+  use libc::RLIMIT_CORE;
+
+  if util::isolate("ensure_dumps_core") {
+    return
+  }
+
+  let core_file = match util::core_file() {
+    Some(core_file) => core_file,
+    None => return,
+  };
+
+  let mut limit = rlimit {
+    rlim_cur: 0,
+    rlim_max: 0,
+  };
+  assert_eq!(unsafe { getrlimit(RLIMIT_CORE, &mut limit) }, 0);
+  limit.rlim_cur = 0;
+  assert_eq!(unsafe { setrlimit(RLIMIT_CORE, &limit) }, 0);
+
+  assert!(ensure_core!(1 + 1 == 2));
+  assert!(!core_file.exists());
+
+  assert!(!ensure_core!(1 + 1 == 3, "math is {}", "broken"));
+  let () = util::assert_core(&core_file);
+
+  assert_eq!(unsafe { getrlimit(RLIMIT_CORE, &mut limit) }, 0);
+  assert_eq!(limit.rlim_cur, 0);
+}
+
+
+/// Check that exiting with a status registered as abnormal creates a
+/// core file.
+#[test]
+#[cfg(target_env = "gnu")]
+fn dump_on_exit() {
+  use std::process::exit;
+
+  use coredump::dump_on_abnormal_exit;
+
+  if let Some(_guard) = util::parent() {
+    let core_file = match util::core_file() {
+      Some(core_file) => core_file,
+      None => return,
+    };
+
+    let rc = util::child("dump_on_exit").status().unwrap();
+
+    assert_eq!(rc.code(), Some(3));
+    let () = util::assert_core(&core_file);
+  } else {
+    dump_on_abnormal_exit(&[2, 3]).unwrap();
+    exit(3);
+  }
+}
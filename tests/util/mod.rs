@@ -0,0 +1,122 @@
+// util/mod.rs
+
+// Copyright (C) 2026 Daniel Mueller <deso@posteo.net>
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+//! Harness shared by tests that invoke the test binary itself.
+//!
+//! Most tests induce a panic or crash and so have to run in a child
+//! process of their own. The parent re-executes the test binary with
+//! only the test in question selected and with [`CHILD_MARKER`] set
+//! in its environment, and checks how the child terminated.
+
+#![allow(dead_code)]
+
+use std::env::current_exe;
+use std::env::temp_dir;
+use std::env::var_os;
+use std::fs::read_to_string;
+use std::fs::remove_file;
+use std::io::ErrorKind;
+use std::path::Path;
+use std::path::PathBuf;
+use std::process::Command;
+use std::sync::Mutex;
+use std::sync::MutexGuard;
+use std::sync::PoisonError;
+
+use libc::c_int;
+
+/// The environment variable marking a re-executed child.
+pub const CHILD_MARKER: &str = "COREDUMP_TEST_CHILD";
+
+/// The lock serializing the parent side of tests.
+///
+/// Children dump core into a shared location and some tests adjust
+/// process wide state, so only one test of a binary may be in flight
+/// at any time.
+static LOCK: Mutex<()> = Mutex::new(());
+
+
+/// Check whether we run as the parent side of a test, returning a
+/// guard serializing it with all other tests of the binary if so.
+pub fn parent() -> Option<MutexGuard<'static, ()>> {
+  if var_os(CHILD_MARKER).is_none() {
+    Some(LOCK.lock().unwrap_or_else(PoisonError::into_inner))
+  } else {
+    None
+  }
+}
+
+/// Create a command re-executing the test binary with only `test`
+/// selected.
+pub fn child(test: &str) -> Command {
+  let mut command = Command::new(current_exe().unwrap());
+  let _ = command
+    .args(["--exact", test])
+    .env_clear()
+    .env(CHILD_MARKER, "true");
+  command
+}
+
+/// Run `test` in a child process of its own, isolating the process
+/// wide state it adjusts, and check that it succeeded.
+///
+/// Returns `true` on the parent side, in which case the caller is
+/// expected to return right away.
+pub fn isolate(test: &str) -> bool {
+  if let Some(_guard) = parent() {
+    let output = child(test).output().unwrap();
+    assert!(
+      output.status.success(),
+      "{}",
+      String::from_utf8_lossy(&output.stdout)
+    );
+    true
+  } else {
+    false
+  }
+}
+
+/// Retrieve the path of the core file dumped by a child, after making
+/// sure that no stale one exists.
+///
+/// Note that the core pattern presumably could be many things and half
+/// of them would make our tests not succeed. We just use it to do
+/// something slightly more clever than hardcoding "core", but
+/// ultimately are willing to only put so much effort into this...
+///
+/// A core file pattern beginning with a pipe symbol means that the core
+/// dump is actually redirected to a program. That's a system
+/// configuration we probably cannot and most likely do not want to
+/// change, so if the system is configured this way (systemd enabled
+/// systems likely are), `None` is returned and tests checking for a
+/// core file cannot run.
+pub fn core_file() -> Option<PathBuf> {
+  let core_pattern = read_to_string("/proc/sys/kernel/core_pattern").ok()?;
+  if core_pattern.starts_with('|') {
+    return None
+  }
+  let core_file = temp_dir().join(core_pattern.trim_end());
+
+  match remove_file(&core_file) {
+    Ok(()) => (),
+    Err(ref err) if err.kind() == ErrorKind::NotFound => (),
+    Err(err) => panic!("unexpected error: {}", err),
+  };
+  Some(core_file)
+}
+
+/// Remove the core file dumped by a child, if any.
+pub fn remove_core() {
+  let _ = core_file();
+}
+
+/// Check that a core file exists at `path` and remove it.
+pub fn assert_core(path: &Path) {
+  assert!(path.exists(), "core file {} does not exist", path.display());
+  let _ = remove_file(path);
+}
+
+/// A signal handler doing nothing.
+pub extern "C" fn ignore(_signal: c_int) {}